@@ -0,0 +1,61 @@
+extern crate sdl2;
+
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::AudioSubsystem;
+
+use chip8_rust::{AudioSink, Buzzer, BuzzerSettings, Chip8};
+
+const SAMPLE_RATE: i32 = 44100;
+
+/// Feeds the samples of a `Buzzer` to SDL
+pub struct BuzzerCallback {
+    buzzer: Buzzer,
+}
+
+impl AudioCallback for BuzzerCallback {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.buzzer.fill(out);
+    }
+}
+
+/// Plays the buzzer through the default SDL audio device
+pub struct SdlAudioSink {
+    device: AudioDevice<BuzzerCallback>,
+    muted: bool,
+}
+
+impl SdlAudioSink {
+    /// Opens the default audio device. Playback starts immediately and is silent until
+    /// `AudioSink::update` sees a running sound timer.
+    pub fn open(audio_subsystem: &AudioSubsystem, settings: BuzzerSettings) -> Result<Self, String> {
+        let desired_spec = AudioSpecDesired {
+            freq: Some(SAMPLE_RATE),
+            channels: Some(1),
+            samples: None,
+        };
+
+        let device = audio_subsystem.open_playback(None, &desired_spec, |spec| BuzzerCallback {
+            buzzer: Buzzer::new(settings, spec.freq as f32),
+        })?;
+        device.resume();
+
+        Ok(Self { device, muted: false })
+    }
+}
+
+impl AudioSink for SdlAudioSink {
+    fn update(&mut self, c8: &Chip8) {
+        self.device.lock().buzzer.sync(c8);
+    }
+
+    fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        self.device.lock().buzzer.set_muted(muted);
+    }
+
+    fn is_muted(&self) -> bool {
+        self.muted
+    }
+}
@@ -0,0 +1,702 @@
+//! Runs a ROM without a window, for CI and scripting.
+//!
+//! Exits with 0 when the run completes, 1 when the ROM cannot be loaded, the interpreter
+//! fails or a verified movie desyncs, and 2 on invalid arguments.
+
+use std::io::{BufRead, Write};
+
+use chip8_rust::emulator::debugger::{format_state, parse_address, parse_command, COMMAND_HELP};
+use chip8_rust::emulator::image::{encode, ImageFormat, MAX_SCALE};
+use chip8_rust::emulator::tracer::{TraceFilter, TraceFormat, Tracer};
+use chip8_rust::{Chip8, Chip8Error, DebugCommand, Debugger, Movie, Palette, Platform, Quirks};
+
+const USAGE: &str = "\
+Usage: chip8-headless [OPTIONS] <ROM>
+
+Arguments:
+  <ROM>                    Path to a binary or hex-text .ch8 ROM
+
+Options:
+  -p, --platform <NAME>    Platform to emulate: vip, chip48, schip or xochip
+  -q, --quirks <PROFILE>   Quirk profile: default, vip, chip48 or schip [default: default, or the platform's quirks]
+  -f, --ipf <N>            Instructions executed per frame [default: 4, or the platform's speed]
+  -n, --frames <N>         Number of 60 Hz frames to run [default: 600]
+      --stop-on-loop       Stop early once the program jumps to itself, the usual way to end a test ROM
+      --seed <N>           Seed for the random number generator [default: 0]
+      --press <FRAME:KEY[:FRAMES]>
+                           Hold hex key KEY from frame FRAME on, for FRAMES frames [default: 1].
+                           May be repeated
+  -o, --output <FILE>      Write the final display to FILE: a .png or .pbm image, or one text
+                           line per row for any other extension
+  -s, --scale <N>          Scale factor of image output from 1 to 64 [default: 1]
+      --debug              Start paused at a debugger prompt that reads commands from stdin
+  -b, --break <ADDR>       Pause at a debugger prompt when pc reaches hex address ADDR.
+                           May be repeated
+      --trace <FILE>       Write every executed instruction and the registers it changed to FILE
+      --trace-format <NAME>
+                           Trace format: text or binary [default: text]
+      --trace-range <START-END>
+                           Only trace instructions at hex addresses START to END. May be repeated
+      --trace-ops <CLASSES>
+                           Only trace opcodes starting with one of the comma-separated hex
+                           digits, e.g. 8,D
+      --trace-limit <N>    Stop tracing after N instructions
+      --verify <FILE>      Instead of running the ROM, play back a movie recorded with
+                           `chip8-rust --record` and check that it ends in the recorded state
+  -h, --help               Print this help message
+
+The program also stops when it executes the SUPER-CHIP exit instruction 00FD.";
+
+/// A key held down by the input script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct KeyPress {
+    frame: u32,
+    key: u8,
+    frames: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Config {
+    rom_path: String,
+    platform: Option<Platform>,
+    quirks: Quirks,
+    instructions_per_frame: u32,
+    frames: u32,
+    stop_on_loop: bool,
+    seed: u64,
+    presses: Vec<KeyPress>,
+    output_path: Option<String>,
+    scale: u32,
+    debug: bool,
+    breakpoints: Vec<u16>,
+    trace_path: Option<String>,
+    trace_format: TraceFormat,
+    trace_filter: TraceFilter,
+    verify_path: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Run(Box<Config>),
+    Help,
+}
+
+/// Why the run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StopReason {
+    FrameLimit,
+    Exited,
+    Loop,
+    /// The debugger prompt was closed
+    Quit,
+}
+
+fn main() {
+    let config = match parse_args(std::env::args().skip(1)) {
+        Ok(Command::Run(config)) => config,
+        Ok(Command::Help) => {
+            println!("{}", USAGE);
+            return;
+        },
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        },
+    };
+
+    if let Some(path) = &config.verify_path {
+        std::process::exit(verify_movie(path, &config.rom_path));
+    }
+
+    let loaded = match config.platform {
+        Some(platform) => {
+            let mut c8 = Chip8::with_platform(platform);
+            c8.load_rom_file(&config.rom_path).map(|_| c8)
+        },
+        None => Chip8::new(&config.rom_path),
+    };
+
+    let mut c8 = match loaded {
+        Ok(c8) => c8,
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", config.rom_path, e);
+            std::process::exit(1);
+        },
+    };
+    c8.set_quirks(config.quirks);
+    c8.seed_rng(config.seed);
+
+    let mut tracer = match &config.trace_path {
+        Some(path) => match Tracer::create(path, config.trace_format, config.trace_filter.clone(), c8.get_platform()) {
+            Ok(tracer) => Some(tracer),
+            Err(e) => {
+                eprintln!("Failed to start trace: {}", e);
+                std::process::exit(1);
+            },
+        },
+        None => None,
+    };
+    c8.set_tracing(tracer.is_some());
+
+    let (frames_run, mut result) = if config.debug || !config.breakpoints.is_empty() {
+        run_debugger(&mut c8, &config, &mut tracer, &mut std::io::stdin().lock())
+    } else {
+        run(&mut c8, &config, &mut tracer)
+    };
+    if let Some(Err(e)) = tracer.as_mut().map(|tracer| tracer.finish()) {
+        result = result.and(Err(e));
+    }
+
+    if let Some(path) = &config.output_path {
+        let display_colors = c8.get_display_colors();
+        let output = match ImageFormat::from_path(path) {
+            Some(format) => encode(format, &display_colors, config.scale as usize, &Palette::default()),
+            None => display_to_text(&display_colors).into_bytes(),
+        };
+        if let Err(e) = std::fs::write(path, output) {
+            eprintln!("Failed to write {}: {}", path, e);
+            std::process::exit(1);
+        }
+    }
+
+    print_state(&c8, frames_run);
+
+    match result {
+        Ok(reason) => println!("stopped: {}", match reason {
+            StopReason::FrameLimit => "frame limit",
+            StopReason::Exited => "exited",
+            StopReason::Loop => "loop",
+            StopReason::Quit => "quit",
+        }),
+        Err(e) => {
+            println!("stopped: error");
+            eprintln!("Emulation halted: {}", e);
+            std::process::exit(1);
+        },
+    }
+}
+
+/// Runs frames until the frame limit or a halt condition. Returns the number of frames run.
+fn run<W: Write>(c8: &mut Chip8, config: &Config, tracer: &mut Option<Tracer<W>>) -> (u32, Result<StopReason, Chip8Error>) {
+    for frame in 0..config.frames {
+        *c8.get_keypad() = keypad_at(&config.presses, frame);
+
+        let result = c8.run_frame(config.instructions_per_frame);
+        if let Err(e) = result.and(write_trace(c8, tracer)) {
+            return (frame + 1, Err(e));
+        }
+        if c8.has_exited() {
+            return (frame + 1, Ok(StopReason::Exited));
+        }
+        if config.stop_on_loop && is_jumping_to_itself(c8) {
+            return (frame + 1, Ok(StopReason::Loop));
+        }
+    }
+
+    (config.frames, Ok(StopReason::FrameLimit))
+}
+
+/// Like `run`, but under the debugger, reading commands from `input` whenever it is paused.
+/// Returns the number of frames started.
+fn run_debugger<W: Write, R: BufRead>(c8: &mut Chip8, config: &Config, tracer: &mut Option<Tracer<W>>, input: &mut R) -> (u32, Result<StopReason, Chip8Error>) {
+    let mut debugger = Debugger::new(config.instructions_per_frame);
+    for address in config.breakpoints.iter() {
+        debugger.add_breakpoint(*address);
+    }
+    if config.debug {
+        debugger.pause();
+        println!("{}", format_state(c8));
+    }
+
+    let mut frames_run = 0;
+    while frames_run < config.frames {
+        *c8.get_keypad() = keypad_at(&config.presses, frames_run);
+
+        let result = if debugger.is_paused() {
+            prompt(c8, &mut debugger, input)
+        } else {
+            let result = debugger.run_frame(c8).map(|completed| Some(u32::from(completed)));
+            if debugger.is_paused() {
+                println!("break at {:03X}\n{}", c8.get_pc(), format_state(c8));
+            }
+            result
+        };
+        let result = result.and_then(|frames| write_trace(c8, tracer).map(|_| frames));
+
+        let frames_started = |frames_run: u32| frames_run + u32::from(!debugger.is_at_frame_start());
+        match result {
+            Ok(Some(frames)) => frames_run += frames,
+            Ok(None) => return (frames_started(frames_run), Ok(StopReason::Quit)),
+            Err(e) => return (frames_run + 1, Err(e)),
+        }
+        if c8.has_exited() {
+            return (frames_started(frames_run), Ok(StopReason::Exited));
+        }
+        if config.stop_on_loop && !debugger.is_paused() && is_jumping_to_itself(c8) {
+            return (frames_started(frames_run), Ok(StopReason::Loop));
+        }
+    }
+
+    (frames_run, Ok(StopReason::FrameLimit))
+}
+
+/// Reads and applies one debugger command. Returns the number of frames completed,
+/// or `None` to quit.
+fn prompt<R: BufRead>(c8: &mut Chip8, debugger: &mut Debugger, input: &mut R) -> Result<Option<u32>, Chip8Error> {
+    print!("(chip8) ");
+    let _ = std::io::stdout().flush();
+
+    let mut line = String::new();
+    match input.read_line(&mut line) {
+        Ok(0) | Err(_) => return Ok(None),    // end of input
+        Ok(_) => {},
+    }
+    if line.trim().is_empty() {
+        return Ok(Some(0));
+    }
+
+    let command = match parse_command(&line) {
+        Ok(command) => command,
+        Err(e) => {
+            println!("{}", e);
+            return Ok(Some(0));
+        },
+    };
+
+    let mut frames = 0;
+    match command {
+        DebugCommand::Continue => debugger.resume(),
+        DebugCommand::Step(count) => {
+            for _ in 0..count {
+                frames += u32::from(debugger.step(c8)?);
+            }
+            println!("{}", format_state(c8));
+        },
+        DebugCommand::StepOver => {
+            frames += u32::from(debugger.step_over(c8)?);
+            if debugger.is_paused() {
+                println!("{}", format_state(c8));
+            }
+        },
+        DebugCommand::StepOut => {
+            if !debugger.step_out(c8) {
+                println!("not inside a subroutine");
+            }
+        },
+        DebugCommand::Break(address) => {
+            let address = address.unwrap_or(c8.get_pc());
+            debugger.add_breakpoint(address);
+            println!("breakpoint at {:03X}", address);
+        },
+        DebugCommand::Delete(address) => {
+            if !debugger.remove_breakpoint(address) {
+                println!("no breakpoint at {:03X}", address);
+            }
+        },
+        DebugCommand::Info => {
+            let breakpoints: Vec<String> = debugger.get_breakpoints().iter().map(|address| format!("{:03X}", address)).collect();
+            println!("{}\nbreakpoints: {}", format_state(c8), breakpoints.join(" "));
+        },
+        DebugCommand::Help => println!("{}", COMMAND_HELP),
+        DebugCommand::Quit => return Ok(None),
+    }
+
+    Ok(Some(frames))
+}
+
+/// Plays back the movie at `movie_path` and reports whether it ends in the recorded state.
+/// Returns the exit code.
+fn verify_movie(movie_path: &str, rom_path: &str) -> i32 {
+    match Movie::verify_file(movie_path, rom_path) {
+        Ok(movie) => {
+            println!("{}: {} frames, final state matches", movie_path, movie.len());
+            0
+        },
+        Err(e) => {
+            eprintln!("{}: {}", movie_path, e);
+            1
+        },
+    }
+}
+
+/// Writes the instructions executed since the last call to the trace, and stops tracing
+/// once the trace is complete
+fn write_trace<W: Write>(c8: &mut Chip8, tracer: &mut Option<Tracer<W>>) -> Result<(), Chip8Error> {
+    if let Some(tracer) = tracer {
+        tracer.record(&c8.take_trace())?;
+        if tracer.is_done() {
+            c8.set_tracing(false);
+        }
+    }
+
+    Ok(())
+}
+
+/// Keypad state during `frame` according to the input script
+fn keypad_at(presses: &[KeyPress], frame: u32) -> [u8; 16] {
+    let mut keypad = [0; 16];
+    for press in presses.iter() {
+        if frame >= press.frame && frame - press.frame < press.frames {
+            keypad[usize::from(press.key)] = 1;
+        }
+    }
+    keypad
+}
+
+/// Whether the next instruction is a `1nnn` jump to its own address
+fn is_jumping_to_itself(c8: &Chip8) -> bool {
+    let pc = usize::from(c8.get_pc());
+    match c8.get_memory().get(pc..pc + 2) {
+        Some(bytes) => u16::from_be_bytes([bytes[0], bytes[1]]) == 0x1000 | c8.get_pc(),
+        None => false,
+    }
+}
+
+/// One line per row; `.` for pixels that are off and `#` for pixels on in plane 1.
+/// XO-CHIP pixels on only in plane 2 are `+`, and `@` in both planes.
+fn display_to_text(display_colors: &[Vec<u8>]) -> String {
+    let mut text = String::new();
+    for row in display_colors.iter() {
+        for color_index in row.iter() {
+            text.push(match color_index & 0b11 {
+                0b00 => '.',
+                0b01 => '#',
+                0b10 => '+',
+                _ => '@',
+            });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn print_state(c8: &Chip8, frames_run: u32) {
+    let registers: Vec<String> = c8.get_registers().iter().map(|value| format!("{:02X}", value)).collect();
+    let stack: Vec<String> = c8.get_stack()[..usize::from(c8.get_sp())].iter().map(|address| format!("{:03X}", address)).collect();
+
+    println!("frames: {}", frames_run);
+    println!("pc: {:03X}", c8.get_pc());
+    println!("i: {:03X}", c8.get_index_register());
+    println!("v: {}", registers.join(" "));
+    println!("sp: {}", c8.get_sp());
+    println!("stack: {}", stack.join(" "));
+    println!("delay_timer: {}", c8.get_delay_timer());
+    println!("sound_timer: {}", c8.get_sound_timer());
+    println!("state_hash: {:016X}", c8.get_state_hash());
+}
+
+fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut args = args.into_iter();
+
+    let mut rom_path = None;
+    let mut platform = None;
+    let mut quirks = None;
+    let mut instructions_per_frame = None;
+    let mut frames = 600;
+    let mut stop_on_loop = false;
+    let mut seed = 0;
+    let mut presses = Vec::new();
+    let mut output_path = None;
+    let mut scale = 1;
+    let mut debug = false;
+    let mut breakpoints = Vec::new();
+    let mut trace_path = None;
+    let mut trace_format = None;
+    let mut trace_filter = TraceFilter::default();
+    let mut verify_path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-p" | "--platform" => {
+                let value = next_value(&arg, &mut args)?;
+                platform = Some(Platform::from_name(&value).ok_or_else(|| {
+                    format!("option '{}' expects one of {}, got '{}'", arg, Platform::NAMES.join(", "), value)
+                })?);
+            },
+            "-q" | "--quirks" => {
+                let value = next_value(&arg, &mut args)?;
+                quirks = Some(Quirks::from_name(&value).ok_or_else(|| {
+                    format!("option '{}' expects one of {}, got '{}'", arg, Quirks::NAMES.join(", "), value)
+                })?);
+            },
+            "-f" | "--ipf" => instructions_per_frame = Some(parse_positive(&arg, next_value(&arg, &mut args)?)?),
+            "-n" | "--frames" => frames = parse_positive(&arg, next_value(&arg, &mut args)?)?,
+            "--stop-on-loop" => stop_on_loop = true,
+            "--seed" => {
+                let value = next_value(&arg, &mut args)?;
+                seed = value.parse::<u64>()
+                    .map_err(|_| format!("option '{}' expects an unsigned 64-bit integer, got '{}'", arg, value))?;
+            },
+            "--press" => presses.push(parse_press(&arg, next_value(&arg, &mut args)?)?),
+            "-o" | "--output" => output_path = Some(next_value(&arg, &mut args)?),
+            "-s" | "--scale" => scale = parse_scale(&arg, next_value(&arg, &mut args)?)?,
+            "--debug" => debug = true,
+            "-b" | "--break" => {
+                let value = next_value(&arg, &mut args)?;
+                breakpoints.push(parse_address(&value)
+                    .ok_or_else(|| format!("option '{}' expects a hex address, got '{}'", arg, value))?);
+            },
+            "--trace" => trace_path = Some(next_value(&arg, &mut args)?),
+            "--verify" => verify_path = Some(next_value(&arg, &mut args)?),
+            "--trace-format" => {
+                let value = next_value(&arg, &mut args)?;
+                trace_format = Some(TraceFormat::from_name(&value).ok_or_else(|| {
+                    format!("option '{}' expects one of {}, got '{}'", arg, TraceFormat::NAMES.join(", "), value)
+                })?);
+            },
+            "--trace-range" => {
+                let value = next_value(&arg, &mut args)?;
+                trace_filter.address_ranges.push(TraceFilter::parse_range(&value)
+                    .ok_or_else(|| format!("option '{}' expects a hex address range START-END, got '{}'", arg, value))?);
+            },
+            "--trace-ops" => {
+                let value = next_value(&arg, &mut args)?;
+                trace_filter.opcode_classes = TraceFilter::parse_classes(&value)
+                    .ok_or_else(|| format!("option '{}' expects comma-separated hex digits, got '{}'", arg, value))?;
+            },
+            "--trace-limit" => trace_filter.limit = Some(u64::from(parse_positive(&arg, next_value(&arg, &mut args)?)?)),
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ => {
+                if rom_path.is_some() {
+                    return Err(format!("unexpected argument '{}'", arg));
+                }
+                rom_path = Some(arg);
+            },
+        }
+    }
+
+    let rom_path = match rom_path {
+        Some(path) => path,
+        None => return Err(String::from("missing required argument <ROM>")),
+    };
+    if trace_path.is_none() && (trace_format.is_some() || trace_filter != TraceFilter::default()) {
+        return Err(String::from("the --trace-* options require --trace"));
+    }
+    // a movie brings its own platform, quirks, speed, seed and input
+    if verify_path.is_some() && (debug || !breakpoints.is_empty() || trace_path.is_some()) {
+        return Err(String::from("option '--verify' cannot be combined with '--debug', '--break' or '--trace'"));
+    }
+
+    Ok(Command::Run(Box::new(Config {
+        rom_path,
+        platform,
+        quirks: quirks.unwrap_or_else(|| platform.map_or(Quirks::default(), |platform| platform.quirks())),
+        instructions_per_frame: instructions_per_frame.unwrap_or_else(|| platform.map_or(4, |platform| platform.instructions_per_frame())),
+        frames,
+        stop_on_loop,
+        seed,
+        presses,
+        output_path,
+        scale,
+        debug,
+        breakpoints,
+        trace_path,
+        trace_format: trace_format.unwrap_or(TraceFormat::Text),
+        trace_filter,
+        verify_path,
+    })))
+}
+
+fn next_value<I: Iterator<Item = String>>(option: &str, args: &mut I) -> Result<String, String> {
+    match args.next() {
+        Some(value) => Ok(value),
+        None => Err(format!("option '{}' requires a value", option)),
+    }
+}
+
+fn parse_scale(option: &str, value: String) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(n) if (1..=MAX_SCALE).contains(&n) => Ok(n),
+        _ => Err(format!("option '{}' expects a scale factor from 1 to {}, got '{}'", option, MAX_SCALE, value)),
+    }
+}
+
+fn parse_positive(option: &str, value: String) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("option '{}' expects a positive integer, got '{}'", option, value)),
+    }
+}
+
+/// Parses `FRAME:KEY[:FRAMES]`, with `KEY` a hex digit
+fn parse_press(option: &str, value: String) -> Result<KeyPress, String> {
+    let error = || format!("option '{}' expects FRAME:KEY[:FRAMES], got '{}'", option, value);
+
+    let parts: Vec<&str> = value.split(':').collect();
+    if parts.len() < 2 || parts.len() > 3 {
+        return Err(error());
+    }
+
+    let frame = parts[0].parse::<u32>().map_err(|_| error())?;
+    let key = match u8::from_str_radix(parts[1], 16) {
+        Ok(key) if key <= 0xF => key,
+        _ => return Err(error()),
+    };
+    let frames = match parts.get(2) {
+        Some(frames) => frames.parse::<u32>().map_err(|_| error())?,
+        None => 1,
+    };
+
+    Ok(KeyPress { frame, key, frames })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn no_trace() -> Option<Tracer<Vec<u8>>> {
+        None
+    }
+
+    fn config(rom: &str) -> Config {
+        match parse_args(args(&[rom])).unwrap() {
+            Command::Run(config) => *config,
+            Command::Help => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let command = parse_args(args(&[
+            "-p", "schip", "-n", "120", "--stop-on-loop", "--seed", "9", "--press", "10:a", "--press", "20:5:30", "-o", "out.png", "-s", "4", "--debug", "-b", "0x2A0", "game.ch8",
+        ])).unwrap();
+
+        let expected = Config {
+            rom_path: String::from("game.ch8"),
+            platform: Some(Platform::SuperChip),
+            quirks: Quirks::schip(),
+            instructions_per_frame: 30,
+            frames: 120,
+            stop_on_loop: true,
+            seed: 9,
+            presses: vec![
+                KeyPress { frame: 10, key: 0xA, frames: 1 },
+                KeyPress { frame: 20, key: 0x5, frames: 30 },
+            ],
+            output_path: Some(String::from("out.png")),
+            scale: 4,
+            debug: true,
+            breakpoints: vec![0x2A0],
+            trace_path: None,
+            trace_format: TraceFormat::Text,
+            trace_filter: TraceFilter::default(),
+            verify_path: None,
+        };
+        assert_eq!(command, Command::Run(Box::new(expected)));
+
+        let command = parse_args(args(&["--verify", "run.c8m", "game.ch8"])).unwrap();
+        assert!(matches!(command, Command::Run(config) if config.verify_path.as_deref() == Some("run.c8m")));
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert!(parse_args(args(&[])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--frames", "0"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--press", "10"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--press", "10:G"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--press", "10:1:2:3"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--break", "0x"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--trace-limit", "10"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--scale", "0"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--scale", "65"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--verify", "run.c8m", "--debug"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--trace", "t.txt", "--trace-ops", "G"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--trace", "t.txt", "--trace-format", "json"])).is_err());
+    }
+
+    #[test]
+    fn test_keypad_at() {
+        let presses = [KeyPress { frame: 2, key: 0x1, frames: 2 }, KeyPress { frame: 3, key: 0xF, frames: 1 }];
+
+        assert_eq!(keypad_at(&presses, 1), [0; 16]);
+        assert_eq!(keypad_at(&presses, 2)[0x1], 1);
+        assert_eq!(keypad_at(&presses, 3)[0x1], 1);
+        assert_eq!(keypad_at(&presses, 3)[0xF], 1);
+        assert_eq!(keypad_at(&presses, 4), [0; 16]);
+    }
+
+    #[test]
+    fn test_run_stops_on_loop() {
+        // 0x200: V0 = 5; 0x202: jump to 0x202
+        let mut c8 = Chip8::from_rom_bytes(&[0x60, 0x05, 0x12, 0x02]).unwrap();
+        let mut config = config("game.ch8");
+        config.stop_on_loop = true;
+
+        let (frames_run, result) = run(&mut c8, &config, &mut no_trace());
+
+        assert_eq!(frames_run, 1);
+        assert_eq!(result, Ok(StopReason::Loop));
+        assert_eq!(c8.get_registers()[0], 5);
+    }
+
+    #[test]
+    fn test_run_reports_errors() {
+        // 0x200: return with an empty stack
+        let mut c8 = Chip8::from_rom_bytes(&[0x00, 0xEE]).unwrap();
+
+        let (frames_run, result) = run(&mut c8, &config("game.ch8"), &mut no_trace());
+
+        assert_eq!(frames_run, 1);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_run_writes_trace() {
+        // 0x200: V0 = 5; 0x202: V1 = 6; 0x204: jump to 0x204
+        let mut c8 = Chip8::from_rom_bytes(&[0x60, 0x05, 0x61, 0x06, 0x12, 0x04]).unwrap();
+        let mut config = config("game.ch8");
+        config.frames = 2;
+        config.trace_filter.limit = Some(3);
+        let mut tracer = Some(Tracer::new(Vec::new(), "trace.txt", TraceFormat::Text, config.trace_filter.clone(), None).unwrap());
+        c8.set_tracing(true);
+
+        let (frames_run, result) = run(&mut c8, &config, &mut tracer);
+        assert_eq!(frames_run, 2);
+        assert!(result.is_ok());
+
+        let trace = String::from_utf8(tracer.unwrap().get_writer().clone()).unwrap();
+        assert_eq!(trace.lines().map(|line| &line[..4]).collect::<Vec<_>>(), ["0200", "0202", "0204"]);
+        assert!(!c8.is_tracing());
+    }
+
+    #[test]
+    fn test_run_debugger() {
+        // 0x200: V0 += 1; 0x202: call 0x208; 0x204: V2 += 1; 0x206: jump to 0x200
+        // 0x208: V1 += 1; 0x20A: return
+        let rom = [0x70, 0x01, 0x22, 0x08, 0x72, 0x01, 0x12, 0x00, 0x71, 0x01, 0x00, 0xEE];
+        let mut c8 = Chip8::from_rom_bytes(&rom).unwrap();
+        let mut config = config("game.ch8");
+        config.breakpoints = vec![0x208];
+
+        let mut input = "s 2\nbogus\nfinish\n\nq\n".as_bytes();
+        let (frames_run, result) = run_debugger(&mut c8, &config, &mut no_trace(), &mut input);
+
+        assert_eq!(frames_run, 1);
+        assert_eq!(result, Ok(StopReason::Quit));
+        assert_eq!(c8.get_pc(), 0x204);
+        assert_eq!(c8.get_registers()[..3], [1, 1, 0]);
+    }
+
+    #[test]
+    fn test_run_debugger_without_input() {
+        let mut c8 = Chip8::from_rom_bytes(&[0x60, 0x05, 0x12, 0x02]).unwrap();
+        let mut config = config("game.ch8");
+        config.debug = true;
+
+        let (frames_run, result) = run_debugger(&mut c8, &config, &mut no_trace(), &mut "".as_bytes());
+
+        assert_eq!(frames_run, 0);
+        assert_eq!(result, Ok(StopReason::Quit));
+        assert_eq!(c8.get_pc(), 0x200);
+    }
+
+    #[test]
+    fn test_display_to_text() {
+        assert_eq!(display_to_text(&[vec![0, 1], vec![2, 3]]), ".#\n+@\n");
+    }
+}
@@ -0,0 +1,691 @@
+use chip8_rust::emulator::debugger::parse_address;
+use chip8_rust::emulator::disassembler::Syntax;
+use chip8_rust::emulator::image::MAX_SCALE;
+use chip8_rust::emulator::tracer::{TraceFilter, TraceFormat};
+use chip8_rust::{BuzzerSettings, Platform, Quirks, Waveform};
+
+pub const USAGE: &str = "\
+Usage: chip8-rust [OPTIONS] <ROM>
+       chip8-rust disasm [OPTIONS] <ROM>
+       chip8-rust asm [OPTIONS] <SOURCE>
+
+Commands:
+  disasm                   Print a ROM as assembly source, see `chip8-rust disasm --help`
+  asm                      Build a ROM from assembly source, see `chip8-rust asm --help`
+
+Arguments:
+  <ROM>                    Path to a binary or hex-text .ch8 ROM
+
+Options:
+  -p, --platform <NAME>    Platform to emulate: vip, chip48, schip or xochip. Selects the
+                           memory size, stack depth, font, quirks and speed of that platform
+  -s, --scale <N>          Window scale factor from 1 to 64, applied to the platform's highest
+                           resolution [default: 12]
+  -f, --ipf <N>            Instructions executed per 60 Hz frame [default: 4, or the platform's speed]
+  -i, --ips <N>            Instructions executed per second, rounded to a whole number per frame
+      --speed <FACTOR>     Emulation speed multiplier from 0.01 to 100, e.g. 0.5 or 2 [default: 1]
+  -q, --quirks <PROFILE>   Quirk profile: default, vip, chip48 or schip [default: default, or the platform's quirks]
+      --fg <RRGGBB>        Foreground (pixel on) color [default: FFFFFF]
+      --bg <RRGGBB>        Background (pixel off) color [default: 000000]
+      --fg2 <RRGGBB>       XO-CHIP second plane color [default: FF6600]
+      --blend <RRGGBB>     XO-CHIP color of pixels on in both planes [default: 662200]
+      --seed <N>           Seed for the random number generator, to make runs reproducible
+      --record <FILE>      Record the keypad input of this run to a movie file
+      --play <FILE>        Play back a movie recorded with --record, then hand control back
+      --verify <FILE>      Play back a movie without opening a window and check that it ends
+                           in the recorded state
+      --capture <PATH>     Capture the display from the start: an animated GIF if PATH ends in
+                           .gif, otherwise a directory of numbered PNG frames
+  -k, --keymap <FILE>      Key map file with one `<SDL key name> <CHIP-8 key>` pair per line
+      --tone <HZ>          Frequency of the buzzer [default: 440]
+      --waveform <NAME>    Buzzer waveform: square, triangle, sawtooth or sine [default: square]
+      --volume <PERCENT>   Buzzer volume from 0 to 100 [default: 25]
+  -m, --mute               Start with sound muted; press M to toggle
+      --rewind-depth <N>   Number of frames that can be rewound [default: 600]
+      --rewind-memory <MB> Memory the rewind buffer may use, in MiB [default: 64]
+      --debug              Start paused in the debugger
+  -b, --break <ADDR>       Pause in the debugger when pc reaches hex address ADDR. May be repeated
+      --trace <FILE>       Write every executed instruction and the registers it changed to FILE
+      --trace-format <NAME>
+                           Trace format: text or binary [default: text]
+      --trace-range <START-END>
+                           Only trace instructions at hex addresses START to END. May be repeated
+      --trace-ops <CLASSES>
+                           Only trace opcodes starting with one of the comma-separated hex
+                           digits, e.g. 8,D
+      --trace-limit <N>    Stop tracing after N instructions
+  -h, --help               Print this help message
+
+Hotkeys:
+  Tab                      Toggle fast-forward
+  `                        Toggle slow motion
+  Backspace (hold)         Rewind
+  F1-F10                   Load the state saved in slot 1-10
+  Shift+F1-F10             Save the state to slot 1-10, next to the ROM
+  F11                      Start or stop capturing an animated GIF next to the ROM
+  F12                      Save a screenshot next to the ROM, at the window scale
+  M                        Toggle sound
+  P                        Pause or resume; pausing prints the registers, stack and timers
+  N                        While paused, execute a single instruction
+  O                        While paused, step over a subroutine call
+  U                        While paused, run until the current subroutine returns
+  B                        Toggle a breakpoint at the current pc
+  Escape                   Quit";
+
+/// Range of `--speed`; the frame pacer cannot represent much slower or faster frames
+const MIN_SPEED: f64 = 0.01;
+const MAX_SPEED: f64 = 100.0;
+
+pub const DISASM_USAGE: &str = "\
+Usage: chip8-rust disasm [OPTIONS] <ROM>
+
+Prints the instructions of a ROM, with the address and raw bytes of each in a comment.
+Jump and call targets are labeled; words that are not instructions are written as data.
+
+Arguments:
+  <ROM>                    Path to a binary or hex-text .ch8 ROM
+
+Options:
+  -p, --platform <NAME>    Platform whose instruction set to decode: vip, chip48, schip or
+                           xochip [default: CHIP-8 only]
+      --syntax <NAME>      Assembly syntax: octo or classic [default: octo]
+  -o, --output <FILE>      Write the listing to FILE instead of standard output
+  -h, --help               Print this help message";
+
+pub const ASM_USAGE: &str = "\
+Usage: chip8-rust asm [OPTIONS] <SOURCE>
+
+Assembles a program written with the classic (Cowgod) mnemonics into a ROM loaded at 0x200.
+Supports labels, `NAME = value` constants, DB/DW data, SPRITE bitmaps and INCLUDE \"file\";
+see the documentation of the assembler module for the full syntax.
+
+Arguments:
+  <SOURCE>                 Path to the assembly source
+
+Options:
+  -o, --output <FILE>      Write the ROM to FILE [default: SOURCE with a .ch8 extension]
+  -l, --listing <FILE>     Also write a listing of the address and bytes of each source line
+  -h, --help               Print this help message";
+
+/// Settings for a single emulator run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub rom_path: String,
+    pub platform: Option<Platform>,
+    pub scale: u32,
+    pub instructions_per_frame: u32,
+    pub speed: f64,
+    pub quirks: Quirks,
+    pub foreground: u32,
+    pub background: u32,
+    pub plane2_color: u32,
+    pub blend_color: u32,
+    pub seed: Option<u64>,
+    pub record_path: Option<String>,
+    pub play_path: Option<String>,
+    pub verify_path: Option<String>,
+    pub capture_path: Option<String>,
+    pub keymap_path: Option<String>,
+    pub buzzer: BuzzerSettings,
+    pub mute: bool,
+    pub rewind_depth: u32,
+    /// Rewind buffer budget in MiB
+    pub rewind_memory: u32,
+    pub debug: bool,
+    pub breakpoints: Vec<u16>,
+    pub trace_path: Option<String>,
+    pub trace_format: TraceFormat,
+    pub trace_filter: TraceFilter,
+}
+
+/// Settings for the `disasm` subcommand
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisasmConfig {
+    pub rom_path: String,
+    pub platform: Option<Platform>,
+    pub syntax: Syntax,
+    pub output_path: Option<String>,
+}
+
+/// Settings for the `asm` subcommand
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmConfig {
+    pub source_path: String,
+    pub output_path: String,
+    pub listing_path: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run(Box<Config>),
+    Disassemble(DisasmConfig),
+    Assemble(AsmConfig),
+    Help,
+    DisasmHelp,
+    AsmHelp,
+}
+
+impl Config {
+    fn with_rom(rom_path: String) -> Self {
+        Self {
+            rom_path,
+            platform: None,
+            scale: 12,
+            instructions_per_frame: 4,
+            speed: 1.0,
+            quirks: Quirks::default(),
+            foreground: 0xFFFFFF,
+            background: 0x000000,
+            plane2_color: 0xFF6600,
+            blend_color: 0x662200,
+            seed: None,
+            record_path: None,
+            play_path: None,
+            verify_path: None,
+            capture_path: None,
+            keymap_path: None,
+            buzzer: BuzzerSettings::default(),
+            mute: false,
+            rewind_depth: 600,
+            rewind_memory: 64,
+            debug: false,
+            breakpoints: Vec::new(),
+            trace_path: None,
+            trace_format: TraceFormat::Text,
+            trace_filter: TraceFilter::default(),
+        }
+    }
+}
+
+/// Parses the command-line arguments, excluding the program name
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut args = args.into_iter().peekable();
+    if args.next_if(|arg| arg == "disasm").is_some() {
+        return parse_disasm_args(args);
+    }
+    if args.next_if(|arg| arg == "asm").is_some() {
+        return parse_asm_args(args);
+    }
+
+    let mut rom_path = None;
+    let mut platform = None;
+    let mut scale = None;
+    let mut instructions_per_frame = None;
+    let mut speed = None;
+    let mut quirks = None;
+    let mut foreground = None;
+    let mut background = None;
+    let mut plane2_color = None;
+    let mut blend_color = None;
+    let mut seed = None;
+    let mut record_path = None;
+    let mut play_path = None;
+    let mut verify_path = None;
+    let mut capture_path = None;
+    let mut keymap_path = None;
+    let mut frequency = None;
+    let mut waveform = None;
+    let mut volume = None;
+    let mut mute = false;
+    let mut rewind_depth = None;
+    let mut rewind_memory = None;
+    let mut debug = false;
+    let mut breakpoints = Vec::new();
+    let mut trace_path = None;
+    let mut trace_format = None;
+    let mut trace_filter = TraceFilter::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-p" | "--platform" => platform = Some(parse_platform(&arg, next_value(&arg, &mut args)?)?),
+            "-s" | "--scale" => scale = Some(parse_scale(&arg, next_value(&arg, &mut args)?)?),
+            "-f" | "--ipf" => instructions_per_frame = Some(parse_positive(&arg, next_value(&arg, &mut args)?)?),
+            "-i" | "--ips" => {
+                let instructions_per_second = parse_positive(&arg, next_value(&arg, &mut args)?)?;
+                instructions_per_frame = Some((instructions_per_second.saturating_add(30) / 60).max(1));
+            },
+            "--speed" => speed = Some(parse_speed(&arg, next_value(&arg, &mut args)?)?),
+            "-q" | "--quirks" => quirks = Some(parse_quirks(&arg, next_value(&arg, &mut args)?)?),
+            "--fg" => foreground = Some(parse_color(&arg, next_value(&arg, &mut args)?)?),
+            "--bg" => background = Some(parse_color(&arg, next_value(&arg, &mut args)?)?),
+            "--fg2" => plane2_color = Some(parse_color(&arg, next_value(&arg, &mut args)?)?),
+            "--blend" => blend_color = Some(parse_color(&arg, next_value(&arg, &mut args)?)?),
+            "--seed" => seed = Some(parse_seed(&arg, next_value(&arg, &mut args)?)?),
+            "--record" => record_path = Some(next_value(&arg, &mut args)?),
+            "--play" => play_path = Some(next_value(&arg, &mut args)?),
+            "--verify" => verify_path = Some(next_value(&arg, &mut args)?),
+            "--capture" => capture_path = Some(next_value(&arg, &mut args)?),
+            "-k" | "--keymap" => keymap_path = Some(next_value(&arg, &mut args)?),
+            "--tone" => frequency = Some(parse_positive(&arg, next_value(&arg, &mut args)?)?),
+            "--waveform" => waveform = Some(parse_waveform(&arg, next_value(&arg, &mut args)?)?),
+            "--volume" => volume = Some(parse_percent(&arg, next_value(&arg, &mut args)?)?),
+            "-m" | "--mute" => mute = true,
+            "--rewind-depth" => rewind_depth = Some(parse_positive(&arg, next_value(&arg, &mut args)?)?),
+            "--rewind-memory" => rewind_memory = Some(parse_positive(&arg, next_value(&arg, &mut args)?)?),
+            "--debug" => debug = true,
+            "-b" | "--break" => {
+                let value = next_value(&arg, &mut args)?;
+                breakpoints.push(parse_address(&value)
+                    .ok_or_else(|| format!("option '{}' expects a hex address, got '{}'", arg, value))?);
+            },
+            "--trace" => trace_path = Some(next_value(&arg, &mut args)?),
+            "--trace-format" => {
+                let value = next_value(&arg, &mut args)?;
+                trace_format = Some(TraceFormat::from_name(&value).ok_or_else(|| {
+                    format!("option '{}' expects one of {}, got '{}'", arg, TraceFormat::NAMES.join(", "), value)
+                })?);
+            },
+            "--trace-range" => {
+                let value = next_value(&arg, &mut args)?;
+                trace_filter.address_ranges.push(TraceFilter::parse_range(&value)
+                    .ok_or_else(|| format!("option '{}' expects a hex address range START-END, got '{}'", arg, value))?);
+            },
+            "--trace-ops" => {
+                let value = next_value(&arg, &mut args)?;
+                trace_filter.opcode_classes = TraceFilter::parse_classes(&value)
+                    .ok_or_else(|| format!("option '{}' expects comma-separated hex digits, got '{}'", arg, value))?;
+            },
+            "--trace-limit" => trace_filter.limit = Some(u64::from(parse_positive(&arg, next_value(&arg, &mut args)?)?)),
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ => {
+                if rom_path.is_some() {
+                    return Err(format!("unexpected argument '{}'", arg));
+                }
+                rom_path = Some(arg);
+            },
+        }
+    }
+
+    let rom_path = match rom_path {
+        Some(path) => path,
+        None => return Err(String::from("missing required argument <ROM>")),
+    };
+
+    if [&record_path, &play_path, &verify_path].iter().filter(|path| path.is_some()).count() > 1 {
+        return Err(String::from("options '--record', '--play' and '--verify' cannot be combined"));
+    }
+    // pausing in the middle of a frame would break the frame-by-frame input of a movie
+    if (debug || !breakpoints.is_empty()) && (record_path.is_some() || play_path.is_some()) {
+        return Err(String::from("options '--debug' and '--break' cannot be combined with '--record' or '--play'"));
+    }
+    if trace_path.is_none() && (trace_format.is_some() || trace_filter != TraceFilter::default()) {
+        return Err(String::from("the --trace-* options require --trace"));
+    }
+    if trace_path.is_some() && verify_path.is_some() {
+        return Err(String::from("options '--trace' and '--verify' cannot be combined"));
+    }
+
+    let mut config = Config::with_rom(rom_path);
+    if let Some(platform) = platform {
+        config.platform = Some(platform);
+        config.instructions_per_frame = platform.instructions_per_frame();
+        config.quirks = platform.quirks();
+    }
+    config.scale = scale.unwrap_or(config.scale);
+    config.instructions_per_frame = instructions_per_frame.unwrap_or(config.instructions_per_frame);
+    config.speed = speed.unwrap_or(config.speed);
+    config.quirks = quirks.unwrap_or(config.quirks);
+    config.foreground = foreground.unwrap_or(config.foreground);
+    config.background = background.unwrap_or(config.background);
+    config.plane2_color = plane2_color.unwrap_or(config.plane2_color);
+    config.blend_color = blend_color.unwrap_or(config.blend_color);
+    config.seed = seed;
+    config.record_path = record_path;
+    config.play_path = play_path;
+    config.verify_path = verify_path;
+    config.capture_path = capture_path;
+    config.keymap_path = keymap_path;
+    config.buzzer.frequency = frequency.map_or(config.buzzer.frequency, |hz| hz as f32);
+    config.buzzer.waveform = waveform.unwrap_or(config.buzzer.waveform);
+    config.buzzer.volume = volume.map_or(config.buzzer.volume, |percent| percent as f32 / 100.0);
+    config.mute = mute;
+    config.rewind_depth = rewind_depth.unwrap_or(config.rewind_depth);
+    config.rewind_memory = rewind_memory.unwrap_or(config.rewind_memory);
+    config.debug = debug;
+    config.breakpoints = breakpoints;
+    config.trace_path = trace_path;
+    config.trace_format = trace_format.unwrap_or(config.trace_format);
+    config.trace_filter = trace_filter;
+
+    Ok(Command::Run(Box::new(config)))
+}
+
+fn parse_disasm_args<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut rom_path = None;
+    let mut platform = None;
+    let mut syntax = Syntax::Octo;
+    let mut output_path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::DisasmHelp),
+            "-p" | "--platform" => platform = Some(parse_platform(&arg, next_value(&arg, &mut args)?)?),
+            "--syntax" => {
+                let value = next_value(&arg, &mut args)?;
+                syntax = Syntax::from_name(&value).ok_or_else(|| {
+                    format!("option '{}' expects one of {}, got '{}'", arg, Syntax::NAMES.join(", "), value)
+                })?;
+            },
+            "-o" | "--output" => output_path = Some(next_value(&arg, &mut args)?),
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ => {
+                if rom_path.is_some() {
+                    return Err(format!("unexpected argument '{}'", arg));
+                }
+                rom_path = Some(arg);
+            },
+        }
+    }
+
+    match rom_path {
+        Some(rom_path) => Ok(Command::Disassemble(DisasmConfig { rom_path, platform, syntax, output_path })),
+        None => Err(String::from("missing required argument <ROM>")),
+    }
+}
+
+fn parse_asm_args<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut source_path = None;
+    let mut output_path = None;
+    let mut listing_path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::AsmHelp),
+            "-o" | "--output" => output_path = Some(next_value(&arg, &mut args)?),
+            "-l" | "--listing" => listing_path = Some(next_value(&arg, &mut args)?),
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ => {
+                if source_path.is_some() {
+                    return Err(format!("unexpected argument '{}'", arg));
+                }
+                source_path = Some(arg);
+            },
+        }
+    }
+
+    let source_path: String = source_path.ok_or_else(|| String::from("missing required argument <SOURCE>"))?;
+    let output_path = output_path.unwrap_or_else(|| {
+        std::path::Path::new(&source_path).with_extension("ch8").to_string_lossy().into_owned()
+    });
+    if output_path == source_path {
+        return Err(format!("the ROM would overwrite the source '{}', choose another with --output", source_path));
+    }
+
+    Ok(Command::Assemble(AsmConfig { source_path, output_path, listing_path }))
+}
+
+fn next_value<I: Iterator<Item = String>>(option: &str, args: &mut I) -> Result<String, String> {
+    match args.next() {
+        Some(value) => Ok(value),
+        None => Err(format!("option '{}' requires a value", option)),
+    }
+}
+
+fn parse_positive(option: &str, value: String) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("option '{}' expects a positive integer, got '{}'", option, value)),
+    }
+}
+
+fn parse_scale(option: &str, value: String) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(n) if (1..=MAX_SCALE).contains(&n) => Ok(n),
+        _ => Err(format!("option '{}' expects a scale factor from 1 to {}, got '{}'", option, MAX_SCALE, value)),
+    }
+}
+
+fn parse_platform(option: &str, value: String) -> Result<Platform, String> {
+    match Platform::from_name(&value) {
+        Some(platform) => Ok(platform),
+        None => Err(format!("option '{}' expects one of {}, got '{}'", option, Platform::NAMES.join(", "), value)),
+    }
+}
+
+fn parse_quirks(option: &str, value: String) -> Result<Quirks, String> {
+    match Quirks::from_name(&value) {
+        Some(quirks) => Ok(quirks),
+        None => Err(format!("option '{}' expects one of {}, got '{}'", option, Quirks::NAMES.join(", "), value)),
+    }
+}
+
+fn parse_seed(option: &str, value: String) -> Result<u64, String> {
+    match value.parse::<u64>() {
+        Ok(seed) => Ok(seed),
+        Err(_) => Err(format!("option '{}' expects an unsigned 64-bit integer, got '{}'", option, value)),
+    }
+}
+
+fn parse_speed(option: &str, value: String) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(speed) if (MIN_SPEED..=MAX_SPEED).contains(&speed) => Ok(speed),
+        _ => Err(format!("option '{}' expects a speed from {} to {}, got '{}'", option, MIN_SPEED, MAX_SPEED, value)),
+    }
+}
+
+fn parse_percent(option: &str, value: String) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(n) if n <= 100 => Ok(n),
+        _ => Err(format!("option '{}' expects a percentage from 0 to 100, got '{}'", option, value)),
+    }
+}
+
+fn parse_waveform(option: &str, value: String) -> Result<Waveform, String> {
+    match Waveform::from_name(&value) {
+        Some(waveform) => Ok(waveform),
+        None => Err(format!("option '{}' expects one of {}, got '{}'", option, Waveform::NAMES.join(", "), value)),
+    }
+}
+
+/// Parses an `RRGGBB` hex color, with or without a leading `#`
+fn parse_color(option: &str, value: String) -> Result<u32, String> {
+    let hex = value.trim_start_matches('#');
+    // from_str_radix alone would also accept a sign, like `+12345`
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("option '{}' expects a color in RRGGBB form, got '{}'", option, value));
+    }
+
+    u32::from_str_radix(hex, 16)
+        .map_err(|_| format!("option '{}' expects a color in RRGGBB form, got '{}'", option, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args_defaults() {
+        let command = parse_args(args(&["game.ch8"])).unwrap();
+
+        assert_eq!(command, Command::Run(Box::new(Config::with_rom(String::from("game.ch8")))));
+    }
+
+    #[test]
+    fn test_parse_args_all_options() {
+        let command = parse_args(args(&[
+            "--scale", "8", "-f", "12", "--speed", "0.5", "-q", "vip", "--fg", "#33FF66", "--bg", "101010", "--fg2", "00FF00", "--blend", "0000FF", "--seed", "1234", "--record", "run.c8m", "--capture", "run.gif", "-k", "keys.txt",
+            "--tone", "880", "--waveform", "sine", "--volume", "50", "--mute",
+            "--rewind-depth", "120", "--rewind-memory", "8", "game.ch8",
+        ])).unwrap();
+
+        let expected = Config {
+            rom_path: String::from("game.ch8"),
+            platform: None,
+            scale: 8,
+            instructions_per_frame: 12,
+            speed: 0.5,
+            quirks: Quirks::cosmac_vip(),
+            foreground: 0x33FF66,
+            background: 0x101010,
+            plane2_color: 0x00FF00,
+            blend_color: 0x0000FF,
+            seed: Some(1234),
+            record_path: Some(String::from("run.c8m")),
+            play_path: None,
+            verify_path: None,
+            capture_path: Some(String::from("run.gif")),
+            keymap_path: Some(String::from("keys.txt")),
+            buzzer: BuzzerSettings { frequency: 880.0, waveform: Waveform::Sine, volume: 0.5 },
+            mute: true,
+            rewind_depth: 120,
+            rewind_memory: 8,
+            debug: false,
+            breakpoints: Vec::new(),
+            trace_path: None,
+            trace_format: TraceFormat::Text,
+            trace_filter: TraceFilter::default(),
+        };
+        assert_eq!(command, Command::Run(Box::new(expected)));
+    }
+
+    #[test]
+    fn test_parse_args_platform() {
+        let command = parse_args(args(&["--platform", "schip", "game.ch8"])).unwrap();
+
+        let mut expected = Config::with_rom(String::from("game.ch8"));
+        expected.platform = Some(Platform::SuperChip);
+        expected.instructions_per_frame = 30;
+        expected.quirks = Quirks::schip();
+        assert_eq!(command, Command::Run(Box::new(expected)));
+
+        // explicit options take precedence over the platform's settings
+        let command = parse_args(args(&["-p", "schip", "-i", "600", "-q", "default", "game.ch8"])).unwrap();
+
+        let mut expected = Config::with_rom(String::from("game.ch8"));
+        expected.platform = Some(Platform::SuperChip);
+        expected.instructions_per_frame = 10;
+        assert_eq!(command, Command::Run(Box::new(expected)));
+    }
+
+    #[test]
+    fn test_parse_args_ips() {
+        let command = parse_args(args(&["--ips", "700", "game.ch8"])).unwrap();
+
+        let mut expected = Config::with_rom(String::from("game.ch8"));
+        expected.instructions_per_frame = 12;
+        assert_eq!(command, Command::Run(Box::new(expected)));
+
+        // never drops below one instruction per frame
+        let command = parse_args(args(&["--ips", "10", "game.ch8"])).unwrap();
+
+        let mut expected = Config::with_rom(String::from("game.ch8"));
+        expected.instructions_per_frame = 1;
+        assert_eq!(command, Command::Run(Box::new(expected)));
+
+        // the largest value does not overflow while rounding
+        let command = parse_args(args(&["--ips", "4294967295", "game.ch8"])).unwrap();
+
+        let mut expected = Config::with_rom(String::from("game.ch8"));
+        expected.instructions_per_frame = u32::MAX / 60;
+        assert_eq!(command, Command::Run(Box::new(expected)));
+    }
+
+    #[test]
+    fn test_parse_args_debugger() {
+        let command = parse_args(args(&["--debug", "-b", "2a0", "--break", "0x300", "game.ch8"])).unwrap();
+
+        let mut expected = Config::with_rom(String::from("game.ch8"));
+        expected.debug = true;
+        expected.breakpoints = vec![0x2A0, 0x300];
+        assert_eq!(command, Command::Run(Box::new(expected)));
+    }
+
+    #[test]
+    fn test_parse_args_trace() {
+        let command = parse_args(args(&[
+            "--trace", "run.trace", "--trace-format", "binary", "--trace-range", "200-2ff", "--trace-range", "0x400-0x4FF",
+            "--trace-ops", "8,D", "--trace-limit", "1000", "game.ch8",
+        ])).unwrap();
+
+        let mut expected = Config::with_rom(String::from("game.ch8"));
+        expected.trace_path = Some(String::from("run.trace"));
+        expected.trace_format = TraceFormat::Binary;
+        expected.trace_filter = TraceFilter {
+            address_ranges: vec![(0x200, 0x2FF), (0x400, 0x4FF)],
+            opcode_classes: vec![0x8, 0xD],
+            limit: Some(1000),
+        };
+        assert_eq!(command, Command::Run(Box::new(expected)));
+
+        assert!(parse_args(args(&["--trace-limit", "10", "game.ch8"])).is_err());
+        assert!(parse_args(args(&["--trace", "run.trace", "--trace-range", "2FF-200", "game.ch8"])).is_err());
+        assert!(parse_args(args(&["--trace", "run.trace", "--verify", "run.c8m", "game.ch8"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_disasm_args() {
+        let command = parse_args(args(&["disasm", "-p", "xochip", "--syntax", "classic", "-o", "game.asm", "game.ch8"])).unwrap();
+
+        let expected = DisasmConfig {
+            rom_path: String::from("game.ch8"),
+            platform: Some(Platform::XoChip),
+            syntax: Syntax::Classic,
+            output_path: Some(String::from("game.asm")),
+        };
+        assert_eq!(command, Command::Disassemble(expected));
+
+        assert_eq!(parse_args(args(&["disasm", "--help"])), Ok(Command::DisasmHelp));
+        assert!(parse_args(args(&["disasm"])).is_err());
+        assert!(parse_args(args(&["disasm", "--syntax", "intel", "game.ch8"])).is_err());
+        assert!(parse_args(args(&["disasm", "--scale", "2", "game.ch8"])).is_err());
+
+        // only the first argument selects the subcommand
+        let command = parse_args(args(&["-s", "2", "disasm"])).unwrap();
+        assert!(matches!(command, Command::Run(config) if config.rom_path == "disasm"));
+    }
+
+    #[test]
+    fn test_parse_asm_args() {
+        let command = parse_args(args(&["asm", "-l", "game.lst", "src/game.asm"])).unwrap();
+
+        let expected = AsmConfig {
+            source_path: String::from("src/game.asm"),
+            output_path: String::from("src/game.ch8"),
+            listing_path: Some(String::from("game.lst")),
+        };
+        assert_eq!(command, Command::Assemble(expected));
+
+        let command = parse_args(args(&["asm", "game.asm", "--output", "out.bin"])).unwrap();
+        assert!(matches!(command, Command::Assemble(config) if config.output_path == "out.bin"));
+
+        assert_eq!(parse_args(args(&["asm", "-h"])), Ok(Command::AsmHelp));
+        assert!(parse_args(args(&["asm"])).is_err());
+        assert!(parse_args(args(&["asm", "game.ch8"])).is_err());
+        assert!(parse_args(args(&["asm", "-p", "schip", "game.asm"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_args_help() {
+        assert_eq!(parse_args(args(&["game.ch8", "--help"])), Ok(Command::Help));
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert!(parse_args(args(&[])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--scale"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--scale", "0"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--scale", "65"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--ips", "fast"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--speed", "0"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--speed", "1e-300"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--speed", "101"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--speed", "NaN"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--seed", "-1"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--record", "a.c8m", "--play", "b.c8m"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--record", "a.c8m", "--debug"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--break", "start"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--quirks", "eti660"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--platform", "eti660"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--fg", "FFF"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--fg", "+12345"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--volume", "150"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--waveform", "noise"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "--turbo"])).is_err());
+        assert!(parse_args(args(&["game.ch8", "other.ch8"])).is_err());
+    }
+}
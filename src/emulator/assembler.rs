@@ -0,0 +1,830 @@
+//! Assembler for CHIP-8 programs written with Cowgod's mnemonics, the `classic` syntax of the
+//! disassembler.
+//!
+//! Each line holds at most one label, one statement and one comment:
+//!
+//! ```text
+//! SPEED = 3               ; constant; may be used anywhere a number is expected
+//! loop:   ADD V0, SPEED   ; label, then an instruction
+//!         LD I, ball
+//!         DRW V0, V1, 4
+//!         JP loop
+//! ball:   SPRITE ".##.", "####", "####", ".##."
+//! ```
+//!
+//! - Mnemonics and register names are case-insensitive; labels and constants are not.
+//! - Numbers are decimal, hex with `0x` or binary with `0b`, and may contain `_` separators.
+//!   Operands can add and subtract numbers, labels and constants, e.g. `LD I, digits + 5`.
+//! - Instructions: `CLS`, `RET`, `JP addr`, `JP V0, addr`, `CALL addr`, `SE`/`SNE Vx, byte|Vy`,
+//!   `LD Vx, byte|Vy|DT|K|[I]|R`, `LD I|DT|ST|F|HF|B|[I]|R, ...`, `ADD Vx, byte|Vy`, `ADD I, Vx`,
+//!   `OR`/`AND`/`XOR`/`SUB`/`SUBN Vx, Vy`, `SHR`/`SHL Vx[, Vy]`, `RND Vx, byte`,
+//!   `DRW Vx, Vy, n`, `SKP`/`SKNP Vx`.
+//! - SUPER-CHIP: `SCD n`, `SCR`, `SCL`, `EXIT`, `LOW`, `HIGH`, `LD HF, Vx`, `LD R, Vx`, `LD Vx, R`.
+//! - XO-CHIP: `LD I, LONG addr` (4 bytes), `LD [I], Vx-Vy`, `LD Vx-Vy, [I]`, `PLANE n`, `AUDIO`,
+//!   `PITCH Vx`.
+//! - `DB` and `DW` emit comma-separated bytes and big-endian words.
+//! - `SPRITE` emits one row per string, `#` or `1` for pixels that are on and `.` or `0` for
+//!   pixels that are off. Rows up to 8 pixels wide take a byte, rows up to 16 pixels take two.
+//! - `INCLUDE "file"` assembles another file in place, relative to the including one.
+//!
+//! The program is placed at 0x200, the start of a loaded ROM.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::constants::PROGRAM_START_ADDRESS;
+use super::error::Chip8Error;
+use super::instruction::Instruction;
+
+/// Deepest nesting of `INCLUDE`s, which also stops files from including themselves
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Deepest chain of constants defined in terms of each other
+const MAX_CONSTANT_DEPTH: usize = 32;
+
+const MNEMONICS: [&str; 30] = [
+    "CLS", "RET", "SCD", "SCR", "SCL", "EXIT", "LOW", "HIGH", "JP", "CALL", "SE", "SNE", "LD", "ADD", "OR",
+    "AND", "XOR", "SUB", "SUBN", "SHR", "SHL", "RND", "DRW", "SKP", "SKNP", "PLANE", "AUDIO", "PITCH",
+    "DB", "DW",
+];
+
+/// The ROM built from a program, with a listing of where every source line ended up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembly {
+    pub rom: Vec<u8>,
+    pub listing: Vec<ListingLine>,
+}
+
+/// One source line and the bytes it assembled to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingLine {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub source: String,
+}
+
+impl Assembly {
+    /// One line per source line: the address, up to 4 bytes in hex, then the source.
+    /// Longer data continues on the following lines.
+    pub fn format_listing(&self) -> String {
+        let mut listing = String::new();
+
+        for line in self.listing.iter() {
+            let mut chunks = line.bytes.chunks(4);
+            let first = chunks.next().map_or(String::new(), format_bytes);
+            listing.push_str(format!("{:04X}  {:<13}{}", line.address, first, line.source).trim_end());
+            listing.push('\n');
+
+            for (i, chunk) in chunks.enumerate() {
+                listing.push_str(&format!("{:04X}  {}\n", usize::from(line.address) + 4 * (i + 1), format_bytes(chunk)));
+            }
+        }
+
+        listing
+    }
+}
+
+fn format_bytes(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(" ")
+}
+
+/// Assembles `source`, reading included files relative to `path`. `path` is also the file
+/// name reported in errors.
+pub fn assemble(source: &str, path: &str) -> Result<Assembly, Chip8Error> {
+    assemble_with(source, path, &|include_path| std::fs::read_to_string(include_path))
+}
+
+pub fn assemble_file(path: &str) -> Result<Assembly, Chip8Error> {
+    let source = std::fs::read_to_string(path).map_err(|e| Chip8Error::Io {
+        path: path.to_string(),
+        reason: e.to_string(),
+    })?;
+
+    assemble(&source, path)
+}
+
+fn assemble_with(source: &str, path: &str, read_file: &dyn Fn(&str) -> std::io::Result<String>) -> Result<Assembly, Chip8Error> {
+    let mut program = Program::default();
+    program.read_source(source, path, read_file, 0)?;
+
+    let mut rom = Vec::with_capacity(program.address - PROGRAM_START_ADDRESS);
+    for statement in program.statements.iter() {
+        let bytes = program.encode(statement)?;
+        rom.extend_from_slice(&bytes);
+        program.listing[statement.listing_index].bytes = bytes;
+    }
+
+    Ok(Assembly { rom, listing: program.listing })
+}
+
+/// Where something was written in the source; lines and columns start at 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Location {
+    path: String,
+    line: usize,
+    column: usize,
+}
+
+impl Location {
+    fn error(&self, reason: impl Into<String>) -> Chip8Error {
+        Chip8Error::Assembly {
+            path: self.path.clone(),
+            line: self.line,
+            column: self.column,
+            reason: reason.into(),
+        }
+    }
+
+    fn at_column(&self, column: usize) -> Self {
+        Self { column, ..self.clone() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    Number(i64),
+    Symbol(String),
+}
+
+/// A sum of numbers, labels and constants
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Expr {
+    terms: Vec<(bool, Term)>,   // (negated, term)
+    location: Location,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum OperandKind {
+    Register(u8),
+    RegisterRange(u8, u8),
+    I,
+    IndirectI,
+    DelayTimer,
+    SoundTimer,
+    Key,
+    Font,
+    BigFont,
+    Bcd,
+    Flags,
+    Long(Expr),
+    Value(Expr),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Operand {
+    kind: OperandKind,
+    location: Location,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Symbol {
+    Label(u16),
+    Constant(Expr),
+}
+
+/// A line that emits bytes, waiting for every label to be known
+#[derive(Debug, Clone)]
+struct Statement {
+    mnemonic: String,
+    operands: Vec<Operand>,
+    location: Location,
+    listing_index: usize,
+}
+
+/// Everything collected in the first pass over the source
+#[derive(Debug)]
+struct Program {
+    symbols: HashMap<String, Symbol>,
+    statements: Vec<Statement>,
+    listing: Vec<ListingLine>,
+    address: usize,   // where the next statement goes
+}
+
+impl Default for Program {
+    fn default() -> Self {
+        Self {
+            symbols: HashMap::new(),
+            statements: Vec::new(),
+            listing: Vec::new(),
+            address: PROGRAM_START_ADDRESS,
+        }
+    }
+}
+
+// first pass: parse lines, place labels and size statements
+impl Program {
+    fn read_source(&mut self, source: &str, path: &str, read_file: &dyn Fn(&str) -> std::io::Result<String>, depth: usize) -> Result<(), Chip8Error> {
+        for (i, text) in source.lines().enumerate() {
+            let location = Location { path: path.to_string(), line: i + 1, column: 1 };
+            let line = parse_line(text, &location)?;
+
+            if let Some((name, location)) = line.label {
+                self.define(name, Symbol::Label(self.address as u16), &location)?;
+            }
+            if let Some((name, expr)) = line.constant {
+                let location = expr.location.clone();
+                self.define(name, Symbol::Constant(expr), &location)?;
+            }
+
+            let (mnemonic, operands, location) = match line.statement {
+                Some(statement) => statement,
+                None => {
+                    if !text.trim().is_empty() {
+                        self.listing.push(ListingLine { address: self.address as u16, bytes: Vec::new(), source: text.trim_end().to_string() });
+                    }
+                    continue;
+                },
+            };
+
+            if mnemonic.eq_ignore_ascii_case("INCLUDE") {
+                self.listing.push(ListingLine { address: self.address as u16, bytes: Vec::new(), source: text.trim_end().to_string() });
+                self.include(&operands, path, &location, read_file, depth)?;
+                continue;
+            }
+
+            let size = statement_size(&mnemonic, &operands, &location)?;
+            if self.address + size > 0x10000 {
+                return Err(location.error("the program does not fit in the 64 KiB address space"));
+            }
+
+            self.statements.push(Statement { mnemonic, operands, location, listing_index: self.listing.len() });
+            self.listing.push(ListingLine { address: self.address as u16, bytes: Vec::new(), source: text.trim_end().to_string() });
+            self.address += size;
+        }
+
+        Ok(())
+    }
+
+    fn include(&mut self, operands: &[Operand], path: &str, location: &Location, read_file: &dyn Fn(&str) -> std::io::Result<String>, depth: usize) -> Result<(), Chip8Error> {
+        let file_name = match operands {
+            [Operand { kind: OperandKind::Text(file_name), .. }] => file_name,
+            _ => return Err(location.error("INCLUDE expects a quoted file name")),
+        };
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(location.error(format!("includes are nested more than {} levels deep", MAX_INCLUDE_DEPTH)));
+        }
+
+        let include_path = match Path::new(path).parent() {
+            Some(directory) => directory.join(file_name).to_string_lossy().into_owned(),
+            None => file_name.clone(),
+        };
+        let source = read_file(&include_path)
+            .map_err(|e| operands[0].location.error(format!("could not read {}: {}", include_path, e)))?;
+
+        self.read_source(&source, &include_path, read_file, depth + 1)
+    }
+
+    fn define(&mut self, name: String, symbol: Symbol, location: &Location) -> Result<(), Chip8Error> {
+        if is_reserved(&name) {
+            return Err(location.error(format!("'{}' is reserved and cannot be used as a name", name)));
+        }
+        if self.symbols.contains_key(&name) {
+            return Err(location.error(format!("'{}' is already defined", name)));
+        }
+
+        self.symbols.insert(name, symbol);
+        Ok(())
+    }
+}
+
+// second pass: encode statements
+impl Program {
+    fn encode(&self, statement: &Statement) -> Result<Vec<u8>, Chip8Error> {
+        use Instruction::*;
+        use OperandKind::*;
+
+        let mnemonic = statement.mnemonic.to_ascii_uppercase();
+        let operands = &statement.operands;
+        let kinds: Vec<&OperandKind> = operands.iter().map(|operand| &operand.kind).collect();
+
+        let instruction = match (mnemonic.as_str(), kinds.as_slice()) {
+            ("DB", _) => return statement.operands.iter().map(|operand| self.byte(operand)).collect(),
+            ("DW", _) => {
+                let mut bytes = Vec::new();
+                for operand in statement.operands.iter() {
+                    bytes.extend_from_slice(&self.word(operand)?.to_be_bytes());
+                }
+                return Ok(bytes);
+            },
+            ("SPRITE", _) => return sprite_rows(&statement.operands, &statement.location),
+
+            ("CLS", []) => ClearScreen,
+            ("RET", []) => Return,
+            ("SCD", [Value(_)]) => ScrollDown { n: self.nibble(&operands[0])? },
+            ("SCR", []) => ScrollRight,
+            ("SCL", []) => ScrollLeft,
+            ("EXIT", []) => Exit,
+            ("LOW", []) => LowRes,
+            ("HIGH", []) => HighRes,
+            ("JP", [Value(_)]) => Jump { nnn: self.address(&operands[0])? },
+            ("JP", [Register(0), Value(_)]) => JumpOffset { nnn: self.address(&operands[1])? },
+            ("CALL", [Value(_)]) => Call { nnn: self.address(&operands[0])? },
+            ("SE", [Register(x), Value(_)]) => SkipEqImm { x: *x, kk: self.byte(&operands[1])? },
+            ("SNE", [Register(x), Value(_)]) => SkipNeImm { x: *x, kk: self.byte(&operands[1])? },
+            ("SE", [Register(x), Register(y)]) => SkipEqReg { x: *x, y: *y },
+            ("LD", [IndirectI, RegisterRange(x, y)]) => SaveRange { x: *x, y: *y },
+            ("LD", [RegisterRange(x, y), IndirectI]) => LoadRange { x: *x, y: *y },
+            ("LD", [Register(x), Value(_)]) => LoadImm { x: *x, kk: self.byte(&operands[1])? },
+            ("ADD", [Register(x), Value(_)]) => AddImm { x: *x, kk: self.byte(&operands[1])? },
+            ("LD", [Register(x), Register(y)]) => Move { x: *x, y: *y },
+            ("OR", [Register(x), Register(y)]) => Or { x: *x, y: *y },
+            ("AND", [Register(x), Register(y)]) => And { x: *x, y: *y },
+            ("XOR", [Register(x), Register(y)]) => Xor { x: *x, y: *y },
+            ("ADD", [Register(x), Register(y)]) => AddReg { x: *x, y: *y },
+            ("SUB", [Register(x), Register(y)]) => Sub { x: *x, y: *y },
+            ("SHR", [Register(x)]) => ShiftRight { x: *x, y: *x },
+            ("SHR", [Register(x), Register(y)]) => ShiftRight { x: *x, y: *y },
+            ("SUBN", [Register(x), Register(y)]) => SubN { x: *x, y: *y },
+            ("SHL", [Register(x)]) => ShiftLeft { x: *x, y: *x },
+            ("SHL", [Register(x), Register(y)]) => ShiftLeft { x: *x, y: *y },
+            ("SNE", [Register(x), Register(y)]) => SkipNeReg { x: *x, y: *y },
+            ("LD", [I, Value(_)]) => LoadI { nnn: self.address(&operands[1])? },
+            ("LD", [I, Long(expr)]) => {
+                let address = self.evaluate_in_range(expr, 0, 0xFFFF, "a 16-bit address")? as u16;
+                return Ok([LoadILong.encode().to_be_bytes(), address.to_be_bytes()].concat());
+            },
+            ("RND", [Register(x), Value(_)]) => Random { x: *x, kk: self.byte(&operands[1])? },
+            ("DRW", [Register(x), Register(y), Value(_)]) => Draw { x: *x, y: *y, n: self.nibble(&operands[2])? },
+            ("SKP", [Register(x)]) => SkipKey { x: *x },
+            ("SKNP", [Register(x)]) => SkipNotKey { x: *x },
+            ("PLANE", [Value(_)]) => SelectPlanes { n: self.nibble(&operands[0])? },
+            ("AUDIO", []) => LoadAudio,
+            ("LD", [Register(x), DelayTimer]) => GetDelay { x: *x },
+            ("LD", [Register(x), Key]) => WaitKey { x: *x },
+            ("LD", [DelayTimer, Register(x)]) => SetDelay { x: *x },
+            ("LD", [SoundTimer, Register(x)]) => SetSound { x: *x },
+            ("ADD", [I, Register(x)]) => AddI { x: *x },
+            ("LD", [Font, Register(x)]) => LoadFont { x: *x },
+            ("LD", [BigFont, Register(x)]) => LoadBigFont { x: *x },
+            ("LD", [Bcd, Register(x)]) => StoreBcd { x: *x },
+            ("PITCH", [Register(x)]) => SetPitch { x: *x },
+            ("LD", [IndirectI, Register(x)]) => SaveRegisters { x: *x },
+            ("LD", [Register(x), IndirectI]) => LoadRegisters { x: *x },
+            ("LD", [Flags, Register(x)]) => SaveFlags { x: *x },
+            ("LD", [Register(x), Flags]) => LoadFlags { x: *x },
+            _ => return Err(statement.location.error(format!("invalid operands for {}", mnemonic))),
+        };
+
+        Ok(instruction.encode().to_be_bytes().to_vec())
+    }
+
+    fn nibble(&self, operand: &Operand) -> Result<u8, Chip8Error> {
+        Ok(self.value(operand, 0, 0xF, "a nibble (0-15)")? as u8)
+    }
+
+    fn byte(&self, operand: &Operand) -> Result<u8, Chip8Error> {
+        Ok(self.value(operand, -0x80, 0xFF, "a byte")? as u8)
+    }
+
+    fn word(&self, operand: &Operand) -> Result<u16, Chip8Error> {
+        Ok(self.value(operand, -0x8000, 0xFFFF, "a word")? as u16)
+    }
+
+    fn address(&self, operand: &Operand) -> Result<u16, Chip8Error> {
+        Ok(self.value(operand, 0, 0xFFF, "a 12-bit address")? as u16)
+    }
+
+    fn value(&self, operand: &Operand, min: i64, max: i64, description: &str) -> Result<i64, Chip8Error> {
+        match &operand.kind {
+            OperandKind::Value(expr) => self.evaluate_in_range(expr, min, max, description),
+            _ => Err(operand.location.error(format!("expected {}", description))),
+        }
+    }
+
+    fn evaluate_in_range(&self, expr: &Expr, min: i64, max: i64, description: &str) -> Result<i64, Chip8Error> {
+        let value = self.evaluate(expr, 0)?;
+        if value < min || value > max {
+            return Err(expr.location.error(format!("{} does not fit in {}", value, description)));
+        }
+        Ok(value)
+    }
+
+    fn evaluate(&self, expr: &Expr, depth: usize) -> Result<i64, Chip8Error> {
+        if depth > MAX_CONSTANT_DEPTH {
+            return Err(expr.location.error("constant is defined in terms of itself"));
+        }
+
+        let mut sum: i64 = 0;
+        for (negated, term) in expr.terms.iter() {
+            let value = match term {
+                Term::Number(value) => *value,
+                Term::Symbol(name) => match self.symbols.get(name) {
+                    Some(Symbol::Label(address)) => i64::from(*address),
+                    Some(Symbol::Constant(constant)) => self.evaluate(constant, depth + 1)?,
+                    None => return Err(expr.location.error(format!("'{}' is not defined", name))),
+                },
+            };
+            sum = if *negated { sum.wrapping_sub(value) } else { sum.wrapping_add(value) };
+        }
+
+        Ok(sum)
+    }
+}
+
+/// Bytes a statement will take up, known before any label is
+fn statement_size(mnemonic: &str, operands: &[Operand], location: &Location) -> Result<usize, Chip8Error> {
+    let mnemonic = mnemonic.to_ascii_uppercase();
+    match mnemonic.as_str() {
+        "DB" | "DW" if operands.is_empty() => Err(location.error(format!("{} expects at least one value", mnemonic))),
+        "DB" => Ok(operands.len()),
+        "DW" => Ok(2 * operands.len()),
+        "SPRITE" => Ok(sprite_rows(operands, location)?.len()),
+        "LD" if matches!(operands, [Operand { kind: OperandKind::I, .. }, Operand { kind: OperandKind::Long(_), .. }]) => Ok(4),
+        _ if MNEMONICS.contains(&mnemonic.as_str()) => Ok(2),
+        _ => Err(location.error(format!("unknown mnemonic '{}'", mnemonic))),
+    }
+}
+
+/// Converts the rows of a `SPRITE` into bytes
+fn sprite_rows(operands: &[Operand], location: &Location) -> Result<Vec<u8>, Chip8Error> {
+    if operands.is_empty() {
+        return Err(location.error("SPRITE expects at least one row"));
+    }
+
+    let mut width = None;
+    let mut bytes = Vec::new();
+    for operand in operands.iter() {
+        let row = match &operand.kind {
+            OperandKind::Text(row) => row,
+            _ => return Err(operand.location.error("sprite rows must be quoted, e.g. \"#..#\"")),
+        };
+        if row.is_empty() || row.chars().count() > 16 {
+            return Err(operand.location.error("sprite rows must be 1 to 16 pixels wide"));
+        }
+        if *width.get_or_insert(row.chars().count()) != row.chars().count() {
+            return Err(operand.location.error("all rows of a sprite must be the same width"));
+        }
+
+        let mut bits: u16 = 0;
+        for (i, pixel) in row.chars().enumerate() {
+            match pixel {
+                '#' | '1' => bits |= 0x8000 >> i,
+                '.' | '0' => {},
+                _ => return Err(operand.location.error(format!("'{}' is not a sprite pixel, expected '#' or '.'", pixel))),
+            }
+        }
+
+        let row_bytes = bits.to_be_bytes();
+        if row.chars().count() > 8 {
+            bytes.extend_from_slice(&row_bytes);
+        } else {
+            bytes.push(row_bytes[0]);
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// The parts of a single source line
+#[derive(Debug, Default)]
+struct ParsedLine {
+    label: Option<(String, Location)>,
+    constant: Option<(String, Expr)>,
+    statement: Option<(String, Vec<Operand>, Location)>,
+}
+
+fn parse_line(text: &str, location: &Location) -> Result<ParsedLine, Chip8Error> {
+    let mut line = ParsedLine::default();
+    let code = strip_comment(text);
+
+    let mut rest = skip_whitespace(code, 0);
+    let word_end = identifier_end(code, rest);
+
+    // `name:` defines a label, and may be followed by a statement
+    if word_end > rest && code[word_end..].starts_with(':') {
+        line.label = Some((code[rest..word_end].to_string(), location.at_column(column(code, rest))));
+        rest = skip_whitespace(code, word_end + 1);
+    }
+    if rest == code.len() {
+        return Ok(line);
+    }
+
+    let word_end = identifier_end(code, rest);
+    if word_end == rest {
+        return Err(location.at_column(column(code, rest)).error("expected a label, constant or mnemonic"));
+    }
+    let word = &code[rest..word_end];
+    let word_location = location.at_column(column(code, rest));
+
+    // `name = value` defines a constant
+    let after_word = skip_whitespace(code, word_end);
+    if code[after_word..].starts_with('=') {
+        if line.label.is_some() {
+            return Err(word_location.error("a constant cannot be labeled"));
+        }
+        let expr = parse_expr(code, after_word + 1, code.len(), location)?;
+        line.constant = Some((word.to_string(), expr));
+        return Ok(line);
+    }
+
+    let operands = split_operands(code, word_end)
+        .into_iter()
+        .map(|(start, end)| parse_operand(code, start, end, location))
+        .collect::<Result<Vec<_>, _>>()?;
+    line.statement = Some((word.to_string(), operands, word_location));
+
+    Ok(line)
+}
+
+/// Removes a `;` comment, unless it is inside quotes
+fn strip_comment(text: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &text[..i],
+            _ => {},
+        }
+    }
+    text
+}
+
+fn skip_whitespace(text: &str, start: usize) -> usize {
+    text[start..].find(|c: char| !c.is_whitespace()).map_or(text.len(), |offset| start + offset)
+}
+
+fn identifier_end(text: &str, start: usize) -> usize {
+    let mut end = start;
+    for (i, c) in text[start..].char_indices() {
+        let valid = if i == 0 { c.is_ascii_alphabetic() || c == '_' } else { c.is_ascii_alphanumeric() || c == '_' || c == '.' };
+        if !valid {
+            break;
+        }
+        end = start + i + c.len_utf8();
+    }
+    end
+}
+
+/// 1-based column of the byte offset `index`
+fn column(text: &str, index: usize) -> usize {
+    text[..index].chars().count() + 1
+}
+
+/// Byte ranges of the comma-separated operands after `start`, trimmed
+fn split_operands(text: &str, start: usize) -> Vec<(usize, usize)> {
+    let mut operands = Vec::new();
+    if text[start..].trim().is_empty() {
+        return operands;
+    }
+
+    let mut quoted = false;
+    let mut operand_start = start;
+    for (i, c) in text[start..].char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                operands.push((operand_start, start + i));
+                operand_start = start + i + 1;
+            },
+            _ => {},
+        }
+    }
+    operands.push((operand_start, text.len()));
+
+    operands.into_iter()
+        .map(|(start, end)| {
+            let trimmed_start = skip_whitespace(&text[..end], start);
+            let trimmed_end = trimmed_start + text[trimmed_start..end].trim_end().len();
+            (trimmed_start, trimmed_end)
+        })
+        .collect()
+}
+
+fn parse_operand(text: &str, start: usize, end: usize, location: &Location) -> Result<Operand, Chip8Error> {
+    let location = location.at_column(column(text, start));
+    let operand = &text[start..end];
+    let upper = operand.to_ascii_uppercase();
+
+    let kind = match upper.as_str() {
+        "" => return Err(location.error("expected an operand")),
+        "I" => OperandKind::I,
+        "[I]" => OperandKind::IndirectI,
+        "DT" => OperandKind::DelayTimer,
+        "ST" => OperandKind::SoundTimer,
+        "K" => OperandKind::Key,
+        "F" => OperandKind::Font,
+        "HF" => OperandKind::BigFont,
+        "B" => OperandKind::Bcd,
+        "R" => OperandKind::Flags,
+        _ if operand.starts_with('"') => match operand[1..].strip_suffix('"') {
+            Some(text) if !text.contains('"') => OperandKind::Text(text.to_string()),
+            _ => return Err(location.error("unterminated string")),
+        },
+        _ if upper.starts_with("LONG") && upper[4..].starts_with(char::is_whitespace) => {
+            OperandKind::Long(parse_expr(text, start + 4, end, &location)?)
+        },
+        _ => match (register(&upper), upper.split_once('-')) {
+            (Some(x), _) => OperandKind::Register(x),
+            (None, Some((first, last))) if register(first.trim()).is_some() && register(last.trim()).is_some() => {
+                OperandKind::RegisterRange(register(first.trim()).unwrap(), register(last.trim()).unwrap())
+            },
+            _ => OperandKind::Value(parse_expr(text, start, end, &location)?),
+        },
+    };
+
+    Ok(Operand { kind, location })
+}
+
+/// `V0` to `VF`, already in upper case
+fn register(name: &str) -> Option<u8> {
+    let digit = name.strip_prefix('V')?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+fn is_reserved(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    register(&upper).is_some() || ["I", "DT", "ST", "K", "F", "HF", "B", "R", "LONG"].contains(&upper.as_str())
+}
+
+/// Parses a sum like `sprites + 5 - 0x2` from `text[start..end]`
+fn parse_expr(text: &str, start: usize, end: usize, location: &Location) -> Result<Expr, Chip8Error> {
+    let start = skip_whitespace(&text[..end], start);
+    let expr_location = location.at_column(column(text, start));
+
+    let mut terms = Vec::new();
+    let mut negated = false;
+    let mut position = start;
+    loop {
+        position = skip_whitespace(&text[..end], position);
+        if text[position..end].starts_with('-') && terms.is_empty() {
+            negated = true;
+            position = skip_whitespace(&text[..end], position + 1);
+        }
+
+        let term_end = text[position..end].find(|c: char| c == '+' || c == '-' || c.is_whitespace()).map_or(end, |offset| position + offset);
+        let term = &text[position..term_end];
+        let term_location = location.at_column(column(text, position));
+        terms.push((negated, parse_term(term, &term_location)?));
+
+        position = skip_whitespace(&text[..end], term_end);
+        if position == end {
+            break;
+        }
+        negated = match text[position..end].chars().next() {
+            Some('+') => false,
+            Some('-') => true,
+            _ => return Err(location.at_column(column(text, position)).error("expected '+' or '-'")),
+        };
+        position += 1;
+    }
+
+    Ok(Expr { terms, location: expr_location })
+}
+
+fn parse_term(term: &str, location: &Location) -> Result<Term, Chip8Error> {
+    if term.is_empty() {
+        return Err(location.error("expected a value"));
+    }
+    if identifier_end(term, 0) == term.len() {
+        return Ok(Term::Symbol(term.to_string()));
+    }
+
+    let digits = term.replace('_', "");
+    let lower = digits.to_ascii_lowercase();
+    let parsed = if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2)
+    } else {
+        lower.parse::<i64>()
+    };
+
+    parsed.map(Term::Number).map_err(|_| location.error(format!("'{}' is not a number or name", term)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::disassembler::{disassemble, format_listing, Syntax};
+    use crate::emulator::platform::Platform;
+
+    fn assemble_source(source: &str) -> Result<Vec<u8>, Chip8Error> {
+        assemble_with(source, "test.asm", &|_| Err(std::io::ErrorKind::NotFound.into())).map(|assembly| assembly.rom)
+    }
+
+    fn error_position(source: &str) -> (usize, usize, String) {
+        match assemble_source(source) {
+            Err(Chip8Error::Assembly { line, column, reason, .. }) => (line, column, reason),
+            result => panic!("expected an assembly error, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_assemble_program() {
+        let source = "\
+SPEED = 3               ; pixels per frame
+start:  CLS
+loop:   add v0, SPEED
+        LD I, ball
+        DRW V0, V1, 4
+        CALL wait
+        JP loop
+wait:   LD V2, DT
+        SE V2, 0
+        JP wait
+        RET
+ball:   SPRITE \".##.\", \"####\"
+";
+        let rom = assemble_source(source).unwrap();
+
+        assert_eq!(rom, [
+            0x00, 0xE0, 0x70, 0x03, 0xA2, 0x14, 0xD0, 0x14, 0x22, 0x0C, 0x12, 0x02,
+            0xF2, 0x07, 0x32, 0x00, 0x12, 0x0C, 0x00, 0xEE, 0x60, 0xF0,
+        ]);
+    }
+
+    #[test]
+    fn test_operand_forms() {
+        let source = "\
+LD [I], V3
+LD V3, [I]
+LD [I], V1-V4
+LD v1 - v4, [I]
+LD I, LONG end + 2
+JP V0, 0x300
+SHR V5
+SHL V5, V6
+SCD 0b1010
+PLANE 3
+end: DB 1, -1, 0xF_F
+DW 0x1234, end
+";
+        let rom = assemble_source(source).unwrap();
+
+        assert_eq!(rom, [
+            0xF3, 0x55, 0xF3, 0x65, 0x51, 0x42, 0x51, 0x43, 0xF0, 0x00, 0x02, 0x18, 0xB3, 0x00,
+            0x85, 0x56, 0x85, 0x6E, 0x00, 0xCA, 0xF3, 0x01, 0x01, 0xFF, 0xFF, 0x12, 0x34, 0x02, 0x16,
+        ]);
+    }
+
+    #[test]
+    fn test_sprites() {
+        assert_eq!(assemble_source("SPRITE \"#.......\", \"10000001\"").unwrap(), [0x80, 0x81]);
+        assert_eq!(assemble_source("SPRITE \"#..............#\"").unwrap(), [0x80, 0x01]);
+
+        assert_eq!(error_position("SPRITE \"#\", \"##\"").1, 13);
+        assert_eq!(error_position("SPRITE \"#x\"").1, 8);
+        assert_eq!(error_position("SPRITE 0xFF").1, 8);
+    }
+
+    #[test]
+    fn test_errors_point_at_line_and_column() {
+        assert_eq!(error_position("CLS\n  FOO V0"), (2, 3, String::from("unknown mnemonic 'FOO'")));
+        assert_eq!(error_position("LD V0, 256"), (1, 8, String::from("256 does not fit in a byte")));
+        assert_eq!(error_position("JP nowhere"), (1, 4, String::from("'nowhere' is not defined")));
+        assert_eq!(error_position("loop: CLS\nloop: RET"), (2, 1, String::from("'loop' is already defined")));
+        assert_eq!(error_position("ADD V0"), (1, 1, String::from("invalid operands for ADD")));
+        assert_eq!(error_position("V1 = 3").2, "'V1' is reserved and cannot be used as a name");
+        assert_eq!(error_position("A = B2\nB2 = A\nLD V0, A").0, 1);
+        assert_eq!(error_position("LD V0, 5 5"), (1, 10, String::from("expected '+' or '-'")));
+        assert_eq!(error_position("DB 1,,2"), (1, 6, String::from("expected an operand")));
+        assert_eq!(error_position("LD V0, 5 é"), (1, 10, String::from("expected '+' or '-'")));
+    }
+
+    #[test]
+    fn test_includes() {
+        let files: HashMap<&str, &str> = HashMap::from([
+            ("lib/digits.asm", "INCLUDE \"zero.asm\"\none: DB 0x20"),
+            ("lib/zero.asm", "zero: DB 0xF0"),
+            ("lib/loop.asm", "INCLUDE \"loop.asm\""),
+        ]);
+        let read_file = |path: &str| files.get(path).map(|source| source.to_string()).ok_or_else(|| std::io::ErrorKind::NotFound.into());
+
+        let assembly = assemble_with("LD I, one\nINCLUDE \"lib/digits.asm\"", "main.asm", &read_file).unwrap();
+        assert_eq!(assembly.rom, [0xA2, 0x03, 0xF0, 0x20]);
+
+        let result = assemble_with("INCLUDE \"lib/loop.asm\"", "main.asm", &read_file);
+        assert!(matches!(result, Err(Chip8Error::Assembly { path, .. }) if path == "lib/loop.asm"));
+
+        let result = assemble_with("\n  INCLUDE \"missing.asm\"", "main.asm", &read_file);
+        assert!(matches!(result, Err(Chip8Error::Assembly { line: 2, column: 11, .. })));
+    }
+
+    #[test]
+    fn test_listing() {
+        let assembly = assemble_with("start: ; entry point\n  LD V0, 1\nDB 1, 2, 3, 4, 5", "main.asm", &|_| Err(std::io::ErrorKind::NotFound.into())).unwrap();
+
+        assert_eq!(assembly.format_listing(), "\
+0200               start: ; entry point
+0200  60 01          LD V0, 1
+0202  01 02 03 04  DB 1, 2, 3, 4, 5
+0206  05
+");
+    }
+
+    #[test]
+    fn test_disassembly_round_trip() {
+        let rom = [
+            0x00, 0xE0, 0x22, 0x0A, 0x3A, 0x12, 0x5A, 0xB0, 0x8A, 0xB6, 0x00, 0xFF, 0xF0, 0x00, 0x12, 0x34,
+            0xF1, 0x01, 0x5A, 0xB2, 0xFA, 0x85, 0xE1, 0xA1, 0xB2, 0x00, 0xC1, 0xFF, 0x00, 0xEE, 0xFF, 0xFF, 0x42,
+        ];
+
+        let listing = format_listing(&disassemble(&rom, Some(Platform::XoChip), Syntax::Classic), Syntax::Classic);
+
+        assert_eq!(assemble_source(&listing).unwrap(), rom);
+    }
+}
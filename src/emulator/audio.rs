@@ -0,0 +1,232 @@
+use super::chip8::Chip8;
+
+/// Shape of the tone played while the sound timer is running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+}
+
+impl Waveform {
+    /// Names accepted by `Waveform::from_name`
+    pub const NAMES: [&'static str; 4] = ["square", "triangle", "sawtooth", "sine"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "square" => Some(Waveform::Square),
+            "triangle" => Some(Waveform::Triangle),
+            "sawtooth" | "saw" => Some(Waveform::Sawtooth),
+            "sine" => Some(Waveform::Sine),
+            _ => None,
+        }
+    }
+
+    /// Value of the waveform in `[-1, 1]` at `phase` in `[0, 1)`
+    fn sample(&self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Triangle => 4.0 * (phase - 0.5).abs() - 1.0,
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+            Waveform::Sine => (2.0 * std::f32::consts::PI * phase).sin(),
+        }
+    }
+}
+
+/// Tone played by the buzzer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BuzzerSettings {
+    /// Frequency of the tone in Hz
+    pub frequency: f32,
+    pub waveform: Waveform,
+    /// Amplitude in `[0, 1]`
+    pub volume: f32,
+}
+
+impl Default for BuzzerSettings {
+    fn default() -> Self {
+        Self {
+            frequency: 440.0,
+            waveform: Waveform::Square,
+            volume: 0.25,
+        }
+    }
+}
+
+/// Generates the samples of the CHIP-8 buzzer, independently of any audio backend.
+///
+/// The buzzer sounds while the sound timer is above 0. On XO-CHIP the 1-bit audio pattern
+/// buffer is played at the rate set by the pitch register instead of the configured waveform.
+#[derive(Debug, Clone)]
+pub struct Buzzer {
+    settings: BuzzerSettings,
+    sample_rate: f32,
+    phase: f32,     // position within one period of the waveform, or bit index into the pattern
+    playing: bool,
+    muted: bool,
+    pattern: Option<[u8; 16]>,
+    pattern_rate: f32,
+}
+
+impl Buzzer {
+    pub fn new(settings: BuzzerSettings, sample_rate: f32) -> Self {
+        Self {
+            settings,
+            sample_rate,
+            phase: 0.0,
+            playing: false,
+            muted: false,
+            pattern: None,
+            pattern_rate: 0.0,
+        }
+    }
+
+    /// Copies the sound state of the emulator into the buzzer. Call once per frame.
+    pub fn sync(&mut self, c8: &Chip8) {
+        self.playing = c8.get_sound_timer() > 0;
+
+        if c8.get_platform().is_some_and(|platform| platform.supports_xochip()) {
+            self.pattern = Some(*c8.get_audio_pattern());
+            self.pattern_rate = c8.get_audio_playback_rate();
+        } else {
+            self.pattern = None;
+        }
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Fills `out` with mono samples in `[-volume, volume]`
+    pub fn fill(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            if !self.playing || self.muted {
+                *sample = 0.0;
+                continue;
+            }
+
+            *sample = match self.pattern {
+                Some(pattern) => {
+                    let bit_index = self.phase as usize % 128;
+                    let bit = (pattern[bit_index / 8] >> (7 - bit_index % 8)) & 1;
+                    self.phase = (self.phase + self.pattern_rate / self.sample_rate) % 128.0;
+
+                    if bit == 1 { self.settings.volume } else { -self.settings.volume }
+                },
+                None => {
+                    let value = self.settings.waveform.sample(self.phase);
+                    self.phase = (self.phase + self.settings.frequency / self.sample_rate) % 1.0;
+
+                    value * self.settings.volume
+                },
+            };
+        }
+    }
+}
+
+/// Somewhere the buzzer can be played
+pub trait AudioSink {
+    /// Updates the sink with the sound state of the emulator. Called once per frame.
+    fn update(&mut self, c8: &Chip8);
+
+    fn set_muted(&mut self, muted: bool);
+
+    fn is_muted(&self) -> bool;
+}
+
+/// An `AudioSink` that discards all sound, for headless runs
+#[derive(Debug, Default)]
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn update(&mut self, _c8: &Chip8) {}
+
+    fn set_muted(&mut self, _muted: bool) {}
+
+    fn is_muted(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::platform::Platform;
+
+    fn playing_chip8(platform: Option<Platform>) -> Chip8 {
+        let mut c8 = match platform {
+            Some(platform) => Chip8::with_platform(platform),
+            None => Chip8::from_rom_bytes(&[]).unwrap(),
+        };
+        c8.set_sound_timer(10);
+        c8
+    }
+
+    #[test]
+    fn test_square_wave() {
+        let settings = BuzzerSettings { frequency: 1000.0, waveform: Waveform::Square, volume: 0.5 };
+        let mut buzzer = Buzzer::new(settings, 8000.0);
+        buzzer.sync(&playing_chip8(None));
+
+        let mut out = [0.0; 8];
+        buzzer.fill(&mut out);
+
+        assert_eq!(out, [0.5, 0.5, 0.5, 0.5, -0.5, -0.5, -0.5, -0.5]);
+    }
+
+    #[test]
+    fn test_silent_without_sound_timer() {
+        let mut buzzer = Buzzer::new(BuzzerSettings::default(), 8000.0);
+        let mut c8 = playing_chip8(None);
+        c8.set_sound_timer(0);
+        buzzer.sync(&c8);
+
+        let mut out = [1.0; 8];
+        buzzer.fill(&mut out);
+
+        assert!(!buzzer.is_playing());
+        assert_eq!(out, [0.0; 8]);
+    }
+
+    #[test]
+    fn test_muted() {
+        let mut buzzer = Buzzer::new(BuzzerSettings::default(), 8000.0);
+        buzzer.sync(&playing_chip8(None));
+        buzzer.set_muted(true);
+
+        let mut out = [1.0; 8];
+        buzzer.fill(&mut out);
+
+        assert_eq!(out, [0.0; 8]);
+    }
+
+    #[test]
+    fn test_xochip_pattern() {
+        let mut buzzer = Buzzer::new(BuzzerSettings::default(), 4000.0);
+        let mut c8 = playing_chip8(Some(Platform::XoChip));
+        c8.get_memory_mut()[0x300] = 0b1010_0000;
+        c8.set_index_register(0x300);
+        c8.execute_opcode(0xf002).unwrap();
+        buzzer.sync(&c8);
+
+        let mut out = [0.0; 4];
+        buzzer.fill(&mut out);
+
+        assert_eq!(out, [0.25, -0.25, 0.25, -0.25]);
+    }
+
+    #[test]
+    fn test_waveform_from_name() {
+        assert_eq!(Waveform::from_name("Sine"), Some(Waveform::Sine));
+        assert_eq!(Waveform::from_name("noise"), None);
+    }
+}
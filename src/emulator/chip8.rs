@@ -1,28 +1,56 @@
-use rand::Rng;
-use super::utils::get_bits_of_byte;
+use super::error::Chip8Error;
+use super::instruction::Instruction;
+use super::platform::Platform;
+use super::quirks::Quirks;
+use super::rng::Rng;
+use super::tracer::{TraceEntry, TraceSnapshot};
+use super::state::{invalid_state, rom_hash, StateReader, StateWriter, STATE_MAGIC, STATE_VERSION};
+use super::utils::{get_bits_of_byte, is_hex_text};
 
 use super::constants::{
+    BIG_FONT_SET,
+    BIG_FONT_SET_START_ADDRESS,
     FONT_SET,
     FONT_SET_START_ADDRESS,
+    HIRES_HEIGHT,
+    HIRES_WIDTH,
+    LORES_HEIGHT,
+    LORES_WIDTH,
+    DEFAULT_PITCH,
     PIXEL_OFF,
     PIXEL_ON,
     PROGRAM_START_ADDRESS,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Chip8 {
     registers: [u8; 16],
-    memory: [u8; 4096],
+    memory: Vec<u8>,
+    decoded: Vec<Option<Instruction>>,   // instruction starting at each address, until it is written to
     index_register: u16,
     pc: u16,            // program counter
-    stack: [u16; 16],
+    stack: Vec<u16>,
     sp: u8,             // stack pointer
     delay_timer: u8,
     sound_timer: u8,
     keypad: [u8; 16],
-    display_memory: [[u32; 64]; 32],
-
-    rng: rand::rngs::ThreadRng,
+    display_memory: Vec<Vec<u32>>,   // indexed as [y][x]; resized when switching resolution
+    plane2_memory: Vec<Vec<u32>>,    // second XO-CHIP bitplane, same layout as display_memory
+    selected_planes: u8,             // bit 0 selects display_memory, bit 1 selects plane2_memory
+    hires: bool,
+    rpl_flags: [u8; 16],
+    exited: bool,
+    audio_pattern: [u8; 16],
+    pitch: u8,
+
+    platform: Option<Platform>,
+    quirks: Quirks,
+    drawn_this_frame: bool,   // used by the display wait quirk
+
+    rng: Rng,
+    rom_hash: u64,            // identifies the loaded ROM in save states
+
+    trace: Option<Vec<TraceEntry>>,   // executed instructions not yet taken, while tracing
 }
 
 // setup methods
@@ -30,205 +58,486 @@ impl Chip8 {
     fn _new() -> Self {
         Self {
             registers: [0; 16],
-            memory: [0; 4096],
+            memory: vec![0; 4096],
+            decoded: vec![None; 4096],
             index_register: 0,
             pc: PROGRAM_START_ADDRESS as u16,
-            stack: [0; 16],
+            stack: vec![0; 16],
             sp: 0,
             delay_timer: 0,
             sound_timer: 0,
             keypad: [0; 16],
-            display_memory: [[PIXEL_OFF; 64]; 32],
-
-            rng: rand::thread_rng(),
+            display_memory: vec![vec![PIXEL_OFF; LORES_WIDTH]; LORES_HEIGHT],
+            plane2_memory: vec![vec![PIXEL_OFF; LORES_WIDTH]; LORES_HEIGHT],
+            selected_planes: 0b01,
+            hires: false,
+            rpl_flags: [0; 16],
+            exited: false,
+            audio_pattern: [0; 16],
+            pitch: DEFAULT_PITCH,
+
+            platform: None,
+            quirks: Quirks::default(),
+            drawn_this_frame: false,
+
+            rng: Rng::from_entropy(),
+            rom_hash: rom_hash(&[]),
+
+            trace: None,
         }
     }
 
     /// Creates and initializes a new Chip8 instance with the given instructions
-    pub fn new(instruction_file: &str) -> Self {
-        let mut c8 = Self {
-            registers: [0; 16],
-            memory: [0; 4096],
-            index_register: 0,
-            pc: PROGRAM_START_ADDRESS as u16,
-            stack: [0; 16],
-            sp: 0,
-            delay_timer: 0,
-            sound_timer: 0,
-            keypad: [0; 16],
-            display_memory: [[PIXEL_OFF; 64]; 32],
+    pub fn new(instruction_file: &str) -> Result<Self, Chip8Error> {
+        Self::from_rom_file(instruction_file)
+    }
+
+    /// Creates a Chip8 instance set up like the given platform, with its font set loaded
+    /// and no program in memory.
+    ///
+    /// The other constructors keep this emulator's original setup: 4 KiB of memory, a 16 level
+    /// stack, the standard font set and `Quirks::default()`.
+    pub fn with_platform(platform: Platform) -> Self {
+        let mut c8 = Self::_new();
+        c8.memory = vec![0; platform.memory_size()];
+        c8.stack = vec![0; platform.stack_depth()];
+        c8.quirks = platform.quirks();
+        c8.platform = Some(platform);
+        c8.load_font(platform.font_set());
+        if platform.supports_schip() {
+            c8.memory[BIG_FONT_SET_START_ADDRESS..BIG_FONT_SET_START_ADDRESS + BIG_FONT_SET.len()].copy_from_slice(&BIG_FONT_SET);
+        }
+        c8.clear_decoded();
 
-            rng: rand::thread_rng(),
-        };
+        c8
+    }
+
+    /// Creates and initializes a new Chip8 instance from a raw binary ROM image
+    pub fn from_rom_bytes(rom: &[u8]) -> Result<Self, Chip8Error> {
+        let mut c8 = Self::_new();
         c8.load_fontset();
-        c8.load_instructions_from_file(instruction_file);
+        c8.load_rom_bytes(rom)?;
 
-        c8
+        Ok(c8)
+    }
+
+    /// Creates and initializes a new Chip8 instance from a `.ch8` file.
+    ///
+    /// Both binary ROMs and hex-text dumps (whitespace-separated 16-bit words) are accepted;
+    /// the format is detected from the file contents.
+    pub fn from_rom_file(file_path: &str) -> Result<Self, Chip8Error> {
+        let mut c8 = Self::_new();
+        c8.load_fontset();
+        c8.load_rom_file(file_path)?;
+
+        Ok(c8)
+    }
+
+    /// Restarts the random number generator behind `Cxkk` from `seed`. Runs with the same seed,
+    /// ROM and input produce the same random bytes.
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
     }
 
-    /// Reads instructions from a `.ch8` file and loads the opcodes into memory
-    fn load_instructions_from_file(&mut self, file_path: &str) {
-        let contents = std::fs::read_to_string(file_path).unwrap();
+    /// Replaces the random number generator behind `Cxkk`
+    pub fn set_rng(&mut self, rng: Rng) {
+        self.rng = rng;
+    }
 
-        let mut opcodes = Vec::new();
+    /// Reads instructions from a `.ch8` file and loads the opcodes into memory.
+    /// Binary and hex-text files are both accepted.
+    pub fn load_rom_file(&mut self, file_path: &str) -> Result<(), Chip8Error> {
+        let contents = std::fs::read(file_path).map_err(|e| Chip8Error::Io {
+            path: file_path.to_string(),
+            reason: e.to_string(),
+        })?;
 
-        for value in contents.trim().replace("\n", " ").split(" ") {
-            let hex_value = match u16::from_str_radix(value, 16) {
-                Ok(v) => v,
-                Err(e) => {
-                    panic!("Error parsing instruction file. Tried converting {} and got {}", value, e)
-                }
-            };
-            opcodes.push(hex_value);
+        if is_hex_text(&contents) {
+            let text = String::from_utf8_lossy(&contents);
+            self.load_instructions_from_hex_text(&text)
+        } else {
+            self.load_rom_bytes(&contents)
         }
+    }
 
-        self.load_opcodes_into_memory(&opcodes);
+    /// Parses whitespace-separated hex words and loads the opcodes into memory
+    fn load_instructions_from_hex_text(&mut self, contents: &str) -> Result<(), Chip8Error> {
+        let opcodes = parse_hex_text(contents)?;
+
+        self.load_opcodes_into_memory(&opcodes)
     }
 
-    /// Given an array of 16-bit opcodes, loads them into memory
-    /// starting at address 0x200, in little-endian order
-    fn load_opcodes_into_memory(&mut self, opcodes: &Vec<u16>) {
-        let mut i = PROGRAM_START_ADDRESS;
+    /// Loads a raw binary ROM image into memory starting at address 0x200
+    pub fn load_rom_bytes(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        let max_size = self.memory.len() - PROGRAM_START_ADDRESS;
+        if rom.len() > max_size {
+            return Err(Chip8Error::RomTooLarge { size: rom.len(), max_size });
+        }
+
+        self.memory[PROGRAM_START_ADDRESS..PROGRAM_START_ADDRESS + rom.len()].copy_from_slice(rom);
+        self.clear_decoded();
+        self.rom_hash = rom_hash(rom);
 
-        for opcode in opcodes {
-            let little_end = (*opcode & 0x00FF) as u8;
-            let big_end = ((*opcode & 0xFF00) >> 8) as u8;
+        Ok(())
+    }
 
-            self.memory[i] = little_end;
-            self.memory[i+1] = big_end;
+    /// Given an array of 16-bit opcodes, loads them into memory
+    /// starting at address 0x200, in big-endian order
+    fn load_opcodes_into_memory(&mut self, opcodes: &[u16]) -> Result<(), Chip8Error> {
+        let bytes: Vec<u8> = opcodes.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
 
-            i += 2;
-        }
+        self.load_rom_bytes(&bytes)
     }
 
     /// Loads fontsets into memory starting at address 0x50
     fn load_fontset(&mut self) {
-        for i in 0..FONT_SET.len() {
-            self.memory[FONT_SET_START_ADDRESS + i] = FONT_SET[i];
-        }
+        self.load_font(&FONT_SET);
     }
+
+    fn load_font(&mut self, font_set: &[u8; 80]) {
+        self.memory[FONT_SET_START_ADDRESS..FONT_SET_START_ADDRESS + font_set.len()].copy_from_slice(font_set);
+        self.clear_decoded();
+    }
+}
+
+/// Reads the ROM image from a `.ch8` file, converting hex-text dumps to binary
+pub fn read_rom_file(file_path: &str) -> Result<Vec<u8>, Chip8Error> {
+    let contents = std::fs::read(file_path).map_err(|e| Chip8Error::Io {
+        path: file_path.to_string(),
+        reason: e.to_string(),
+    })?;
+
+    if is_hex_text(&contents) {
+        let opcodes = parse_hex_text(&String::from_utf8_lossy(&contents))?;
+        Ok(opcodes.iter().flat_map(|opcode| opcode.to_be_bytes()).collect())
+    } else {
+        Ok(contents)
+    }
+}
+
+/// Parses whitespace-separated hex words
+fn parse_hex_text(contents: &str) -> Result<Vec<u16>, Chip8Error> {
+    let mut opcodes = Vec::new();
+
+    for value in contents.split_whitespace() {
+        let hex_value = u16::from_str_radix(value, 16).map_err(|e| Chip8Error::ParseError {
+            word: value.to_string(),
+            reason: e.to_string(),
+        })?;
+        opcodes.push(hex_value);
+    }
+
+    Ok(opcodes)
 }
 
 // operation methods
 impl Chip8 {
     /// Returns a random byte valued in the range `[0, 255]`
     fn rand_byte(&mut self) -> u8 {
-        self.rng.gen::<u8>()
+        self.rng.next_u8()
     }
 
-    /// `00E0`: Completely clear the display memory
-    fn cls(&mut self, _opcode: u16) {
-        for i in 0..32 {
-            for j in 0..64 {
-                self.display_memory[i][j] = PIXEL_OFF;
+    /// Reads the byte at `address`, failing if it lies outside of memory
+    fn read_memory(&self, address: usize) -> Result<u8, Chip8Error> {
+        match self.memory.get(address) {
+            Some(value) => Ok(*value),
+            None => Err(Chip8Error::MemoryOutOfBounds { address }),
+        }
+    }
+
+    /// Writes `value` to `address`, failing if it lies outside of memory
+    fn write_memory(&mut self, address: usize, value: u8) -> Result<(), Chip8Error> {
+        match self.memory.get_mut(address) {
+            Some(byte) => {
+                *byte = value;
+                self.invalidate_decoded(address);
+                Ok(())
+            },
+            None => Err(Chip8Error::MemoryOutOfBounds { address }),
+        }
+    }
+
+    /// Forgets the decoded instructions overlapping `address`, the ones starting there and
+    /// one byte earlier
+    fn invalidate_decoded(&mut self, address: usize) {
+        for start in address.saturating_sub(1)..=address {
+            if let Some(instruction) = self.decoded.get_mut(start) {
+                *instruction = None;
             }
         }
     }
 
+    /// Forgets every decoded instruction. Must be called whenever memory is replaced or
+    /// written without `write_memory`.
+    fn clear_decoded(&mut self) {
+        // reuses the table; rewinding replaces memory every frame
+        self.decoded.resize(self.memory.len(), None);
+        self.decoded.fill(None);
+    }
+
+    /// Address of the instruction currently being executed. `pc` is advanced before execution.
+    fn current_instruction_address(&self) -> u16 {
+        self.pc.wrapping_sub(2)
+    }
+
+    fn supports_schip(&self) -> bool {
+        self.platform.is_some_and(|platform| platform.supports_schip())
+    }
+
+    fn supports_xochip(&self) -> bool {
+        self.platform.is_some_and(|platform| platform.supports_xochip())
+    }
+
+    /// Skips over the next instruction, which is 4 bytes long if it is an XO-CHIP `F000 nnnn`
+    fn skip_next_instruction(&mut self) {
+        let pc = usize::from(self.pc);
+        let next_is_long = self.supports_xochip()
+            && self.memory.get(pc) == Some(&0xF0)
+            && self.memory.get(pc + 1) == Some(&0x00);
+
+        self.pc = self.pc.wrapping_add(if next_is_long { 4 } else { 2 });
+    }
+
+    /// Resizes both bitplanes to the given resolution. XO-CHIP clears them; SUPER-CHIP 1.1 keeps
+    /// the picture, scaled to the new resolution.
+    fn set_resolution(&mut self, hires: bool) {
+        let (width, height) = if hires { (HIRES_WIDTH, HIRES_HEIGHT) } else { (LORES_WIDTH, LORES_HEIGHT) };
+        let clear = self.supports_xochip();
+
+        self.hires = hires;
+        self.display_memory = Chip8::resize_plane(&self.display_memory, width, height, clear);
+        self.plane2_memory = Chip8::resize_plane(&self.plane2_memory, width, height, clear);
+    }
+
+    /// A copy of `plane` at `width`x`height`, blank if `clear`. Each lo-res pixel covers 2x2 hi-res pixels.
+    fn resize_plane(plane: &[Vec<u32>], width: usize, height: usize, clear: bool) -> Vec<Vec<u32>> {
+        if clear {
+            return vec![vec![PIXEL_OFF; width]; height];
+        }
+
+        let (old_width, old_height) = (plane[0].len(), plane.len());
+        (0..height)
+            .map(|y| (0..width).map(|x| plane[y * old_height / height][x * old_width / width]).collect())
+            .collect()
+    }
+
+    /// Runs `f` on each bitplane selected by `FN01`. Only the first plane is ever selected
+    /// outside of XO-CHIP.
+    fn for_each_selected_plane<F: FnMut(&mut Vec<Vec<u32>>)>(&mut self, mut f: F) {
+        if self.selected_planes & 0b01 != 0 {
+            f(&mut self.display_memory);
+        }
+        if self.selected_planes & 0b10 != 0 {
+            f(&mut self.plane2_memory);
+        }
+    }
+
+    /// `00E0`: Completely clear the display memory of the selected planes
+    fn cls(&mut self) -> Result<(), Chip8Error> {
+        self.for_each_selected_plane(|plane| {
+            for row in plane.iter_mut() {
+                row.fill(PIXEL_OFF);
+            }
+        });
+
+        Ok(())
+    }
+
     /// `00EE`: Return from a subroutine
-    fn ret(&mut self, _opcode: u16) {
+    fn ret(&mut self) -> Result<(), Chip8Error> {
+        if self.sp == 0 {
+            return Err(Chip8Error::StackUnderflow { address: self.current_instruction_address() });
+        }
+
         self.sp -= 1;
         self.pc = self.stack[self.sp as usize];
+
+        Ok(())
+    }
+
+    /// `00Cn`: Scroll the selected planes down by `n` pixels. Rows scrolled in from the top are blank.
+    fn scroll_down(&mut self, n: u8) -> Result<(), Chip8Error> {
+        let n = usize::from(n).min(self.display_memory.len());
+
+        self.for_each_selected_plane(|plane| {
+            plane.rotate_right(n);
+            for row in plane.iter_mut().take(n) {
+                row.fill(PIXEL_OFF);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// `00FB`: Scroll the selected planes right by 4 pixels
+    fn scroll_right(&mut self) -> Result<(), Chip8Error> {
+        self.for_each_selected_plane(|plane| {
+            for row in plane.iter_mut() {
+                row.rotate_right(4);
+                row[..4].fill(PIXEL_OFF);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// `00FC`: Scroll the selected planes left by 4 pixels
+    fn scroll_left(&mut self) -> Result<(), Chip8Error> {
+        self.for_each_selected_plane(|plane| {
+            for row in plane.iter_mut() {
+                let width = row.len();
+                row.rotate_left(4);
+                row[width - 4..].fill(PIXEL_OFF);
+            }
+        });
+
+        Ok(())
+    }
+
+    /// `00FD`: Exit the interpreter. No further instructions are executed.
+    fn exit(&mut self) -> Result<(), Chip8Error> {
+        self.exited = true;
+
+        Ok(())
+    }
+
+    /// `00FE`: Switch to the 64x32 low resolution mode, clearing the display on XO-CHIP
+    fn lores(&mut self) -> Result<(), Chip8Error> {
+        self.set_resolution(false);
+
+        Ok(())
+    }
+
+    /// `00FF`: Switch to the 128x64 high resolution mode, clearing the display on XO-CHIP
+    fn hires(&mut self) -> Result<(), Chip8Error> {
+        self.set_resolution(true);
+
+        Ok(())
     }
 
     /// `1nnn`: Jump to address `nnn` (`self.pc` -> `nnn`)
-    fn jmp(&mut self, opcode: u16) {
-        self.pc = opcode & 0x0FFF;
+    fn jmp(&mut self, nnn: u16) -> Result<(), Chip8Error> {
+        self.pc = nnn;
+
+        Ok(())
     }
 
     /// `2nnn`: Call the subroutine at `nnn`
-    fn call(&mut self, opcode: u16) {
+    fn call(&mut self, nnn: u16) -> Result<(), Chip8Error> {
+        if usize::from(self.sp) >= self.stack.len() {
+            return Err(Chip8Error::StackOverflow { address: self.current_instruction_address() });
+        }
+
         self.stack[self.sp as usize] = self.pc;
         self.sp += 1;
 
-        self.pc = opcode & 0x0FFF;
+        self.pc = nnn;
+
+        Ok(())
     }
 
     /// `3xkk`: Skip the next instruction if `Vx == kk`
-    fn se_byte(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as u8;
-        let kk = (opcode & 0x00FF) as u8;
-
+    fn se_byte(&mut self, x: u8, kk: u8) -> Result<(), Chip8Error> {
         let vx = self.registers[usize::from(x)];
         
         if vx == kk {
-            self.pc += 0x02;
+            self.skip_next_instruction();
         }
+
+        Ok(())
     }
 
     /// `4xkk`: Skip the next instruction if `Vx != kk`
-    fn sne_byte(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as u8;
-        let kk = (opcode & 0x00FF) as u8;
-
+    fn sne_byte(&mut self, x: u8, kk: u8) -> Result<(), Chip8Error> {
         let vx = self.registers[usize::from(x)];
         if vx != kk {
-            self.pc += 0x02;
+            self.skip_next_instruction();
         }
+
+        Ok(())
     }
 
     /// `5xy0`: Skip the next instruction if `Vx == Vy`
-    fn se_register(&mut self, opcode: u16) {
-        let x = usize::from((opcode & 0x0F00) >> 8);
-        let y = usize::from((opcode & 0x00F0) >> 4);
+    fn se_register(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
+        let (x, y) = (usize::from(x), usize::from(y));
 
         let vx = self.registers[x];
         let vy = self.registers[y];
 
         if vx == vy {
-            self.pc += 2;
+            self.skip_next_instruction();
         }
+
+        Ok(())
     }
 
     /// `6xkk`: Load `kk` into `Vx`
-    fn ld_byte(&mut self, opcode: u16) {
-        let x = usize::from((opcode & 0x0F00) >> 8);
-        let kk = (opcode & 0x00FF) as u8;
+    fn ld_byte(&mut self, x: u8, kk: u8) -> Result<(), Chip8Error> {
+        let x = usize::from(x);
 
         self.registers[x] = kk;
+
+        Ok(())
     }
     
     /// `7xkk`: Add `kk` with the value stored in `Vx` and store the result in `Vx`
-    fn add_byte(&mut self, opcode: u16) {
-        let x = usize::from((opcode & 0x0F00) >> 8);
-        let kk = (opcode & 0x00FF) as u8;
+    fn add_byte(&mut self, x: u8, kk: u8) -> Result<(), Chip8Error> {
+        let x = usize::from(x);
 
         self.registers[x] = self.registers[x].wrapping_add(kk);
+
+        Ok(())
     }
 
     /// `8xy0`: Store the value in `Vy` into `Vx`
-    fn ld_register(&mut self, opcode: u16) {
-        let x = usize::from((opcode & 0x0F00) >> 8);
-        let y = usize::from((opcode & 0x00F0) >> 4);
+    fn ld_register(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
+        let (x, y) = (usize::from(x), usize::from(y));
 
         self.registers[x] = self.registers[y];
+
+        Ok(())
     }
 
     /// `8xy1`: Perform a bitwise OR on the values stored in `Vx`` and `Vy`
     /// then store the result in `Vx`
-    fn or(&mut self, opcode: u16) {
-        let x = usize::from((opcode & 0x0F00) >> 8);
-        let y = usize::from((opcode & 0x00F0) >> 4);
+    fn or(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
+        let (x, y) = (usize::from(x), usize::from(y));
+
+        self.registers[x] |= self.registers[y];
+
+        if self.quirks.vf_reset {
+            self.registers[0xF] = 0;
+        }
 
-        self.registers[x] = self.registers[x] | self.registers[y];
+        Ok(())
     }
 
     /// `8xy2`: Perform a bitwise AND on the values stored in `Vx` and `Vy`
     /// then store the result in `Vx`
-    fn and(&mut self, opcode: u16) {
-        let x = usize::from((opcode & 0x0F00) >> 8);
-        let y = usize::from((opcode & 0x00F0) >> 4);
+    fn and(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
+        let (x, y) = (usize::from(x), usize::from(y));
+
+        self.registers[x] &= self.registers[y];
+
+        if self.quirks.vf_reset {
+            self.registers[0xF] = 0;
+        }
 
-        self.registers[x] = self.registers[x] & self.registers[y]
+        Ok(())
     }
 
     /// `8xy3`: Perform a bitwise XOR on the values stored in `Vx`` and `Vy`
     /// then store the result in `Vx`
-    fn xor(&mut self, opcode: u16) {
-        let x = usize::from((opcode & 0x0F00) >> 8);
-        let y = usize::from((opcode & 0x00F0) >> 4);
+    fn xor(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
+        let (x, y) = (usize::from(x), usize::from(y));
 
-        self.registers[x] = self.registers[x] ^ self.registers[y]
+        self.registers[x] ^= self.registers[y];
+
+        if self.quirks.vf_reset {
+            self.registers[0xF] = 0;
+        }
+
+        Ok(())
     }
 
     /// `8xy4`: Perform an addition with the values in `Vx` and `Vy` then store the
@@ -236,21 +545,21 @@ impl Chip8 {
     /// 
     /// If the result exceeds the capacity of a u8, `VF` is set to 1, otherwise it is set to 0.
     /// Only the rightmost 8 bits of the result is stored in `Vx`.
-    fn add_registers(&mut self, opcode: u16) {
-        let x = usize::from((opcode & 0x0F00) >> 8);
-        let y = usize::from((opcode & 0x00F0) >> 4);
+    fn add_registers(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
+        let (x, y) = (usize::from(x), usize::from(y));
 
         let res = self.registers[x].wrapping_add(self.registers[y]);
 
         self.registers[0xF] = if res < self.registers[x] { 1 } else { 0 };
 
         self.registers[x] = res;
+
+        Ok(())
     }
 
     /// `8xy5`: Subtract `Vx - Vy` and store the result in `Vx`. If `Vx > Vy`, `VF` is set to 1, otherwise 0.
-    fn sub_registers(&mut self, opcode: u16) {
-        let x = usize::from((opcode & 0x0F00) >> 8);
-        let y = usize::from((opcode & 0x00F0) >> 4);
+    fn sub_registers(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
+        let (x, y) = (usize::from(x), usize::from(y));
 
         let vx = self.registers[x];
         let vy = self.registers[y];
@@ -258,13 +567,17 @@ impl Chip8 {
         self.registers[0xF] = if vx > vy { 0x1 } else { 0x0 };
 
         self.registers[x] = vx.wrapping_sub(vy);
+
+        Ok(())
     }
 
     /// `8xy6`: If the least-significant bit of Vy is 1, then VF is set to 1, otherwise 0.
     /// Then Vy is shifted right by 1 and the result is stored in Vx.
-    fn shr(&mut self, opcode: u16) {
-        let x = usize::from((opcode & 0x0F00) >> 8);
-        let y = usize::from((opcode & 0x00F0) >> 4);
+    ///
+    /// With the `shift_vx` quirk, Vx is shifted in place and Vy is ignored.
+    fn shr(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
+        let x = usize::from(x);
+        let y = if self.quirks.shift_vx { x } else { usize::from(y) };
 
         let vy = self.registers[y];
 
@@ -273,12 +586,13 @@ impl Chip8 {
         self.registers[0xF] = if lsb == 1 { 1 } else { 0 };
 
         self.registers[x] = vy >> 1;
+
+        Ok(())
     }
 
     /// `8xy7`: Subtract `Vy - Vx` and store the result in `Vx`. If `Vy > Vx`, `VF` is set to 1, otherwise 0.
-    fn subn_registers(&mut self, opcode: u16) {
-        let x = usize::from((opcode & 0x0F00) >> 8);
-        let y = usize::from((opcode & 0x00F0) >> 4);
+    fn subn_registers(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
+        let (x, y) = (usize::from(x), usize::from(y));
 
         let vx = self.registers[x];
         let vy = self.registers[y];
@@ -286,13 +600,17 @@ impl Chip8 {
         self.registers[0xF] = if vy > vx { 0x1 } else { 0x0 };
 
         self.registers[x] = vy.wrapping_sub(vx);
+
+        Ok(())
     }
 
     /// `8xyE`: If the most-significant bit of Vy is 1, then VF is set to 1, otherwise to 0.
     /// Then Vy is shifted left by 1 and the result is stored in Vx.
-    fn shl(&mut self, opcode: u16) {
-        let x = usize::from((opcode & 0x0F00) >> 8);
-        let y = usize::from((opcode & 0x00F0) >> 4);
+    ///
+    /// With the `shift_vx` quirk, Vx is shifted in place and Vy is ignored.
+    fn shl(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
+        let x = usize::from(x);
+        let y = if self.quirks.shift_vx { x } else { usize::from(y) };
 
         let vy = self.registers[y];
 
@@ -301,41 +619,47 @@ impl Chip8 {
         self.registers[0xF] = if msb == 1 { 0x1 } else { 0x0 };
 
         self.registers[x] = vy << 1;
+
+        Ok(())
     }
 
     /// `9xy0`: Skip the next instruction if `Vx != Vy`
-    fn sne_register(&mut self, opcode: u16) {
-        let x = ((opcode & 0x0F00) >> 8) as u8;
-        let y = ((opcode & 0x00F0) >> 4) as u8;
-
+    fn sne_register(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
         let vx = self.registers[usize::from(x)];
         let vy = self.registers[usize::from(y)];
 
         if vx != vy {
-            self.pc += 2;
+            self.skip_next_instruction();
         }
+
+        Ok(())
     }
 
     /// `Annn`: Stores address `nnn` in `self.index_register`
-    fn ld_i(&mut self, opcode: u16) {
-        let nnn = opcode & 0x0FFF;
-
+    fn ld_i(&mut self, nnn: u16) -> Result<(), Chip8Error> {
         self.index_register = nnn;
+
+        Ok(())
     }
 
     /// `Bnnn`: Jump to the address `nnn + V0`
-    fn jmp_v0(&mut self, opcode: u16) {
-        let nnn = opcode & 0x0FFF;
-        self.pc = nnn + u16::from(self.registers[0x0]);
+    ///
+    /// With the `jump_with_vx` quirk this is `Bxnn`: jump to the address `xnn + Vx`
+    fn jmp_v0(&mut self, nnn: u16) -> Result<(), Chip8Error> {
+        let register = if self.quirks.jump_with_vx { usize::from(nnn >> 8) } else { 0x0 };
+        self.pc = nnn + u16::from(self.registers[register]);
+
+        Ok(())
     }
 
     /// `Cxkk`: Perform a bitwise AND between a random byte and `kk`. Store the value in `Vx`
     /// `Vx -> RAND & kk`
-    fn rand(&mut self, opcode: u16) {
-        let x = usize::from((opcode & 0x0F00) >> 8);
-        let kk = (opcode & 0x00FF) as u8;
+    fn rand(&mut self, x: u8, kk: u8) -> Result<(), Chip8Error> {
+        let x = usize::from(x);
 
         self.registers[x] = self.rand_byte() & kk;
+
+        Ok(())
     }
 
     /// `Dxyn`: Read `n` bytes from memory starting at the address stored in `index_register`.
@@ -345,116 +669,200 @@ impl Chip8 {
     /// each bit in a byte represents a pixel on the screen. For example: a 4 byte sprite will have pixel
     /// dimensions 8x4: 8px wide, 4px tall.
     /// 
+    /// On SUPER-CHIP platforms `Dxy0` draws a 16x16 sprite, read from 32 bytes with 2 bytes per row.
+    /// 
+    /// On XO-CHIP the sprite is drawn on every plane selected by `FN01`. When both planes are selected,
+    /// the sprite data for the second plane immediately follows the data for the first.
+    /// 
     /// Sprites are XOR'd onto the existing screen. If this causes any pixels to be erased, `VF` is
     /// set to 1, otherwise it is set to 0.
     /// 
     /// If the sprite is positioned so part of it is outside the coordinates of the display, it wraps
-    /// around to the opposite side of the screen
+    /// around to the opposite side of the screen. With the `clip_sprites` quirk only the starting
+    /// coordinates wrap and the parts of the sprite past the edge are not drawn.
+    /// 
+    /// With the `display_wait` quirk, only one sprite is drawn per frame; further draws are retried
+    /// after the next `vertical_blank`.
     /// 
     /// This instruction does not change `index_register`.
-    fn draw(&mut self, opcode: u16) {
-        let vx = self.registers[usize::from((opcode & 0x0F00) >> 8)];
-        let vy = self.registers[usize::from((opcode & 0x00F0) >> 4)];
-        let n = usize::from(opcode & 0x000F);
+    fn draw(&mut self, x: u8, y: u8, n: u8) -> Result<(), Chip8Error> {
+        if self.quirks.display_wait {
+            if self.drawn_this_frame {
+                // wait by running this op again on the next cycle
+                self.pc = self.pc.wrapping_sub(2);
+                return Ok(());
+            }
+            self.drawn_this_frame = true;
+        }
+
+        let vx = self.registers[usize::from(x)];
+        let vy = self.registers[usize::from(y)];
+        let n = usize::from(n);
 
-        // read n bytes from memory starting at index_register
-        let mut sprite = vec![0; n];
-        for i in 0..n {
-            sprite[i] = self.memory[self.index_register as usize + i];
+        let (bytes_per_row, rows) = if n == 0 && self.supports_schip() { (2, 16) } else { (1, n) };
+        let plane_count = self.selected_planes.count_ones() as usize;
+        let length = bytes_per_row * rows * plane_count;
+
+        // `Dxy0` outside of SUPER-CHIP and `F001`/`plane 0` draw nothing
+        if length == 0 {
+            self.registers[0xF] = 0;
+            return Ok(());
         }
 
+        // read the sprite from memory starting at index_register
+        let start = self.index_register as usize;
+        let sprite = match self.memory.get(start..start + length) {
+            Some(bytes) => bytes.to_vec(),
+            None => return Err(Chip8Error::MemoryOutOfBounds { address: start + length - 1 }),
+        };
+
+        let (width, height) = self.get_display_size();
+        let clip = self.quirks.clip_sprites;
+        let origin = (vx as usize % width, vy as usize % height);
+
         let mut vf = 0x0_u8;
+        let mut plane_sprites = sprite.chunks(bytes_per_row * rows);
+
+        self.for_each_selected_plane(|plane| {
+            if let Some(plane_sprite) = plane_sprites.next() {
+                if Chip8::draw_on_plane(plane, plane_sprite, bytes_per_row, origin, clip) {
+                    vf = 0x1;
+                }
+            }
+        });
+
+        self.registers[0xF] = vf;
+
+        Ok(())
+    }
+
+    /// XORs a sprite onto a single bitplane, returning true if any pixel was erased
+    fn draw_on_plane(plane: &mut [Vec<u32>], sprite: &[u8], bytes_per_row: usize, origin: (usize, usize), clip: bool) -> bool {
+        let (width, height) = (plane[0].len(), plane.len());
+        let (origin_x, origin_y) = origin;
+        let mut collision = false;
 
         // draw the pixels
-        for i in 0..n {
-            let y = (vy as usize + i) % 32;
-            let pixels = get_bits_of_byte(sprite[i]);
+        for (i, row) in sprite.chunks(bytes_per_row).enumerate() {
+            if clip && origin_y + i >= height {
+                break;
+            }
+            let y = (origin_y + i) % height;
+            let pixels: Vec<u8> = row.iter().flat_map(|byte| get_bits_of_byte(*byte)).collect();
 
-            for j in 0..8 {
-                let x = (vx as usize + j) % 64;
-                let pixel_state = pixels[j];
+            for (j, pixel_state) in pixels.iter().enumerate() {
+                if clip && origin_x + j >= width {
+                    break;
+                }
+                let x = (origin_x + j) % width;
+                let pixel_state = *pixel_state;
 
                 if pixel_state == 1_u8 {
-                    if self.display_memory[y][x] ^ PIXEL_ON == 0 {
+                    if plane[y][x] ^ PIXEL_ON == 0 {
                         // pixel overlaps with another
-                        vf = 0x1;
-                        self.display_memory[y][x] = PIXEL_OFF;
+                        collision = true;
+                        plane[y][x] = PIXEL_OFF;
                     } else {
-                        self.display_memory[y][x] = PIXEL_ON;
+                        plane[y][x] = PIXEL_ON;
                     }
                 }
             }
         }
 
-        self.registers[0xF] = vf;
+        collision
     }
 
     /// `Ex9E`: Skip the next instruction if the key with value `Vx` is pressed.
-    fn skip_key_pressed(&mut self, opcode: u16) {
-        let vx = self.registers[usize::from((opcode & 0x0F00) >> 8)];
+    fn skip_key_pressed(&mut self, x: u8) -> Result<(), Chip8Error> {
+        let vx = self.registers[usize::from(x)];
 
-        if self.keypad[vx as usize] == 1 {
-            self.pc += 2;
+        if self.keypad[usize::from(vx & 0x0F)] == 1 {
+            self.skip_next_instruction();
         }
+
+        Ok(())
     }
 
     /// `ExA1`: Skip the next instruction if the key with value `Vx` is not pressed.
-    fn skip_key_not_pressed(&mut self, opcode: u16) {
-        let vx = self.registers[usize::from((opcode & 0x0F00) >> 8)];
+    fn skip_key_not_pressed(&mut self, x: u8) -> Result<(), Chip8Error> {
+        let vx = self.registers[usize::from(x)];
 
-        if self.keypad[vx as usize] == 0 {
-            self.pc += 2;
+        if self.keypad[usize::from(vx & 0x0F)] == 0 {
+            self.skip_next_instruction();
         }
+
+        Ok(())
     }
 
     /// `Fx07`: Set `Vx = delay_timer`
-    fn ld_delay_timer(&mut self, opcode: u16) {
-        let x = usize::from((opcode & 0x0F00) >> 8);
+    fn ld_delay_timer(&mut self, x: u8) -> Result<(), Chip8Error> {
+        let x = usize::from(x);
         self.registers[x] = self.delay_timer;
+
+        Ok(())
     }
 
     /// `Fx0A`: Wait for a key press and store the value of the key in `Vx`
     /// 
     /// All executions stop until a key is pressed.
-    fn ld_key_press(&mut self, opcode: u16) {
-        let x = usize::from((opcode & 0x0F00) >> 8);
+    fn ld_key_press(&mut self, x: u8) -> Result<(), Chip8Error> {
+        let x = usize::from(x);
 
         for i in 0..self.keypad.len() {
             if self.keypad[i] != 0 {
                 self.registers[x] = i as u8;
-                return;
+                return Ok(());
             }
         }
 
         // none of the keys are pressed. Wait by running this op again on the next cycle
-        self.pc -= 2;
+        self.pc = self.pc.wrapping_sub(2);
+
+        Ok(())
     }
 
     /// `Fx15`: Set the delay_timer to the value of `Vx`
-    fn set_delay_timer(&mut self, opcode: u16) {
-        let vx = self.registers[usize::from((opcode & 0x0F00) >> 8)];
+    fn set_delay_timer_from_register(&mut self, x: u8) -> Result<(), Chip8Error> {
+        let vx = self.registers[usize::from(x)];
 
         self.delay_timer = vx;
+
+        Ok(())
     }
 
     /// `Fx18`: Set the sound_timer to the value of `Vx`
-    fn set_sound_timer(&mut self, opcode: u16) {
-        let vx = self.registers[usize::from((opcode & 0x0F00) >> 8)];
+    fn set_sound_timer_from_register(&mut self, x: u8) -> Result<(), Chip8Error> {
+        let vx = self.registers[usize::from(x)];
 
         self.sound_timer = vx;
+
+        Ok(())
     }
 
     /// `Fx1E`: Add `index_register` and `Vx` and store the result in `index_register`
-    fn add_index_register(&mut self, opcode: u16) {
-        let vx = self.registers[usize::from((opcode & 0x0F00) >> 8)];
+    fn add_index_register(&mut self, x: u8) -> Result<(), Chip8Error> {
+        let vx = self.registers[usize::from(x)];
         self.index_register = self.index_register.wrapping_add(vx as u16);
+
+        Ok(())
     }
 
     /// `Fx29`: Load the address of the sprite corresponding to the value of `Vx` into `index_register`.
-    fn ld_sprite(&mut self, opcode: u16) {
-        let vx = self.registers[usize::from((opcode & 0x0F00) >> 8)];
+    fn ld_sprite(&mut self, x: u8) -> Result<(), Chip8Error> {
+        let vx = self.registers[usize::from(x)];
+
+        self.index_register = FONT_SET_START_ADDRESS as u16 + u16::from(vx & 0x0F) * 5;
+
+        Ok(())
+    }
+
+    /// `Fx30`: Load the address of the 8x10 sprite for the hex digit in `Vx` into `index_register`.
+    fn ld_big_sprite(&mut self, x: u8) -> Result<(), Chip8Error> {
+        let vx = self.registers[usize::from(x)];
 
-        self.index_register = u16::from(0x50 + (vx * 5));
+        self.index_register = BIG_FONT_SET_START_ADDRESS as u16 + u16::from(vx & 0x0F) * 10;
+
+        Ok(())
     }
 
     /// `Fx33`:  Store BCD (binary-coded decimal) representation of `Vx` in
@@ -462,139 +870,296 @@ impl Chip8 {
     /// 
     /// Take the decimal value of Vx, and place the hundreds digit in memory at location in `index_register`,
     /// the tens digit at location `index_register+1`, and the ones digit at location `index_register+2`.
-    fn ld_bcd(&mut self, opcode: u16) {
-        let vx = self.registers[usize::from((opcode & 0x0F00) >> 8)];
+    fn ld_bcd(&mut self, x: u8) -> Result<(), Chip8Error> {
+        let vx = self.registers[usize::from(x)];
+
+        let address = usize::from(self.index_register);
+
+        self.write_memory(address, vx / 100)?;
+        self.write_memory(address + 1, (vx / 10) % 10)?;
+        self.write_memory(address + 2, vx % 10)?;
 
-        self.memory[usize::from(self.index_register)] = vx / 100;
-        self.memory[usize::from(self.index_register + 1)] = (vx / 10) % 10;
-        self.memory[usize::from(self.index_register + 2)] = vx % 10;
+        Ok(())
     }
 
     /// `Fx55`: Store registers `V0` through `Vx` into memory starting at the address in `index_register`
-    fn ld_registers_into_index_register(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
+    fn ld_registers_into_index_register(&mut self, x: u8) -> Result<(), Chip8Error> {
+        let x = u16::from(x);
 
         for i in 0..=x {
-            self.memory[usize::from(self.index_register + i)] = self.registers[i as usize];
+            self.write_memory(usize::from(self.index_register) + usize::from(i), self.registers[i as usize])?;
         }
 
-        self.index_register = self.index_register + x + 1;
+        if self.quirks.load_store_increment_i {
+            self.index_register = self.index_register.wrapping_add(self.load_store_increment(x));
+        }
+
+        Ok(())
+    }
+
+    /// Amount `Fx55`/`Fx65` advance `index_register` by with the `load_store_increment_i` quirk
+    fn load_store_increment(&self, x: u16) -> u16 {
+        if self.quirks.load_store_increment_by_x { x } else { x + 1 }
     }
 
     /// `Fx65`: Read values in memory starting at the address in `index_register`, storing them into registers
     /// `V0` to `Vx`
-    fn read_index_register_into_registers(&mut self, opcode: u16) {
-        let x = (opcode & 0x0F00) >> 8;
+    fn read_index_register_into_registers(&mut self, x: u8) -> Result<(), Chip8Error> {
+        let x = u16::from(x);
 
         for i in 0..=x {
-            self.registers[i as usize] = self.memory[usize::from(self.index_register + i)];
+            self.registers[i as usize] = self.read_memory(usize::from(self.index_register) + usize::from(i))?;
         }
 
-        self.index_register = self.index_register + x + 1;
+        if self.quirks.load_store_increment_i {
+            self.index_register = self.index_register.wrapping_add(self.load_store_increment(x));
+        }
+
+        Ok(())
     }
 }
 
-// opcode decoding and instruction router
+// SUPER-CHIP persistent flag methods
 impl Chip8 {
-    pub fn execute_opcode(&mut self, opcode: u16) {
-        // println!("Executing opcode: 0x{:x}", opcode);
-        let instruction = match self.select_instruction(opcode) {
-            Ok(func) => func,
-            Err(e) => panic!("{}", e),
-        };
+    /// `Fx75`: Store registers `V0` through `Vx` in the RPL user flags
+    fn ld_registers_into_flags(&mut self, x: u8) -> Result<(), Chip8Error> {
+        let x = usize::from(x);
+
+        self.rpl_flags[..=x].copy_from_slice(&self.registers[..=x]);
+
+        Ok(())
+    }
+
+    /// `Fx85`: Read registers `V0` through `Vx` from the RPL user flags
+    fn read_flags_into_registers(&mut self, x: u8) -> Result<(), Chip8Error> {
+        let x = usize::from(x);
 
-        instruction(self, opcode);
+        self.registers[..=x].copy_from_slice(&self.rpl_flags[..=x]);
+
+        Ok(())
+    }
+}
+
+// XO-CHIP methods
+impl Chip8 {
+    /// `5xy2`: Store registers `Vx` through `Vy` into memory starting at the address in `index_register`.
+    /// The registers are stored in reverse order if `x > y`. `index_register` is not changed.
+    fn ld_register_range_into_memory(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
+        let (x, y) = (usize::from(x), usize::from(y));
+        let start = usize::from(self.index_register);
+
+        for (offset, register) in Chip8::register_range(x, y).into_iter().enumerate() {
+            self.write_memory(start + offset, self.registers[register])?;
+        }
+
+        Ok(())
+    }
+
+    /// `5xy3`: Read registers `Vx` through `Vy` from memory starting at the address in `index_register`.
+    /// The registers are read in reverse order if `x > y`. `index_register` is not changed.
+    fn read_memory_into_register_range(&mut self, x: u8, y: u8) -> Result<(), Chip8Error> {
+        let (x, y) = (usize::from(x), usize::from(y));
+        let start = usize::from(self.index_register);
+
+        for (offset, register) in Chip8::register_range(x, y).into_iter().enumerate() {
+            self.registers[register] = self.read_memory(start + offset)?;
+        }
+
+        Ok(())
     }
 
-    fn select_instruction(&self, opcode: u16) -> Result<fn(&mut Chip8, u16), &'static str> {
-        let first_digit = (opcode & 0xF000) >> 12;
-        match first_digit {
-            0x0 => self.select_00e_instruction(opcode),
-            0x1 => Ok(Chip8::jmp),
-            0x2 => Ok(Chip8::call),
-            0x3 => Ok(Chip8::se_byte),
-            0x4 => Ok(Chip8::sne_byte),
-            0x5 => Ok(Chip8::se_register),
-            0x6 => Ok(Chip8::ld_byte),
-            0x7 => Ok(Chip8::add_byte),
-            0x8 => self.select_8_instruction(opcode),
-            0x9 => Ok(Chip8::sne_register),
-            0xA => Ok(Chip8::ld_i),
-            0xB => Ok(Chip8::jmp_v0),
-            0xC => Ok(Chip8::rand),
-            0xD => Ok(Chip8::draw),
-            0xE => self.select_e_instruction(opcode),
-            0xF => self.select_f_instruction(opcode),
-            _ => Err("No instruction for opcode"),
+    /// Register indices from `x` to `y` inclusive, counting down if `x > y`
+    fn register_range(x: usize, y: usize) -> Vec<usize> {
+        if x <= y {
+            (x..=y).collect()
+        } else {
+            (y..=x).rev().collect()
         }
     }
 
-    fn select_8_instruction(&self, opcode: u16) -> Result<fn(&mut Chip8, u16), &'static str> {
-        let last_digit = opcode & 0xF;
-        match last_digit {
-            0x0 => Ok(Chip8::ld_register),
-            0x1 => Ok(Chip8::or),
-            0x2 => Ok(Chip8::and),
-            0x3 => Ok(Chip8::xor),
-            0x4 => Ok(Chip8::add_registers),
-            0x5 => Ok(Chip8::sub_registers),
-            0x6 => Ok(Chip8::shr),
-            0x7 => Ok(Chip8::subn_registers),
-            0xE => Ok(Chip8::shl),
-            _ => Err("No instruction for opcode"),
+    /// `F000 nnnn`: Load the 16-bit address `nnnn` stored in the next two bytes into `index_register`
+    fn ld_i_long(&mut self) -> Result<(), Chip8Error> {
+        let high = self.read_memory(usize::from(self.pc))?;
+        let low = self.read_memory(usize::from(self.pc) + 1)?;
+
+        self.index_register = u16::from_be_bytes([high, low]);
+        self.pc = self.pc.wrapping_add(2);
+
+        Ok(())
+    }
+
+    /// `Fn01`: Select the bitplanes that drawing, clearing and scrolling instructions act on.
+    /// `n` is a bitmask: 1 for the first plane, 2 for the second, 3 for both.
+    fn select_planes(&mut self, n: u8) -> Result<(), Chip8Error> {
+        self.selected_planes = n & 0b11;
+
+        Ok(())
+    }
+
+    /// `F002`: Load the 16 bytes starting at `index_register` into the audio pattern buffer
+    fn ld_audio_pattern(&mut self) -> Result<(), Chip8Error> {
+        let start = usize::from(self.index_register);
+
+        for i in 0..self.audio_pattern.len() {
+            self.audio_pattern[i] = self.read_memory(start + i)?;
         }
+
+        Ok(())
+    }
+
+    /// `Fx3A`: Set the playback pitch of the audio pattern to `Vx`
+    fn ld_pitch(&mut self, x: u8) -> Result<(), Chip8Error> {
+        self.pitch = self.registers[usize::from(x)];
+
+        Ok(())
     }
+}
+
+// opcode decoding and instruction router
+impl Chip8 {
+    /// Decodes and executes a single opcode. `pc` is expected to already point past the instruction.
+    pub fn execute_opcode(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let instruction = self.decode(opcode, self.current_instruction_address())?;
 
-    fn select_e_instruction(&self, opcode: u16) -> Result<fn(&mut Chip8, u16), &'static str> {
-        let last_two_digits = opcode & 0x00FF;
-        match last_two_digits {
-            0x9E => Ok(Chip8::skip_key_pressed),
-            0xA1 => Ok(Chip8::skip_key_not_pressed),
-            _ => Err("No instruction for opcode"),
+        self.execute(instruction)
+    }
+
+    /// Decodes an opcode fetched from `address` into an instruction of the emulated platform
+    fn decode(&self, opcode: u16, address: u16) -> Result<Instruction, Chip8Error> {
+        match Instruction::decode(opcode) {
+            Some(instruction) if instruction.is_supported_by(self.platform) => Ok(instruction),
+            _ => Err(Chip8Error::UnknownOpcode { opcode, address }),
         }
     }
 
-    fn select_f_instruction(&self, opcode: u16) -> Result<fn(&mut Chip8, u16), &'static str> {
-        let last_two_digits = opcode & 0x00FF;
+    /// Reads and decodes the instruction at `address`, keeping it until that memory is written
+    fn fetch(&mut self, address: usize) -> Result<Instruction, Chip8Error> {
+        // opcodes are stored big-endian
+        let opcode = u16::from_be_bytes([self.read_memory(address)?, self.read_memory(address + 1)?]);
+        let instruction = self.decode(opcode, address as u16)?;
+        self.decoded[address] = Some(instruction);
+
+        Ok(instruction)
+    }
+
+    /// Executes a decoded instruction. `pc` is expected to already point past the opcode.
+    pub fn execute(&mut self, instruction: Instruction) -> Result<(), Chip8Error> {
+        if self.trace.is_none() {
+            return self.dispatch(instruction);
+        }
+
+        let address = self.current_instruction_address();
+        let mut bytes = instruction.encode().to_be_bytes().to_vec();
+        if instruction == Instruction::LoadILong {
+            let pc = usize::from(self.pc);
+            bytes.extend_from_slice(self.memory.get(pc..pc + 2).unwrap_or(&[]));
+        }
+
+        let before = self.trace_snapshot();
+        let result = self.dispatch(instruction);
+        let changes = before.changes_to(&self.trace_snapshot());
 
-        match last_two_digits {
-            0x07 => Ok(Chip8::ld_delay_timer),
-            0x0A => Ok(Chip8::ld_key_press),
-            0x15 => Ok(Chip8::set_delay_timer),
-            0x18 => Ok(Chip8::set_sound_timer),
-            0x1E => Ok(Chip8::add_index_register),
-            0x29 => Ok(Chip8::ld_sprite),
-            0x33 => Ok(Chip8::ld_bcd),
-            0x55 => Ok(Chip8::ld_registers_into_index_register),
-            0x65 => Ok(Chip8::read_index_register_into_registers),
-            _ => Err("No instruction for opcode"),
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(TraceEntry { address, bytes, instruction, changes });
         }
+        result
     }
 
-    fn select_00e_instruction(&self, opcode: u16) -> Result<fn(&mut Chip8, u16), &'static str> {
-        let last_digit = opcode & 0xF;
+    fn trace_snapshot(&self) -> TraceSnapshot {
+        TraceSnapshot {
+            registers: self.registers,
+            index_register: self.index_register,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        }
+    }
 
-        match last_digit {
-            0x0 => Ok(Chip8::cls),
-            0xE => Ok(Chip8::ret),
-            _ => Err("No instruction for opcode"),
+    fn dispatch(&mut self, instruction: Instruction) -> Result<(), Chip8Error> {
+        use Instruction::*;
+
+        match instruction {
+            ClearScreen => self.cls(),
+            Return => self.ret(),
+            ScrollDown { n } => self.scroll_down(n),
+            ScrollRight => self.scroll_right(),
+            ScrollLeft => self.scroll_left(),
+            Exit => self.exit(),
+            LowRes => self.lores(),
+            HighRes => self.hires(),
+            Jump { nnn } => self.jmp(nnn),
+            Call { nnn } => self.call(nnn),
+            SkipEqImm { x, kk } => self.se_byte(x, kk),
+            SkipNeImm { x, kk } => self.sne_byte(x, kk),
+            SkipEqReg { x, y } => self.se_register(x, y),
+            SaveRange { x, y } => self.ld_register_range_into_memory(x, y),
+            LoadRange { x, y } => self.read_memory_into_register_range(x, y),
+            LoadImm { x, kk } => self.ld_byte(x, kk),
+            AddImm { x, kk } => self.add_byte(x, kk),
+            Move { x, y } => self.ld_register(x, y),
+            Or { x, y } => self.or(x, y),
+            And { x, y } => self.and(x, y),
+            Xor { x, y } => self.xor(x, y),
+            AddReg { x, y } => self.add_registers(x, y),
+            Sub { x, y } => self.sub_registers(x, y),
+            ShiftRight { x, y } => self.shr(x, y),
+            SubN { x, y } => self.subn_registers(x, y),
+            ShiftLeft { x, y } => self.shl(x, y),
+            SkipNeReg { x, y } => self.sne_register(x, y),
+            LoadI { nnn } => self.ld_i(nnn),
+            JumpOffset { nnn } => self.jmp_v0(nnn),
+            Random { x, kk } => self.rand(x, kk),
+            Draw { x, y, n } => self.draw(x, y, n),
+            SkipKey { x } => self.skip_key_pressed(x),
+            SkipNotKey { x } => self.skip_key_not_pressed(x),
+            LoadILong => self.ld_i_long(),
+            SelectPlanes { n } => self.select_planes(n),
+            LoadAudio => self.ld_audio_pattern(),
+            GetDelay { x } => self.ld_delay_timer(x),
+            WaitKey { x } => self.ld_key_press(x),
+            SetDelay { x } => self.set_delay_timer_from_register(x),
+            SetSound { x } => self.set_sound_timer_from_register(x),
+            AddI { x } => self.add_index_register(x),
+            LoadFont { x } => self.ld_sprite(x),
+            LoadBigFont { x } => self.ld_big_sprite(x),
+            StoreBcd { x } => self.ld_bcd(x),
+            SetPitch { x } => self.ld_pitch(x),
+            SaveRegisters { x } => self.ld_registers_into_index_register(x),
+            LoadRegisters { x } => self.read_index_register_into_registers(x),
+            SaveFlags { x } => self.ld_registers_into_flags(x),
+            LoadFlags { x } => self.read_flags_into_registers(x),
         }
     }
 }
 
 // CPU functionality
 impl Chip8 {
-    pub fn cycle(&mut self) {
-        // fetch the next instruction
-        let opcode_first_byte = self.memory[self.pc as usize] as u16;
-        let opcode_second_byte = self.memory[usize::from(self.pc + 1)] as u16;
-        let opcode = (opcode_second_byte << 8) | opcode_first_byte;
+    /// Signals the start of a new 60 Hz display frame. Releases a `Dxyn` held back by the
+    /// `display_wait` quirk.
+    pub fn vertical_blank(&mut self) {
+        self.drawn_this_frame = false;
+    }
+
+    /// Fetches, decodes and executes the next instruction. Does nothing once the program has exited.
+    pub fn cycle(&mut self) -> Result<(), Chip8Error> {
+        if self.exited {
+            return Ok(());
+        }
+
+        let address = usize::from(self.pc);
+        let instruction = match self.decoded.get(address) {
+            Some(Some(instruction)) => *instruction,
+            _ => self.fetch(address)?,
+        };
 
         // increment pc before executing
-        self.pc += 2;
+        self.pc = self.pc.wrapping_add(2);
 
-        self.execute_opcode(opcode);
+        self.execute(instruction)
+    }
 
+    /// Decrements the delay and sound timers. Must be called at 60 Hz, independently of
+    /// how many instructions are executed.
+    pub fn tick_timers(&mut self) {
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
@@ -603,30 +1168,374 @@ impl Chip8 {
             self.sound_timer -= 1;
         }
     }
-}
 
-// accessors
-impl Chip8 {
-    pub fn get_display_memory(&self) -> [[u32; 64]; 32] {
-        self.display_memory
-    }
+    /// Emulates one 60 Hz frame: signals the vertical blank, executes up to `instructions_per_frame`
+    /// instructions, then ticks the timers once.
+    pub fn run_frame(&mut self, instructions_per_frame: u32) -> Result<(), Chip8Error> {
+        self.vertical_blank();
 
-    pub fn get_keypad(&mut self) -> &mut [u8; 16] {
-        &mut self.keypad
+        for _ in 0..instructions_per_frame {
+            if self.exited {
+                break;
+            }
+            self.cycle()?;
+        }
+
+        self.tick_timers();
+
+        Ok(())
     }
 }
 
-#[cfg(test)]
-#[allow(dead_code)]
-mod tests {
-    use super::*;
+// save state methods
+impl Chip8 {
+    /// Serializes the full machine state into a versioned save state
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter::default();
+        writer.write_bytes(STATE_MAGIC);
+        writer.write_u16(STATE_VERSION);
+        writer.write_u64(self.rom_hash);
+
+        writer.write_platform(self.platform);
+        writer.write_quirks(&self.quirks);
+
+        writer.write_bytes(&self.registers);
+        writer.write_block(&self.memory);
+        writer.write_u16(self.index_register);
+        writer.write_u16(self.pc);
+        writer.write_u8(self.stack.len() as u8);
+        for address in self.stack.iter() {
+            writer.write_u16(*address);
+        }
+        writer.write_u8(self.sp);
+        writer.write_u8(self.delay_timer);
+        writer.write_u8(self.sound_timer);
+        writer.write_bytes(&self.keypad);
+
+        let (width, height) = self.get_display_size();
+        writer.write_bool(self.hires);
+        writer.write_u16(width as u16);
+        writer.write_u16(height as u16);
+        writer.write_block(&pack_plane(&self.display_memory));
+        writer.write_block(&pack_plane(&self.plane2_memory));
+        writer.write_u8(self.selected_planes);
+        writer.write_bytes(&self.rpl_flags);
+        writer.write_bool(self.exited);
+        writer.write_bytes(&self.audio_pattern);
+        writer.write_u8(self.pitch);
+        writer.write_bool(self.drawn_this_frame);
+        writer.write_u64(self.rng.get_seed());
+        writer.write_u64(self.rng.get_state());
+
+        writer.into_bytes()
+    }
+
+    /// Restores a state made by `save_state`. The state must have been saved while running
+    /// the currently loaded ROM. Nothing is changed if the state is rejected.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), Chip8Error> {
+        let mut reader = StateReader::new(state);
+
+        if reader.read_bytes(STATE_MAGIC.len())? != STATE_MAGIC {
+            return Err(invalid_state("not a CHIP-8 save state"));
+        }
+        let version = reader.read_u16()?;
+        if version != STATE_VERSION {
+            return Err(invalid_state(&format!("unsupported version {}, expected {}", version, STATE_VERSION)));
+        }
+        let found_hash = reader.read_u64()?;
+        if found_hash != self.rom_hash {
+            return Err(Chip8Error::StateRomMismatch { expected_hash: self.rom_hash, found_hash });
+        }
+
+        let mut c8 = Self::_new();
+        c8.rom_hash = found_hash;
+        c8.platform = reader.read_platform()?;
+        c8.quirks = reader.read_quirks()?;
+
+        c8.registers = reader.read_array()?;
+        c8.memory = reader.read_block()?.to_vec();
+        if c8.memory.len() <= PROGRAM_START_ADDRESS || c8.memory.len() > 0x10000 {
+            return Err(invalid_state(&format!("memory size {} is out of range", c8.memory.len())));
+        }
+        c8.index_register = reader.read_u16()?;
+        c8.pc = reader.read_u16()?;
+        let stack_depth = reader.read_u8()?;
+        c8.stack = (0..stack_depth).map(|_| reader.read_u16()).collect::<Result<_, _>>()?;
+        c8.sp = reader.read_u8()?;
+        if usize::from(c8.sp) > c8.stack.len() {
+            return Err(invalid_state(&format!("stack pointer {} is past the stack depth {}", c8.sp, c8.stack.len())));
+        }
+        c8.delay_timer = reader.read_u8()?;
+        c8.sound_timer = reader.read_u8()?;
+        c8.keypad = reader.read_array()?;
+
+        c8.hires = reader.read_bool()?;
+        let width = usize::from(reader.read_u16()?);
+        let height = usize::from(reader.read_u16()?);
+        let expected_size = if c8.hires { (HIRES_WIDTH, HIRES_HEIGHT) } else { (LORES_WIDTH, LORES_HEIGHT) };
+        if (width, height) != expected_size {
+            return Err(invalid_state(&format!("unexpected display size {}x{}", width, height)));
+        }
+        c8.display_memory = unpack_plane(reader.read_block()?, width, height)?;
+        c8.plane2_memory = unpack_plane(reader.read_block()?, width, height)?;
+        c8.selected_planes = reader.read_u8()?;
+        c8.rpl_flags = reader.read_array()?;
+        c8.exited = reader.read_bool()?;
+        c8.audio_pattern = reader.read_array()?;
+        c8.pitch = reader.read_u8()?;
+        c8.drawn_this_frame = reader.read_bool()?;
+        let seed = reader.read_u64()?;
+        c8.rng = Rng::from_state(seed, reader.read_u64()?);
+
+        if !reader.is_empty() {
+            return Err(invalid_state("unexpected data after the end of the state"));
+        }
+
+        c8.clear_decoded();
+        c8.trace = self.trace.take();
+        *self = c8;
+
+        Ok(())
+    }
+
+    /// Hash of `save_state`; two machines with the same hash are in the same state
+    pub fn get_state_hash(&self) -> u64 {
+        rom_hash(&self.save_state())
+    }
+
+    /// Writes `save_state` to a file
+    pub fn save_state_file(&self, file_path: &str) -> Result<(), Chip8Error> {
+        std::fs::write(file_path, self.save_state()).map_err(|e| Chip8Error::Io {
+            path: file_path.to_string(),
+            reason: e.to_string(),
+        })
+    }
+
+    /// Restores a state written by `save_state_file`, see `load_state`
+    pub fn load_state_file(&mut self, file_path: &str) -> Result<(), Chip8Error> {
+        let state = std::fs::read(file_path).map_err(|e| Chip8Error::Io {
+            path: file_path.to_string(),
+            reason: e.to_string(),
+        })?;
+
+        self.load_state(&state)
+    }
+}
+
+/// Packs a bitplane into one bit per pixel, row by row, most significant bit first
+fn pack_plane(plane: &[Vec<u32>]) -> Vec<u8> {
+    let pixels: Vec<bool> = plane.iter().flatten().map(|pixel| *pixel != PIXEL_OFF).collect();
+
+    pixels.chunks(8)
+        .map(|chunk| chunk.iter().enumerate().fold(0, |byte, (i, on)| byte | (u8::from(*on) << (7 - i))))
+        .collect()
+}
+
+/// Reverses `pack_plane`
+fn unpack_plane(bits: &[u8], width: usize, height: usize) -> Result<Vec<Vec<u32>>, Chip8Error> {
+    if bits.len() != (width * height).div_ceil(8) {
+        return Err(invalid_state("display data does not match the display size"));
+    }
+
+    let plane = (0..height)
+        .map(|y| {
+            (0..width)
+                .map(|x| {
+                    let i = y * width + x;
+                    if bits[i / 8] & (0x80 >> (i % 8)) != 0 { PIXEL_ON } else { PIXEL_OFF }
+                })
+                .collect()
+        })
+        .collect();
+
+    Ok(plane)
+}
+
+// accessors
+impl Chip8 {
+    pub fn get_quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Changes how ambiguous instructions behave from the next executed instruction on
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    /// Returns the display as rows of pixels, `get_display_size()` in size
+    pub fn get_display_memory(&self) -> &[Vec<u32>] {
+        &self.display_memory
+    }
+
+    /// Returns the current display resolution as `(width, height)`
+    pub fn get_display_size(&self) -> (usize, usize) {
+        (self.display_memory[0].len(), self.display_memory.len())
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Returns one of the two XO-CHIP bitplanes, numbered 1 and 2. Plane 1 is the display memory.
+    pub fn get_plane_memory(&self, plane: usize) -> &[Vec<u32>] {
+        match plane {
+            2 => &self.plane2_memory,
+            _ => &self.display_memory,
+        }
+    }
+
+    /// Returns the color index of every pixel as rows, `get_display_size()` in size.
+    /// Bit 0 is set when the pixel is on in plane 1 and bit 1 when it is on in plane 2.
+    pub fn get_display_colors(&self) -> Vec<Vec<u8>> {
+        self.display_memory.iter().zip(self.plane2_memory.iter())
+            .map(|(row1, row2)| {
+                row1.iter().zip(row2.iter())
+                    .map(|(p1, p2)| u8::from(*p1 != PIXEL_OFF) | (u8::from(*p2 != PIXEL_OFF) << 1))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Returns the XO-CHIP 1-bit audio pattern loaded by `F002`
+    pub fn get_audio_pattern(&self) -> &[u8; 16] {
+        &self.audio_pattern
+    }
+
+    /// Returns the XO-CHIP audio pitch register set by `Fx3A`
+    pub fn get_pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    /// Rate in bits per second at which the audio pattern is played back:
+    /// `4000 * 2^((pitch - 64) / 48)`
+    pub fn get_audio_playback_rate(&self) -> f32 {
+        4000.0 * 2f32.powf((f32::from(self.pitch) - 64.0) / 48.0)
+    }
+
+    /// Returns true once a SUPER-CHIP `00FD` instruction has been executed
+    pub fn has_exited(&self) -> bool {
+        self.exited
+    }
+
+    pub fn get_rpl_flags(&self) -> &[u8; 16] {
+        &self.rpl_flags
+    }
+
+    pub fn get_platform(&self) -> Option<Platform> {
+        self.platform
+    }
+
+    /// Seed of the random number generator behind `Cxkk`, see `seed_rng`
+    pub fn get_rng_seed(&self) -> u64 {
+        self.rng.get_seed()
+    }
+
+    /// Hash of the loaded ROM that save states are checked against
+    pub fn get_rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    pub fn get_keypad(&mut self) -> &mut [u8; 16] {
+        &mut self.keypad
+    }
+
+    /// Returns the general purpose registers `V0` through `VF`
+    pub fn get_registers(&self) -> &[u8; 16] {
+        &self.registers
+    }
+
+    pub fn get_registers_mut(&mut self) -> &mut [u8; 16] {
+        &mut self.registers
+    }
+
+    pub fn get_index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    pub fn set_index_register(&mut self, value: u16) {
+        self.index_register = value;
+    }
+
+    pub fn get_pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, value: u16) {
+        self.pc = value;
+    }
+
+    pub fn get_sp(&self) -> u8 {
+        self.sp
+    }
+
+    /// Fails if `value` is past the stack depth, which would leave `RET` reading outside the stack
+    pub fn set_sp(&mut self, value: u8) -> Result<(), Chip8Error> {
+        if usize::from(value) > self.stack.len() {
+            return Err(invalid_state(&format!("stack pointer {} is past the stack depth {}", value, self.stack.len())));
+        }
+        self.sp = value;
+        Ok(())
+    }
+
+    /// Returns the call stack; its length is the maximum call depth
+    pub fn get_stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    pub fn get_stack_mut(&mut self) -> &mut [u16] {
+        &mut self.stack
+    }
+
+    pub fn get_delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    pub fn get_sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    /// Returns the full address space, 4 KiB unless the platform provides more
+    pub fn get_memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// Starts or stops collecting executed instructions for `take_trace`
+    pub fn set_tracing(&mut self, enabled: bool) {
+        self.trace = if enabled { Some(self.trace.take().unwrap_or_default()) } else { None };
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.trace.is_some()
+    }
+
+    /// Removes and returns the instructions executed since the last call, oldest first
+    pub fn take_trace(&mut self) -> Vec<TraceEntry> {
+        self.trace.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Mutable access to the address space. Instructions are decoded again after any change.
+    pub fn get_memory_mut(&mut self) -> &mut [u8] {
+        self.clear_decoded();
+        &mut self.memory
+    }
+}
+
+#[cfg(test)]
+#[allow(dead_code)]
+mod tests {
+    use super::*;
 
     fn print_display_memory(c8: &Chip8) {
-        for i in 0..32 {
-            let mut row = [0u8; 64];
-            for j in 0..64 {
-                row[j] = if c8.display_memory[i][j] != 0 { 1 } else { 0 };
-            }
+        for line in c8.display_memory.iter() {
+            let row: Vec<u8> = line.iter().map(|&pixel| if pixel != 0 { 1 } else { 0 }).collect();
             println!("{:?}", row);
         }
     }
@@ -658,27 +1567,91 @@ mod tests {
         let mut c8 = Chip8::_new();
         let opcodes = vec![0x6000u16, 0x6100u16, 0xa222u16];
 
-        c8.load_opcodes_into_memory(&opcodes);
+        c8.load_opcodes_into_memory(&opcodes).unwrap();
 
-        let expected_byte_order = vec![
-            0x00u8,
+        let expected_byte_order = [
             0x60u8,
             0x00u8,
             0x61u8,
-            0x22u8,
+            0x00u8,
             0xa2u8,
+            0x22u8,
         ];
 
         // assert that nothing is loaded into reserved memory
-        for i in 0..PROGRAM_START_ADDRESS {
-            assert_eq!(c8.memory[i], 0);
-        }
+        assert!(c8.memory[..PROGRAM_START_ADDRESS].iter().all(|&byte| byte == 0));
 
         // assert that instructions are loaded into memory starting at 0x2000
-        for i in 0..(opcodes.len() * 2) {
-            let address = i + PROGRAM_START_ADDRESS;
-            assert_eq!(c8.memory[address], expected_byte_order[i]);
-        }
+        let program = &c8.memory[PROGRAM_START_ADDRESS..PROGRAM_START_ADDRESS + opcodes.len() * 2];
+        assert_eq!(program, &expected_byte_order[..opcodes.len() * 2]);
+    }
+
+    #[test]
+    fn test_load_rom_bytes() {
+        let mut c8 = Chip8::_new();
+        let rom = [0x60u8, 0x00, 0x61, 0x00, 0xa2, 0x22];
+
+        c8.load_rom_bytes(&rom).unwrap();
+
+        assert_eq!(&c8.memory[PROGRAM_START_ADDRESS..PROGRAM_START_ADDRESS + rom.len()], &rom);
+
+        let mut hex_c8 = Chip8::_new();
+        hex_c8.load_instructions_from_hex_text("6000 6100\na222\n").unwrap();
+
+        assert_eq!(c8.memory, hex_c8.memory);
+    }
+
+    #[test]
+    fn test_state_accessors() {
+        let mut c8 = Chip8::from_rom_bytes(&[0x60, 0x2a]).unwrap();
+
+        assert_eq!(c8.get_memory()[PROGRAM_START_ADDRESS], 0x60);
+        assert_eq!(c8.get_memory()[FONT_SET_START_ADDRESS], FONT_SET[0]);
+
+        c8.set_pc(0x300);
+        c8.set_index_register(0x123);
+        c8.set_sp(2).unwrap();
+        c8.get_stack_mut()[1] = 0x456;
+        c8.get_registers_mut()[0xa] = 0x42;
+        c8.set_delay_timer(7);
+        c8.set_sound_timer(9);
+        c8.get_memory_mut()[0x300] = 0xff;
+
+        assert_eq!(c8.get_pc(), 0x300);
+        assert_eq!(c8.get_index_register(), 0x123);
+        assert_eq!(c8.get_sp(), 2);
+        assert!(c8.set_sp(17).is_err());
+        assert_eq!(c8.get_sp(), 2);
+        assert_eq!(c8.get_stack()[1], 0x456);
+        assert_eq!(c8.get_registers()[0xa], 0x42);
+        assert_eq!(c8.get_delay_timer(), 7);
+        assert_eq!(c8.get_sound_timer(), 9);
+        assert_eq!(c8.get_memory()[0x300], 0xff);
+    }
+
+    #[test]
+    fn test_with_platform() {
+        let vip = Chip8::with_platform(Platform::CosmacVip);
+        assert_eq!(vip.memory.len(), 4096);
+        assert_eq!(vip.stack.len(), 12);
+        assert_eq!(vip.quirks, Quirks::cosmac_vip());
+        assert_eq!(vip.memory[FONT_SET_START_ADDRESS + 5], 0x60);
+
+        let xo = Chip8::with_platform(Platform::XoChip);
+        assert_eq!(xo.memory.len(), 0x10000);
+        assert_eq!(xo.stack.len(), 16);
+        assert_eq!(xo.memory[FONT_SET_START_ADDRESS + 5], 0x20);
+    }
+
+    #[test]
+    fn test_vip_stack_depth() {
+        let mut c8 = Chip8::with_platform(Platform::CosmacVip);
+        c8.sp = 12;
+        c8.pc = 0x202;
+
+        let result = c8.execute_opcode(0x2400);
+
+        assert_eq!(result, Err(Chip8Error::StackOverflow { address: 0x200 }));
     }
 
     #[test]
@@ -687,10 +1660,7 @@ mod tests {
 
         c8.load_fontset();
 
-        for i in 0..FONT_SET.len() {
-            let address = FONT_SET_START_ADDRESS + i;
-            assert_eq!(c8.memory[address], FONT_SET[i]);
-        }
+        assert_eq!(&c8.memory[FONT_SET_START_ADDRESS..FONT_SET_START_ADDRESS + FONT_SET.len()], &FONT_SET[..]);
     }
 
     #[test]
@@ -702,7 +1672,7 @@ mod tests {
         c8.display_memory[12][63] = PIXEL_ON;
         c8.display_memory[8][40] = PIXEL_ON;
 
-        c8.execute_opcode(0x00e0);
+        c8.execute_opcode(0x00e0).unwrap();
 
         assert_eq!(c8.display_memory[23][35], PIXEL_OFF);
         assert_eq!(c8.display_memory[12][63], PIXEL_OFF);
@@ -718,7 +1688,7 @@ mod tests {
         c8.stack[0] = 0x208;
         c8.sp = 1;
 
-        c8.execute_opcode(0x00ee);
+        c8.execute_opcode(0x00ee).unwrap();
 
         assert_eq!(c8.pc, 0x208);
         assert_eq!(c8.sp, 0);
@@ -730,7 +1700,7 @@ mod tests {
 
         c8.pc = 0x220;
 
-        c8.execute_opcode(0x1bea);
+        c8.execute_opcode(0x1bea).unwrap();
 
         assert_eq!(c8.pc, 0xbea);
     }
@@ -743,7 +1713,7 @@ mod tests {
         c8.stack[0] = 0x208;
         c8.sp = 1;
 
-        c8.execute_opcode(0x2512);
+        c8.execute_opcode(0x2512).unwrap();
 
         assert_eq!(c8.pc, 0x512);
         assert_eq!(c8.stack[0], 0x208);
@@ -758,7 +1728,7 @@ mod tests {
         c8.pc = 0x220;
         c8.registers[10] = 0x32;
 
-        c8.execute_opcode(0x3a32);
+        c8.execute_opcode(0x3a32).unwrap();
 
         assert_eq!(c8.pc, 0x0222);
     }
@@ -770,7 +1740,7 @@ mod tests {
         c8.pc = 0x220;
         c8.registers[0xa] = 0x32;
 
-        c8.execute_opcode(0x3abc);
+        c8.execute_opcode(0x3abc).unwrap();
 
         assert_eq!(c8.pc, 0x0220);
     }
@@ -782,7 +1752,7 @@ mod tests {
         c8.pc = 0x220;
         c8.registers[0xa] = 0x32;
 
-        c8.execute_opcode(0x4abc);
+        c8.execute_opcode(0x4abc).unwrap();
 
         assert_eq!(c8.pc, 0x0222);
     }
@@ -794,7 +1764,7 @@ mod tests {
         c8.pc = 0x220;
         c8.registers[0xa] = 0x32;
 
-        c8.execute_opcode(0x4a32);
+        c8.execute_opcode(0x4a32).unwrap();
 
         assert_eq!(c8.pc, 0x0220);
     }
@@ -807,7 +1777,7 @@ mod tests {
         c8.registers[10] = 0x32;
         c8.registers[3] = 0x32;
 
-        c8.execute_opcode(0x5a30);
+        c8.execute_opcode(0x5a30).unwrap();
 
         assert_eq!(c8.pc, 0x0222);
     }
@@ -820,7 +1790,7 @@ mod tests {
         c8.registers[10] = 0x32;
         c8.registers[3] = 0x31;
 
-        c8.execute_opcode(0x5a30);
+        c8.execute_opcode(0x5a30).unwrap();
 
         assert_eq!(c8.pc, 0x0220);
     }
@@ -831,7 +1801,7 @@ mod tests {
 
         c8.registers[0xa] = 0x23;
 
-        c8.execute_opcode(0x6abd);
+        c8.execute_opcode(0x6abd).unwrap();
 
         assert_eq!(c8.registers[0xa], 0xbd);
     }
@@ -842,7 +1812,7 @@ mod tests {
 
         c8.registers[0xa] = 0x23;
 
-        c8.execute_opcode(0x7a05);
+        c8.execute_opcode(0x7a05).unwrap();
 
         assert_eq!(c8.registers[0xa], 0x28);
     }
@@ -852,7 +1822,7 @@ mod tests {
         let mut c8 = Chip8::_new();
         c8.registers[0xa] = 0xFF;
 
-        c8.execute_opcode(0x7a01);
+        c8.execute_opcode(0x7a01).unwrap();
 
         assert_eq!(c8.registers[0xa], 0x0);
     }
@@ -864,7 +1834,7 @@ mod tests {
         c8.registers[0xa] = 0x23;
         c8.registers[0xd] = 0x48;
 
-        c8.execute_opcode(0x8ad0);
+        c8.execute_opcode(0x8ad0).unwrap();
 
         assert_eq!(c8.registers[0xa], 0x48);
     }
@@ -876,7 +1846,7 @@ mod tests {
         c8.registers[0xa] = 0x23;
         c8.registers[0xd] = 0x48;
 
-        c8.execute_opcode(0x8ad1);
+        c8.execute_opcode(0x8ad1).unwrap();
 
         assert_eq!(c8.registers[0xa], 0x23 | 0x48);
     }
@@ -888,7 +1858,7 @@ mod tests {
         c8.registers[0xa] = 0xF0;
         c8.registers[0xd] = 0x0F;
 
-        c8.execute_opcode(0x8ad2);
+        c8.execute_opcode(0x8ad2).unwrap();
 
         assert_eq!(c8.registers[0xa], 0x0);
     }
@@ -900,7 +1870,7 @@ mod tests {
         c8.registers[0xa] = 0x23;
         c8.registers[0xd] = 0x48;
 
-        c8.execute_opcode(0x8ad3);
+        c8.execute_opcode(0x8ad3).unwrap();
 
         assert_eq!(c8.registers[0xa], 0x23 ^ 0x48);
     }
@@ -913,7 +1883,7 @@ mod tests {
         c8.registers[0xd] = 0x48;
         c8.registers[0xf] = 0x01;
 
-        c8.execute_opcode(0x8ad4);
+        c8.execute_opcode(0x8ad4).unwrap();
 
         assert_eq!(c8.registers[0xa], 0x23 + 0x48);
         assert_eq!(c8.registers[0xf], 0x0);
@@ -927,7 +1897,7 @@ mod tests {
         c8.registers[0xd] = 0x1;
         c8.registers[0xf] = 0x0;
 
-        c8.execute_opcode(0x8ad4);
+        c8.execute_opcode(0x8ad4).unwrap();
 
         assert_eq!(c8.registers[0xa], 0x0);
         assert_eq!(c8.registers[0xf], 0x1);
@@ -941,7 +1911,7 @@ mod tests {
         c8.registers[0xd] = 0x1;
         c8.registers[0xf] = 0x0;
 
-        c8.execute_opcode(0x8ad5);
+        c8.execute_opcode(0x8ad5).unwrap();
         
         assert_eq!(c8.registers[0xa], 0xFE);
         assert_eq!(c8.registers[0xf], 0x1);
@@ -955,7 +1925,7 @@ mod tests {
         c8.registers[0xd] = 0xFF;
         c8.registers[0xf] = 0x0;
 
-        c8.execute_opcode(0x8ad5);
+        c8.execute_opcode(0x8ad5).unwrap();
         
         assert_eq!(c8.registers[0xa], 0x3);
         assert_eq!(c8.registers[0xf], 0x0);
@@ -969,7 +1939,7 @@ mod tests {
         c8.registers[0xd] = 0x1f;
         c8.registers[0xf] = 0x0;
 
-        c8.execute_opcode(0x8ad6);
+        c8.execute_opcode(0x8ad6).unwrap();
 
         assert_eq!(c8.registers[0xa], 0xf);
         assert_eq!(c8.registers[0xf], 0x1);
@@ -983,7 +1953,7 @@ mod tests {
         c8.registers[0xd] = 0x10;
         c8.registers[0xf] = 0x1;
 
-        c8.execute_opcode(0x8ad6);
+        c8.execute_opcode(0x8ad6).unwrap();
 
         assert_eq!(c8.registers[0xa], 0x08);
         assert_eq!(c8.registers[0xf], 0x0);
@@ -997,7 +1967,7 @@ mod tests {
         c8.registers[0xb] = 0x0a;
         c8.registers[0xf] = 0x0;
 
-        c8.execute_opcode(0x8ab7);
+        c8.execute_opcode(0x8ab7).unwrap();
 
         assert_eq!(c8.registers[0xa], 0x08);
         assert_eq!(c8.registers[0xf], 0x1);
@@ -1011,7 +1981,7 @@ mod tests {
         c8.registers[0xb] = 0x02;
         c8.registers[0xf] = 0x1;
 
-        c8.execute_opcode(0x8ab7);
+        c8.execute_opcode(0x8ab7).unwrap();
 
         assert_eq!(c8.registers[0xa], 0xf8);
         assert_eq!(c8.registers[0xf], 0x0);
@@ -1025,7 +1995,7 @@ mod tests {
         c8.registers[0xc] = 0x0;
         c8.registers[0xf] = 0x0;
 
-        c8.execute_opcode(0x8cae);
+        c8.execute_opcode(0x8cae).unwrap();
 
         assert_eq!(c8.registers[0xc], 0xfe);
         assert_eq!(c8.registers[0xf], 0x1);
@@ -1039,7 +2009,7 @@ mod tests {
         c8.registers[0xc] = 0x0;
         c8.registers[0xf] = 0x1;
 
-        c8.execute_opcode(0x8cae);
+        c8.execute_opcode(0x8cae).unwrap();
 
         assert_eq!(c8.registers[0xc], 0xfe);
         assert_eq!(c8.registers[0xf], 0x0);
@@ -1053,7 +2023,7 @@ mod tests {
         c8.registers[0xa] = 0x78;
         c8.registers[0xb] = 0x98;
 
-        c8.execute_opcode(0x9ab0);
+        c8.execute_opcode(0x9ab0).unwrap();
     
         assert_eq!(c8.pc, 0x208);
     }
@@ -1066,7 +2036,7 @@ mod tests {
         c8.registers[0xa] = 0x78;
         c8.registers[0xb] = 0x78;
 
-        c8.execute_opcode(0x9ab0);
+        c8.execute_opcode(0x9ab0).unwrap();
     
         assert_eq!(c8.pc, 0x206);
     }
@@ -1077,7 +2047,7 @@ mod tests {
 
         c8.index_register = 0x512;
 
-        c8.execute_opcode(0xaabc);
+        c8.execute_opcode(0xaabc).unwrap();
 
         assert_eq!(c8.index_register, 0xabc);
     }
@@ -1089,11 +2059,23 @@ mod tests {
         c8.pc = 0x224;
         c8.registers[0] = 0x10;
 
-        c8.execute_opcode(0xBabc);
+        c8.execute_opcode(0xbabc).unwrap();
 
         assert_eq!(c8.pc, 0xacc);
     }
 
+    #[test]
+    fn test_execute_decoded_instruction() {
+        let mut c8 = Chip8::_new();
+
+        c8.registers[0x3] = 0x10;
+        c8.execute(Instruction::AddImm { x: 0x3, kk: 0x05 }).unwrap();
+        c8.execute(Instruction::Move { x: 0x4, y: 0x3 }).unwrap();
+
+        assert_eq!(c8.registers[0x3], 0x15);
+        assert_eq!(c8.registers[0x4], 0x15);
+    }
+
     #[test]
     fn test_draw() {
         let mut c8 = Chip8::_new();
@@ -1106,12 +2088,12 @@ mod tests {
         c8.registers[0xb] = 0x0;
         c8.registers[0xf] = 0x1;
         
-        c8.execute_opcode(0xdab5);
+        c8.execute_opcode(0xdab5).unwrap();
 
-        for i in 0..5 {
-            for j in 0..8 {
-                let expected_value = if ONE_SPRITE[i][j] == 1 { PIXEL_ON } else { PIXEL_OFF };
-                assert_eq!(c8.display_memory[i][j], expected_value);
+        for (line, sprite_row) in c8.display_memory.iter().zip(ONE_SPRITE.iter()) {
+            for (&pixel, &bit) in line.iter().zip(sprite_row.iter()) {
+                let expected_value = if bit == 1 { PIXEL_ON } else { PIXEL_OFF };
+                assert_eq!(pixel, expected_value);
             }
         }
         assert_eq!(c8.registers[0xf], 0x0);
@@ -1124,9 +2106,9 @@ mod tests {
         c8.load_fontset();
 
         // preload a sprite at (1, 1)
-        for i in 0..5 {
-            for j in 0..8 {
-                c8.display_memory[i+1][j+1] = if ZERO_SPRITE[i][j] == 1u8 { PIXEL_ON } else { PIXEL_OFF };
+        for (line, sprite_row) in c8.display_memory[1..].iter_mut().zip(ZERO_SPRITE.iter()) {
+            for (pixel, &bit) in line[1..].iter_mut().zip(sprite_row.iter()) {
+                *pixel = if bit == 1u8 { PIXEL_ON } else { PIXEL_OFF };
             }
         }
 
@@ -1135,7 +2117,7 @@ mod tests {
         c8.registers[0xb] = 0x1;
         c8.registers[0xf] = 0x0;
 
-        c8.execute_opcode(0xdab5);
+        c8.execute_opcode(0xdab5).unwrap();
 
         assert_eq!(c8.registers[0xf], 0x1);
 
@@ -1171,15 +2153,13 @@ mod tests {
         c8.registers[0xb] = y as u8;
         c8.registers[0xf] = 0x1;
 
-        c8.execute_opcode(0xdab5);
+        c8.execute_opcode(0xdab5).unwrap();
 
         assert_eq!(c8.index_register, 0x50);
         assert_eq!(c8.registers[0xf], 0x0);
 
-        for i in 0..32 {
-            for j in 0..64 {
-                assert_eq!(c8.display_memory[i][j], expected_display[i][j]);
-            }
+        for (line, expected_line) in c8.display_memory.iter().zip(expected_display.iter()) {
+            assert_eq!(&line[..], &expected_line[..]);
         }
     }
 
@@ -1191,7 +2171,7 @@ mod tests {
         c8.keypad[2] = 1;
         c8.registers[0xa] = 2;
 
-        c8.execute_opcode(0xea9e);
+        c8.execute_opcode(0xea9e).unwrap();
 
         assert_eq!(c8.pc, 0x226);
     }
@@ -1204,7 +2184,7 @@ mod tests {
         c8.keypad[2] = 0;
         c8.registers[0xa] = 2;
 
-        c8.execute_opcode(0xea9e);
+        c8.execute_opcode(0xea9e).unwrap();
 
         assert_eq!(c8.pc, 0x224);
     }
@@ -1217,7 +2197,7 @@ mod tests {
         c8.keypad[2] = 0;
         c8.registers[0xa] = 2;
 
-        c8.execute_opcode(0xeaa1);
+        c8.execute_opcode(0xeaa1).unwrap();
 
         assert_eq!(c8.pc, 0x226);
     }
@@ -1230,7 +2210,7 @@ mod tests {
         c8.keypad[2] = 1;
         c8.registers[0xa] = 2;
 
-        c8.execute_opcode(0xeaa1);
+        c8.execute_opcode(0xeaa1).unwrap();
 
         assert_eq!(c8.pc, 0x224);
     }
@@ -1240,7 +2220,7 @@ mod tests {
         let mut c8 = Chip8::_new();
         c8.delay_timer = 0x20;
 
-        c8.execute_opcode(0xfa07);
+        c8.execute_opcode(0xfa07).unwrap();
 
         assert_eq!(c8.registers[0xa], 0x20);
     }
@@ -1250,7 +2230,7 @@ mod tests {
         let mut c8 = Chip8::_new();
         c8.registers[0xa] = 0x50;
 
-        c8.execute_opcode(0xfa15);
+        c8.execute_opcode(0xfa15).unwrap();
 
         assert_eq!(c8.delay_timer, 0x50);
     }
@@ -1261,7 +2241,7 @@ mod tests {
 
         c8.keypad[0xf] = 1;
 
-        c8.execute_opcode(0xfa0a);
+        c8.execute_opcode(0xfa0a).unwrap();
 
         assert_eq!(c8.registers[0xa], 0xf);
     }
@@ -1271,7 +2251,7 @@ mod tests {
         let mut c8 = Chip8::_new();
 
         c8.pc = 0x204;
-        c8.execute_opcode(0xfa0a);
+        c8.execute_opcode(0xfa0a).unwrap();
 
         assert_eq!(c8.pc, 0x202);
     }
@@ -1282,7 +2262,7 @@ mod tests {
 
         c8.registers[0xa] = 0x20;
 
-        c8.execute_opcode(0xfa18);
+        c8.execute_opcode(0xfa18).unwrap();
 
         assert_eq!(c8.sound_timer, 0x20);
     }
@@ -1294,7 +2274,7 @@ mod tests {
         c8.registers[0xa] = 0x2;
         c8.index_register = 0x220;
 
-        c8.execute_opcode(0xfa1e);
+        c8.execute_opcode(0xfa1e).unwrap();
 
         assert_eq!(c8.index_register, 0x222);
     }
@@ -1306,7 +2286,7 @@ mod tests {
         c8.registers[0xa] = 0x2;
         c8.index_register = 0xFFFF;
 
-        c8.execute_opcode(0xfa1e);
+        c8.execute_opcode(0xfa1e).unwrap();
 
         assert_eq!(c8.index_register, 0x1);
     }
@@ -1317,7 +2297,7 @@ mod tests {
 
         c8.registers[0xa] = 0x4;
 
-        c8.execute_opcode(0xfa29);
+        c8.execute_opcode(0xfa29).unwrap();
 
         assert_eq!(c8.index_register, 0x64);
     }
@@ -1328,7 +2308,7 @@ mod tests {
 
         c8.registers[0xa] = 0x0;
 
-        c8.execute_opcode(0xfa29);
+        c8.execute_opcode(0xfa29).unwrap();
 
         assert_eq!(c8.index_register, 0x50);
     }
@@ -1340,7 +2320,7 @@ mod tests {
         c8.registers[0xa] = 0xFE; // 254
         c8.index_register = 0x100;
 
-        c8.execute_opcode(0xfa33);
+        c8.execute_opcode(0xfa33).unwrap();
 
         assert_eq!(c8.memory[0x100], 2);
         assert_eq!(c8.memory[0x101], 5);
@@ -1354,7 +2334,7 @@ mod tests {
         c8.registers[0xa] = 0x10; // 16
         c8.index_register = 0x100;
 
-        c8.execute_opcode(0xfa33);
+        c8.execute_opcode(0xfa33).unwrap();
 
         assert_eq!(c8.memory[0x100], 0);
         assert_eq!(c8.memory[0x101], 1);
@@ -1368,7 +2348,7 @@ mod tests {
         c8.registers[0xa] = 0x2; // 2
         c8.index_register = 0x100;
 
-        c8.execute_opcode(0xfa33);
+        c8.execute_opcode(0xfa33).unwrap();
 
         assert_eq!(c8.memory[0x100], 0);
         assert_eq!(c8.memory[0x101], 0);
@@ -1386,7 +2366,7 @@ mod tests {
         c8.registers[0x4] = 0x5;
         c8.index_register = 0x100;
 
-        c8.execute_opcode(0xf455);
+        c8.execute_opcode(0xf455).unwrap();
 
         assert_eq!(c8.index_register, 0x105);
 
@@ -1409,7 +2389,7 @@ mod tests {
 
         c8.index_register = 0x100;
 
-        c8.execute_opcode(0xf465);
+        c8.execute_opcode(0xf465).unwrap();
 
         assert_eq!(c8.index_register, 0x105);
 
@@ -1420,14 +2400,532 @@ mod tests {
         assert_eq!(c8.registers[4], 0x5);
     }
 
+    #[test]
+    fn test_shr_quirk_shift_vx() {
+        let mut c8 = Chip8::_new();
+        c8.quirks.shift_vx = true;
+
+        c8.registers[0xa] = 0x05;
+        c8.registers[0xd] = 0x10;
+
+        c8.execute_opcode(0x8ad6).unwrap();
+
+        assert_eq!(c8.registers[0xa], 0x02);
+        assert_eq!(c8.registers[0xf], 0x1);
+    }
+
+    #[test]
+    fn test_shl_quirk_shift_vx() {
+        let mut c8 = Chip8::_new();
+        c8.quirks.shift_vx = true;
+
+        c8.registers[0xa] = 0x01;
+        c8.registers[0xc] = 0x81;
+
+        c8.execute_opcode(0x8cae).unwrap();
+
+        assert_eq!(c8.registers[0xc], 0x02);
+        assert_eq!(c8.registers[0xf], 0x1);
+    }
+
+    #[test]
+    fn test_or_quirk_vf_reset() {
+        let mut c8 = Chip8::_new();
+        c8.quirks.vf_reset = true;
+
+        c8.registers[0xa] = 0x23;
+        c8.registers[0xd] = 0x48;
+        c8.registers[0xf] = 0x1;
+
+        c8.execute_opcode(0x8ad1).unwrap();
+
+        assert_eq!(c8.registers[0xa], 0x23 | 0x48);
+        assert_eq!(c8.registers[0xf], 0x0);
+    }
+
+    #[test]
+    fn test_jmp_v0_quirk_jump_with_vx() {
+        let mut c8 = Chip8::_new();
+        c8.quirks.jump_with_vx = true;
+
+        c8.registers[0x0] = 0x10;
+        c8.registers[0xa] = 0x02;
+
+        c8.execute_opcode(0xbabc).unwrap();
+
+        assert_eq!(c8.pc, 0xabe);
+    }
+
+    #[test]
+    fn test_ld_registers_into_index_register_quirk_no_increment() {
+        let mut c8 = Chip8::_new();
+        c8.quirks.load_store_increment_i = false;
+
+        c8.registers[0x0] = 0x1;
+        c8.registers[0x1] = 0x2;
+        c8.index_register = 0x100;
+
+        c8.execute_opcode(0xf155).unwrap();
+
+        assert_eq!(c8.index_register, 0x100);
+        assert_eq!(c8.memory[0x100], 0x1);
+        assert_eq!(c8.memory[0x101], 0x2);
+    }
+
+    #[test]
+    fn test_ld_registers_into_index_register_quirk_increment_by_x() {
+        let mut c8 = Chip8::_new();
+        c8.quirks = Quirks::chip48();
+
+        c8.index_register = 0x100;
+        c8.execute_opcode(0xf155).unwrap();
+        assert_eq!(c8.index_register, 0x101);
+
+        c8.execute_opcode(0xf265).unwrap();
+        assert_eq!(c8.index_register, 0x103);
+    }
+
+    #[test]
+    fn test_draw_quirk_clip_sprites() {
+        let mut c8 = Chip8::_new();
+        c8.load_fontset();
+        c8.quirks.clip_sprites = true;
+
+        c8.index_register = 0x50;
+        c8.registers[0xa] = 61;
+        c8.registers[0xb] = 29;
+
+        c8.execute_opcode(0xdab5).unwrap();
+
+        for i in 0..32 {
+            for j in 0..64 {
+                let expected_value = if i >= 29 && j >= 61 && ZERO_SPRITE[i - 29][j - 61] == 1 { PIXEL_ON } else { PIXEL_OFF };
+                assert_eq!(c8.display_memory[i][j], expected_value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_draw_quirk_display_wait() {
+        let mut c8 = Chip8::_new();
+        c8.load_fontset();
+        c8.quirks.display_wait = true;
+
+        c8.index_register = 0x50;
+        c8.pc = 0x202;
+
+        c8.execute_opcode(0xdab5).unwrap();
+        assert_eq!(c8.pc, 0x202);
+        assert_eq!(c8.display_memory[0][0], PIXEL_ON);
+
+        // a second draw in the same frame is held back
+        c8.execute_opcode(0xdab5).unwrap();
+        assert_eq!(c8.pc, 0x200);
+        assert_eq!(c8.display_memory[0][0], PIXEL_ON);
+
+        c8.vertical_blank();
+        c8.pc = 0x202;
+        c8.execute_opcode(0xdab5).unwrap();
+        assert_eq!(c8.pc, 0x202);
+        assert_eq!(c8.display_memory[0][0], PIXEL_OFF);
+    }
+
+    #[test]
+    fn test_schip_opcodes_need_schip_platform() {
+        let mut c8 = Chip8::_new();
+        c8.pc = 0x202;
+
+        assert!(matches!(c8.execute_opcode(0x00ff), Err(Chip8Error::UnknownOpcode { .. })));
+        assert!(matches!(c8.execute_opcode(0xfa30), Err(Chip8Error::UnknownOpcode { .. })));
+        assert!(matches!(c8.execute_opcode(0x0123), Err(Chip8Error::UnknownOpcode { .. })));
+    }
+
+    #[test]
+    fn test_hires_and_lores() {
+        let mut c8 = Chip8::with_platform(Platform::SuperChip);
+        assert_eq!(c8.get_display_size(), (64, 32));
+
+        c8.execute_opcode(0x00ff).unwrap();
+        assert!(c8.is_hires());
+        assert_eq!(c8.get_display_size(), (128, 64));
+
+        c8.display_memory[63][127] = PIXEL_ON;
+
+        c8.execute_opcode(0x00fe).unwrap();
+        assert!(!c8.is_hires());
+        assert_eq!(c8.get_display_size(), (64, 32));
+    }
+
+    #[test]
+    fn test_schip_resolution_switch_keeps_display() {
+        let mut c8 = Chip8::with_platform(Platform::SuperChip);
+        c8.display_memory[31][63] = PIXEL_ON;
+
+        // each lo-res pixel becomes 2x2 hi-res pixels
+        c8.execute_opcode(0x00ff).unwrap();
+        assert_eq!(c8.display_memory[62][126], PIXEL_ON);
+        assert_eq!(c8.display_memory[63][127], PIXEL_ON);
+        assert_eq!(c8.display_memory.iter().flatten().filter(|&&pixel| pixel == PIXEL_ON).count(), 4);
+
+        c8.execute_opcode(0x00fe).unwrap();
+        assert_eq!(c8.display_memory[31][63], PIXEL_ON);
+        assert_eq!(c8.display_memory.iter().flatten().filter(|&&pixel| pixel == PIXEL_ON).count(), 1);
+    }
+
+    #[test]
+    fn test_xochip_resolution_switch_clears_display() {
+        let mut c8 = Chip8::with_platform(Platform::XoChip);
+        c8.display_memory[31][63] = PIXEL_ON;
+        c8.plane2_memory[0][0] = PIXEL_ON;
+
+        c8.execute_opcode(0x00ff).unwrap();
+
+        assert!(c8.display_memory.iter().flatten().all(|&pixel| pixel == PIXEL_OFF));
+        assert!(c8.plane2_memory.iter().flatten().all(|&pixel| pixel == PIXEL_OFF));
+    }
+
+    #[test]
+    fn test_scroll_down() {
+        let mut c8 = Chip8::with_platform(Platform::SuperChip);
+        c8.display_memory[0][5] = PIXEL_ON;
+        c8.display_memory[30][5] = PIXEL_ON;
+
+        c8.execute_opcode(0x00c3).unwrap();
+
+        assert_eq!(c8.display_memory[0][5], PIXEL_OFF);
+        assert_eq!(c8.display_memory[3][5], PIXEL_ON);
+        // pixels scrolled off the bottom do not wrap around
+        assert_eq!(c8.display_memory[1][5], PIXEL_OFF);
+        assert_eq!(c8.display_memory[31][5], PIXEL_OFF);
+    }
+
+    #[test]
+    fn test_scroll_right_and_left() {
+        let mut c8 = Chip8::with_platform(Platform::SuperChip);
+        c8.display_memory[2][0] = PIXEL_ON;
+        c8.display_memory[2][62] = PIXEL_ON;
+
+        c8.execute_opcode(0x00fb).unwrap();
+
+        assert_eq!(c8.display_memory[2][0], PIXEL_OFF);
+        assert_eq!(c8.display_memory[2][4], PIXEL_ON);
+        assert_eq!(c8.display_memory[2][2], PIXEL_OFF);
+
+        c8.execute_opcode(0x00fc).unwrap();
+        c8.execute_opcode(0x00fc).unwrap();
+
+        assert_eq!(c8.display_memory[2][0], PIXEL_OFF);
+        assert_eq!(c8.display_memory[2][60], PIXEL_OFF);
+    }
+
+    #[test]
+    fn test_exit() {
+        let mut c8 = Chip8::with_platform(Platform::SuperChip);
+        c8.memory[0x200] = 0x00;
+        c8.memory[0x201] = 0xfd;
+
+        c8.cycle().unwrap();
+        assert!(c8.has_exited());
+        assert_eq!(c8.pc, 0x202);
+
+        c8.cycle().unwrap();
+        assert_eq!(c8.pc, 0x202);
+    }
+
+    #[test]
+    fn test_draw_16x16_sprite() {
+        let mut c8 = Chip8::with_platform(Platform::SuperChip);
+        c8.execute_opcode(0x00ff).unwrap();
+
+        for i in 0..32 {
+            c8.memory[0x300 + i] = if i % 2 == 0 { 0x80 } else { 0x01 };
+        }
+        c8.index_register = 0x300;
+        c8.registers[0xa] = 120;
+        c8.registers[0xb] = 10;
+
+        c8.execute_opcode(0xdab0).unwrap();
+
+        for row in 0..16 {
+            assert_eq!(c8.display_memory[10 + row][120], PIXEL_ON);
+            assert_eq!(c8.display_memory[10 + row][121], PIXEL_OFF);
+        }
+        // the right half of the sprite is clipped at the edge of the screen
+        assert!(c8.display_memory.iter().all(|row| row[7] == PIXEL_OFF));
+        assert_eq!(c8.registers[0xf], 0x0);
+    }
+
+    #[test]
+    fn test_ld_big_sprite() {
+        let mut c8 = Chip8::with_platform(Platform::SuperChip);
+        c8.registers[0xa] = 0x3;
+
+        c8.execute_opcode(0xfa30).unwrap();
+
+        assert_eq!(c8.index_register, 0xbe);
+        assert_eq!(c8.memory[0xbe..0xc8], BIG_FONT_SET[30..40]);
+    }
+
+    #[test]
+    fn test_rpl_flags() {
+        let mut c8 = Chip8::with_platform(Platform::SuperChip);
+        c8.registers[0x0] = 0x12;
+        c8.registers[0x1] = 0x34;
+        c8.registers[0x2] = 0x56;
+
+        c8.execute_opcode(0xf175).unwrap();
+        assert_eq!(c8.rpl_flags[..3], [0x12, 0x34, 0x00]);
+
+        c8.registers[0x0] = 0x0;
+        c8.registers[0x1] = 0x0;
+
+        c8.execute_opcode(0xf185).unwrap();
+        assert_eq!(c8.registers[..3], [0x12, 0x34, 0x56]);
+    }
+
+    #[test]
+    fn test_xochip_opcodes_need_xochip_platform() {
+        let mut c8 = Chip8::with_platform(Platform::SuperChip);
+        c8.pc = 0x202;
+
+        assert!(matches!(c8.execute_opcode(0xf000), Err(Chip8Error::UnknownOpcode { .. })));
+        assert!(matches!(c8.execute_opcode(0x5122), Err(Chip8Error::UnknownOpcode { .. })));
+        assert!(matches!(c8.execute_opcode(0xf201), Err(Chip8Error::UnknownOpcode { .. })));
+    }
+
+    #[test]
+    fn test_ld_i_long() {
+        let mut c8 = Chip8::with_platform(Platform::XoChip);
+        c8.memory[0x200..0x204].copy_from_slice(&[0xf0, 0x00, 0xbe, 0xef]);
+
+        c8.cycle().unwrap();
+
+        assert_eq!(c8.index_register, 0xbeef);
+        assert_eq!(c8.pc, 0x204);
+    }
+
+    #[test]
+    fn test_skip_over_ld_i_long() {
+        let mut c8 = Chip8::with_platform(Platform::XoChip);
+        c8.memory[0x200..0x206].copy_from_slice(&[0x30, 0x00, 0xf0, 0x00, 0xbe, 0xef]);
+
+        c8.cycle().unwrap();
+
+        assert_eq!(c8.pc, 0x206);
+    }
+
+    #[test]
+    fn test_register_range_save_and_load() {
+        let mut c8 = Chip8::with_platform(Platform::XoChip);
+        c8.registers[0x2] = 0x12;
+        c8.registers[0x3] = 0x34;
+        c8.registers[0x4] = 0x56;
+        c8.index_register = 0x400;
+
+        c8.execute_opcode(0x5242).unwrap();
+        assert_eq!(c8.memory[0x400..0x403], [0x12, 0x34, 0x56]);
+        assert_eq!(c8.index_register, 0x400);
+
+        // reversed range loads V4 first
+        c8.execute_opcode(0x5a83).unwrap();
+        assert_eq!(c8.registers[0xa], 0x12);
+        assert_eq!(c8.registers[0x9], 0x34);
+        assert_eq!(c8.registers[0x8], 0x56);
+    }
+
+    #[test]
+    fn test_draw_both_planes() {
+        let mut c8 = Chip8::with_platform(Platform::XoChip);
+        c8.memory[0x400..0x404].copy_from_slice(&[0x80, 0x80, 0xc0, 0x00]);
+        c8.index_register = 0x400;
+
+        c8.execute_opcode(0xf301).unwrap();
+        c8.execute_opcode(0xd002).unwrap();
+
+        let colors = c8.get_display_colors();
+        assert_eq!(colors[0][0], 0b11);
+        assert_eq!(colors[0][1], 0b10);
+        assert_eq!(colors[1][0], 0b01);
+        assert_eq!(colors[1][1], 0b00);
+
+        // clearing only the second plane leaves the first one intact
+        c8.execute_opcode(0xf201).unwrap();
+        c8.execute_opcode(0x00e0).unwrap();
+
+        let colors = c8.get_display_colors();
+        assert_eq!(colors[0][0], 0b01);
+        assert_eq!(colors[0][1], 0b00);
+        assert_eq!(colors[1][0], 0b01);
+    }
+
+    #[test]
+    fn test_draw_nothing() {
+        // Dxy0 draws zero rows without SUPER-CHIP
+        let mut c8 = Chip8::from_rom_bytes(&[0xd0, 0x10]).unwrap();
+        c8.registers[0xf] = 0x1;
+
+        c8.cycle().unwrap();
+
+        assert_eq!(c8.registers[0xf], 0x0);
+        assert!(c8.get_display_colors().iter().flatten().all(|&color| color == 0));
+
+        // no selected planes
+        let mut c8 = Chip8::with_platform(Platform::XoChip);
+        c8.registers[0xf] = 0x1;
+
+        c8.execute_opcode(0xf001).unwrap();
+        c8.execute_opcode(0xd005).unwrap();
+
+        assert_eq!(c8.registers[0xf], 0x0);
+        assert!(c8.get_display_colors().iter().flatten().all(|&color| color == 0));
+    }
+
+    #[test]
+    fn test_skip_at_end_of_memory() {
+        let mut c8 = Chip8::with_platform(Platform::XoChip);
+        c8.pc = 0xfffe;
+
+        c8.execute_opcode(0x3000).unwrap();
+
+        assert_eq!(c8.pc, 0x0000);
+    }
+
+    #[test]
+    fn test_audio_pattern_and_pitch() {
+        let mut c8 = Chip8::with_platform(Platform::XoChip);
+        for i in 0..16 {
+            c8.memory[0x400 + i] = i as u8;
+        }
+        c8.index_register = 0x400;
+        c8.registers[0x5] = 112;
+
+        c8.execute_opcode(0xf002).unwrap();
+        c8.execute_opcode(0xf53a).unwrap();
+
+        assert_eq!(c8.get_audio_pattern()[15], 15);
+        assert_eq!(c8.get_pitch(), 112);
+        assert_eq!(c8.get_audio_playback_rate(), 8000.0);
+    }
+
+    #[test]
+    fn test_unknown_opcode() {
+        let mut c8 = Chip8::_new();
+        c8.pc = 0x204;
+
+        let result = c8.execute_opcode(0x8ab9);
+
+        assert_eq!(result, Err(Chip8Error::UnknownOpcode { opcode: 0x8ab9, address: 0x202 }));
+    }
+
+    #[test]
+    fn test_memory_writes_invalidate_decoded_instructions() {
+        // 0x200: V2 += 1; 0x202: jump to 0x200
+        let mut c8 = Chip8::from_rom_bytes(&[0x72, 0x01, 0x12, 0x00]).unwrap();
+        c8.cycle().unwrap();
+        c8.cycle().unwrap();
+        assert_eq!(c8.decoded[0x200], Some(Instruction::AddImm { x: 2, kk: 1 }));
+
+        // Fx55 rewrites the add to V2 += 5
+        c8.registers[0] = 0x72;
+        c8.registers[1] = 0x05;
+        c8.index_register = 0x200;
+        c8.execute_opcode(0xf155).unwrap();
+        c8.cycle().unwrap();
+        assert_eq!(c8.registers[2], 6);
+
+        // writing the second byte alone is enough
+        c8.cycle().unwrap();
+        c8.index_register = 0x201;
+        c8.registers[0] = 0x0a;
+        c8.execute_opcode(0xf055).unwrap();
+        c8.cycle().unwrap();
+        assert_eq!(c8.registers[2], 16);
+
+        c8.cycle().unwrap();
+        c8.get_memory_mut()[0x201] = 0x64;
+        c8.cycle().unwrap();
+        assert_eq!(c8.registers[2], 116);
+    }
+
+    #[test]
+    fn test_ret_empty_stack() {
+        let mut c8 = Chip8::_new();
+        c8.pc = 0x204;
+
+        let result = c8.execute_opcode(0x00ee);
+
+        assert_eq!(result, Err(Chip8Error::StackUnderflow { address: 0x202 }));
+        assert_eq!(c8.sp, 0);
+    }
+
+    #[test]
+    fn test_call_full_stack() {
+        let mut c8 = Chip8::_new();
+        c8.pc = 0x204;
+        c8.sp = 16;
+
+        let result = c8.execute_opcode(0x2512);
+
+        assert_eq!(result, Err(Chip8Error::StackOverflow { address: 0x202 }));
+        assert_eq!(c8.pc, 0x204);
+    }
+
+    #[test]
+    fn test_draw_out_of_bounds() {
+        let mut c8 = Chip8::_new();
+        c8.index_register = 0xffe;
+
+        let result = c8.execute_opcode(0xdab5);
+
+        assert_eq!(result, Err(Chip8Error::MemoryOutOfBounds { address: 0x1002 }));
+    }
+
+    #[test]
+    fn test_ld_bcd_out_of_bounds() {
+        let mut c8 = Chip8::_new();
+        c8.index_register = 0xfff;
+
+        let result = c8.execute_opcode(0xfa33);
+
+        assert_eq!(result, Err(Chip8Error::MemoryOutOfBounds { address: 0x1000 }));
+    }
+
+    #[test]
+    fn test_ld_registers_into_index_register_out_of_bounds() {
+        let mut c8 = Chip8::_new();
+        c8.index_register = 0xffe;
+
+        let result = c8.execute_opcode(0xf455);
+
+        assert_eq!(result, Err(Chip8Error::MemoryOutOfBounds { address: 0x1000 }));
+    }
+
+    #[test]
+    fn test_rom_too_large() {
+        let rom = vec![0u8; 4096 - PROGRAM_START_ADDRESS + 1];
+
+        let result = Chip8::from_rom_bytes(&rom);
+
+        assert_eq!(result.unwrap_err(), Chip8Error::RomTooLarge { size: 3585, max_size: 3584 });
+    }
+
+    #[test]
+    fn test_hex_text_parse_error() {
+        let mut c8 = Chip8::_new();
+
+        let result = c8.load_instructions_from_hex_text("6000 61g0");
+
+        assert!(matches!(result, Err(Chip8Error::ParseError { .. })));
+    }
+
     #[test]
     fn test_cycle() {
         let mut c8 = Chip8::_new();
 
         // load an instruction into memory and set it to the next op
         c8.pc = 0x200;
-        c8.memory[0x200] = 0xb5;
-        c8.memory[0x201] = 0xda;
+        c8.memory[0x200] = 0xda;
+        c8.memory[0x201] = 0xb5;
 
         // set delay and sound timers
         c8.delay_timer = 3;
@@ -1435,9 +2933,9 @@ mod tests {
 
         // use setup for test_draw_overlapping_sprites
         c8.load_fontset();
-        for i in 0..5 {
-            for j in 0..8 {
-                c8.display_memory[i+1][j+1] = if ZERO_SPRITE[i][j] == 1u8 { PIXEL_ON } else { PIXEL_OFF };
+        for (line, sprite_row) in c8.display_memory[1..].iter_mut().zip(ZERO_SPRITE.iter()) {
+            for (pixel, &bit) in line[1..].iter_mut().zip(sprite_row.iter()) {
+                *pixel = if bit == 1u8 { PIXEL_ON } else { PIXEL_OFF };
             }
         }
         c8.index_register = 0x50;
@@ -1445,12 +2943,12 @@ mod tests {
         c8.registers[0xb] = 0x1;
         c8.registers[0xf] = 0x0;
 
-        c8.cycle();
+        c8.cycle().unwrap();
 
-        // assert cycle operations
+        // assert cycle operations; timers only tick once per frame
         assert_eq!(c8.pc, 0x202);
-        assert_eq!(c8.delay_timer, 2);
-        assert_eq!(c8.sound_timer, 3);
+        assert_eq!(c8.delay_timer, 3);
+        assert_eq!(c8.sound_timer, 4);
 
         // assertions from test_draw_overlapping_sprites
         assert_eq!(c8.registers[0xf], 0x1);
@@ -1461,4 +2959,128 @@ mod tests {
         }
         assert_eq!(c8.index_register, 0x50);
     }
+
+    #[test]
+    fn test_tick_timers() {
+        let mut c8 = Chip8::_new();
+        c8.delay_timer = 1;
+        c8.sound_timer = 0;
+
+        c8.tick_timers();
+        c8.tick_timers();
+
+        assert_eq!(c8.delay_timer, 0);
+        assert_eq!(c8.sound_timer, 0);
+    }
+
+    #[test]
+    fn test_timers_independent_of_instruction_rate() {
+        // 0x200: jump to 0x200 forever
+        let mut slow = Chip8::from_rom_bytes(&[0x12, 0x00]).unwrap();
+        let mut fast = Chip8::from_rom_bytes(&[0x12, 0x00]).unwrap();
+        slow.delay_timer = 60;
+        slow.sound_timer = 60;
+        fast.delay_timer = 60;
+        fast.sound_timer = 60;
+
+        for _ in 0..10 {
+            slow.run_frame(1).unwrap();
+            fast.run_frame(500).unwrap();
+        }
+
+        assert_eq!(slow.delay_timer, 50);
+        assert_eq!(slow.sound_timer, 50);
+        assert_eq!(fast.delay_timer, 50);
+        assert_eq!(fast.sound_timer, 50);
+    }
+
+    #[test]
+    fn test_save_and_load_state() {
+        // 0x200: V0 = random byte; 0x202: draw sprite at I; 0x204: jump to 0x200
+        let rom = [0xC0, 0xFF, 0xD0, 0x15, 0x12, 0x00];
+        let mut c8 = Chip8::with_platform(Platform::SuperChip);
+        c8.load_rom_bytes(&rom).unwrap();
+        c8.execute_opcode(0x00FF).unwrap();
+        c8.index_register = 0x50;
+        c8.delay_timer = 30;
+        c8.keypad[5] = 1;
+        c8.run_frame(5).unwrap();
+
+        let state = c8.save_state();
+        let mut restored = Chip8::with_platform(Platform::SuperChip);
+        restored.load_rom_bytes(&rom).unwrap();
+        restored.load_state(&state).unwrap();
+
+        assert_eq!(restored.save_state(), state);
+        assert_eq!(restored.registers, c8.registers);
+        assert_eq!(restored.memory, c8.memory);
+        assert_eq!(restored.pc, c8.pc);
+        assert_eq!(restored.delay_timer, 29);
+        assert_eq!(restored.keypad, c8.keypad);
+        assert!(restored.hires);
+        assert_eq!(restored.display_memory, c8.display_memory);
+        assert_eq!(restored.quirks, c8.quirks);
+        assert_eq!(restored.platform, Some(Platform::SuperChip));
+
+        // the random sequence continues where the saved machine left off
+        c8.run_frame(30).unwrap();
+        restored.run_frame(30).unwrap();
+        assert_eq!(restored.registers, c8.registers);
+        assert_eq!(restored.display_memory, c8.display_memory);
+    }
+
+    #[test]
+    fn test_load_state_from_other_rom() {
+        let c8 = Chip8::from_rom_bytes(&[0x00, 0xE0]).unwrap();
+        let mut other = Chip8::from_rom_bytes(&[0x12, 0x00]).unwrap();
+        other.registers[0] = 0x42;
+
+        let result = other.load_state(&c8.save_state());
+
+        assert_eq!(result, Err(Chip8Error::StateRomMismatch {
+            expected_hash: other.rom_hash,
+            found_hash: c8.rom_hash,
+        }));
+        assert_eq!(other.registers[0], 0x42);
+    }
+
+    #[test]
+    fn test_load_invalid_state() {
+        let mut c8 = Chip8::from_rom_bytes(&[0x00, 0xE0]).unwrap();
+        let state = c8.save_state();
+
+        assert!(matches!(c8.load_state(b"nonsense"), Err(Chip8Error::InvalidState { .. })));
+        assert!(matches!(c8.load_state(&state[..state.len() - 1]), Err(Chip8Error::InvalidState { .. })));
+
+        let mut newer = state.clone();
+        newer[5] = 99;
+        assert!(matches!(c8.load_state(&newer), Err(Chip8Error::InvalidState { .. })));
+
+        let mut trailing = state.clone();
+        trailing.push(0);
+        assert!(matches!(c8.load_state(&trailing), Err(Chip8Error::InvalidState { .. })));
+    }
+
+    #[test]
+    fn test_seeded_rand() {
+        // 0x200: V0 = random byte; 0x202: V1 = random byte
+        let rom = [0xC0, 0xFF, 0xC1, 0xFF];
+        let mut first = Chip8::from_rom_bytes(&rom).unwrap();
+        let mut second = Chip8::from_rom_bytes(&rom).unwrap();
+        first.seed_rng(42);
+        second.seed_rng(42);
+
+        first.run_frame(2).unwrap();
+        second.run_frame(2).unwrap();
+
+        assert_eq!(first.registers[..2], second.registers[..2]);
+        assert_eq!(first.get_rng_seed(), 42);
+
+        // reseeding restarts the sequence
+        let expected = first.registers;
+        first.seed_rng(42);
+        first.pc = 0x200;
+        first.run_frame(2).unwrap();
+        assert_eq!(first.registers, expected);
+    }
 }
@@ -0,0 +1,411 @@
+use std::collections::BTreeSet;
+
+use super::chip8::Chip8;
+use super::disassembler::{disassemble_instruction, Syntax};
+use super::error::Chip8Error;
+use super::instruction::Instruction;
+
+/// What the debugger does on the next call to `Debugger::run_frame`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunMode {
+    Running,
+    Paused,
+    /// Run until `pc` is back at `return_address` without a deeper call on the stack
+    StepOver { return_address: u16, sp: u8 },
+    /// Run until the stack is shallower than `sp`
+    StepOut { sp: u8 },
+}
+
+/// Runs a machine frame by frame like `Chip8::run_frame`, but can pause between any two
+/// instructions, at breakpoints or on request, and single-step from there.
+///
+/// Frames are counted in instructions: stepping through `instructions_per_frame` instructions
+/// ends the frame and ticks the timers exactly as running it would.
+#[derive(Debug, Clone)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u16>,
+    mode: RunMode,
+    instructions_per_frame: u32,
+    frame_position: u32,       // instructions executed in the current frame
+    ignore_breakpoint: bool,   // lets execution leave the breakpoint it is stopped at
+}
+
+impl Debugger {
+    pub fn new(instructions_per_frame: u32) -> Self {
+        Self {
+            breakpoints: BTreeSet::new(),
+            mode: RunMode::Running,
+            instructions_per_frame: instructions_per_frame.max(1),
+            frame_position: 0,
+            ignore_breakpoint: false,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.mode == RunMode::Paused
+    }
+
+    pub fn pause(&mut self) {
+        self.mode = RunMode::Paused;
+    }
+
+    pub fn resume(&mut self) {
+        self.mode = RunMode::Running;
+        self.ignore_breakpoint = true;
+    }
+
+    /// Whether no instruction of the current frame has been executed yet
+    pub fn is_at_frame_start(&self) -> bool {
+        self.frame_position == 0
+    }
+
+    /// Returns true if the breakpoint was added, false if it already existed
+    pub fn add_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.insert(address)
+    }
+
+    /// Returns true if the breakpoint existed
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// Adds the breakpoint if it is missing, removes it otherwise. Returns true if it is now set.
+    pub fn toggle_breakpoint(&mut self, address: u16) -> bool {
+        if self.remove_breakpoint(address) {
+            false
+        } else {
+            self.add_breakpoint(address)
+        }
+    }
+
+    /// Breakpoint addresses in ascending order
+    pub fn get_breakpoints(&self) -> Vec<u16> {
+        self.breakpoints.iter().copied().collect()
+    }
+
+    /// Runs the rest of the current frame, unless paused. Returns true if the frame was completed,
+    /// false if execution paused before its end.
+    pub fn run_frame(&mut self, c8: &mut Chip8) -> Result<bool, Chip8Error> {
+        if self.is_paused() {
+            return Ok(false);
+        }
+
+        loop {
+            if self.should_stop(c8) {
+                self.mode = RunMode::Paused;
+                return Ok(false);
+            }
+            self.ignore_breakpoint = false;
+
+            if self.execute(c8)? {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Executes a single instruction and pauses. Returns true if it completed the frame.
+    pub fn step(&mut self, c8: &mut Chip8) -> Result<bool, Chip8Error> {
+        self.mode = RunMode::Paused;
+        self.execute(c8)
+    }
+
+    /// Like `step`, but runs a called subroutine up to its return. Returns true if a frame
+    /// was completed.
+    pub fn step_over(&mut self, c8: &mut Chip8) -> Result<bool, Chip8Error> {
+        let pc = usize::from(c8.get_pc());
+        let is_call = c8.get_memory().get(pc..pc + 2)
+            .and_then(|word| Instruction::decode(u16::from_be_bytes([word[0], word[1]])))
+            .is_some_and(|instruction| matches!(instruction, Instruction::Call { .. }));
+        if !is_call {
+            return self.step(c8);
+        }
+
+        self.mode = RunMode::StepOver { return_address: c8.get_pc().wrapping_add(2), sp: c8.get_sp() };
+        self.ignore_breakpoint = true;
+        self.run_frame(c8)
+    }
+
+    /// Resumes until the current subroutine returns. Returns false, staying paused, when
+    /// not inside a subroutine.
+    pub fn step_out(&mut self, c8: &Chip8) -> bool {
+        if c8.get_sp() == 0 {
+            return false;
+        }
+
+        self.mode = RunMode::StepOut { sp: c8.get_sp() };
+        self.ignore_breakpoint = true;
+        true
+    }
+
+    fn should_stop(&self, c8: &Chip8) -> bool {
+        if c8.has_exited() {
+            return false;
+        }
+        if !self.ignore_breakpoint && self.breakpoints.contains(&c8.get_pc()) {
+            return true;
+        }
+
+        match self.mode {
+            RunMode::StepOver { return_address, sp } => c8.get_pc() == return_address && c8.get_sp() <= sp,
+            RunMode::StepOut { sp } => c8.get_sp() < sp,
+            RunMode::Running | RunMode::Paused => false,
+        }
+    }
+
+    /// Executes the next instruction of the frame. Returns true if it was the last one.
+    fn execute(&mut self, c8: &mut Chip8) -> Result<bool, Chip8Error> {
+        if self.frame_position == 0 {
+            c8.vertical_blank();
+        }
+
+        c8.cycle()?;
+
+        self.frame_position += 1;
+        if self.frame_position < self.instructions_per_frame {
+            return Ok(false);
+        }
+
+        c8.tick_timers();
+        self.frame_position = 0;
+        Ok(true)
+    }
+}
+
+/// A command typed at a debugger prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugCommand {
+    Continue,
+    /// Execute this many instructions
+    Step(u32),
+    StepOver,
+    StepOut,
+    /// Set a breakpoint, at `pc` if no address is given
+    Break(Option<u16>),
+    Delete(u16),
+    /// Show the machine state and the breakpoints
+    Info,
+    Help,
+    Quit,
+}
+
+/// Help text for the commands understood by `parse_command`
+pub const COMMAND_HELP: &str = "\
+c, continue       Resume execution
+s, step [N]       Execute N instructions [default: 1]
+n, next           Step over a subroutine call
+f, finish         Run until the current subroutine returns
+b, break [ADDR]   Set a breakpoint at hex address ADDR [default: pc]
+d, delete ADDR    Remove the breakpoint at ADDR
+i, info           Show the registers, stack, timers and breakpoints
+h, help           Show this help
+q, quit           Stop emulating";
+
+pub fn parse_command(line: &str) -> Result<DebugCommand, String> {
+    let mut words = line.split_whitespace();
+    let name = match words.next() {
+        Some(name) => name,
+        None => return Err(String::from("expected a command, type 'help' for a list")),
+    };
+    let argument = words.next();
+    if let Some(extra) = words.next() {
+        return Err(format!("unexpected argument '{}'", extra));
+    }
+
+    let address = |argument: Option<&str>| match argument {
+        Some(value) => parse_address(value).ok_or_else(|| format!("'{}' is not a hex address", value)),
+        None => Err(format!("'{}' expects an address", name)),
+    };
+
+    let command = match name {
+        "c" | "continue" => DebugCommand::Continue,
+        "s" | "step" => match argument {
+            Some(value) => match value.parse::<u32>() {
+                Ok(count) if count > 0 => DebugCommand::Step(count),
+                _ => return Err(format!("'{}' is not a positive number of instructions", value)),
+            },
+            None => DebugCommand::Step(1),
+        },
+        "n" | "next" => DebugCommand::StepOver,
+        "f" | "finish" => DebugCommand::StepOut,
+        "b" | "break" => match argument {
+            Some(_) => DebugCommand::Break(Some(address(argument)?)),
+            None => DebugCommand::Break(None),
+        },
+        "d" | "delete" => DebugCommand::Delete(address(argument)?),
+        "i" | "info" => DebugCommand::Info,
+        "h" | "help" => DebugCommand::Help,
+        "q" | "quit" => DebugCommand::Quit,
+        _ => return Err(format!("unknown command '{}', type 'help' for a list", name)),
+    };
+
+    if argument.is_some() && !matches!(command, DebugCommand::Step(_) | DebugCommand::Break(_) | DebugCommand::Delete(_)) {
+        return Err(format!("'{}' takes no arguments", name));
+    }
+    Ok(command)
+}
+
+/// Parses a hex address, with or without a `0x` prefix
+pub fn parse_address(value: &str) -> Option<u16> {
+    let digits = value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")).unwrap_or(value);
+    u16::from_str_radix(digits, 16).ok()
+}
+
+/// Multi-line listing of the registers, stack and timers, with the instruction at `pc`
+pub fn format_state(c8: &Chip8) -> String {
+    let pc = usize::from(c8.get_pc());
+    let opcode = match c8.get_memory().get(pc..pc + 2) {
+        Some(bytes) => match disassemble_instruction(&c8.get_memory()[pc..], c8.get_platform(), Syntax::Octo) {
+            Some(text) => format!("{:02X}{:02X}: {}", bytes[0], bytes[1], text),
+            None => format!("{:02X}{:02X}", bytes[0], bytes[1]),
+        },
+        None => String::from("----"),
+    };
+
+    let registers: Vec<String> = c8.get_registers().iter().enumerate()
+        .map(|(x, value)| format!("V{:X}={:02X}", x, value))
+        .collect();
+    let stack: Vec<String> = c8.get_stack()[..usize::from(c8.get_sp())].iter()
+        .map(|address| format!("{:03X}", address))
+        .collect();
+
+    format!(
+        "PC={:03X} ({})  I={:03X}  SP={}  DT={}  ST={}\n{}\n{}\nstack: {}",
+        pc, opcode, c8.get_index_register(), c8.get_sp(), c8.get_delay_timer(), c8.get_sound_timer(),
+        registers[..8].join(" "),
+        registers[8..].join(" "),
+        if stack.is_empty() { String::from("empty") } else { stack.join(" ") },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0x200: V0 += 1; 0x202: call 0x208; 0x204: V2 += 1; 0x206: jump to 0x200
+    // 0x208: V1 += 1; 0x20A: return
+    const ROM: [u8; 12] = [0x70, 0x01, 0x22, 0x08, 0x72, 0x01, 0x12, 0x00, 0x71, 0x01, 0x00, 0xEE];
+
+    #[test]
+    fn test_runs_like_chip8() {
+        let mut expected = Chip8::from_rom_bytes(&ROM).unwrap();
+        expected.set_delay_timer(30);
+        let mut c8 = expected.clone();
+        let mut debugger = Debugger::new(4);
+
+        for _ in 0..10 {
+            expected.run_frame(4).unwrap();
+            assert_eq!(debugger.run_frame(&mut c8), Ok(true));
+        }
+
+        assert_eq!(c8.get_state_hash(), expected.get_state_hash());
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let mut c8 = Chip8::from_rom_bytes(&ROM).unwrap();
+        let mut debugger = Debugger::new(100);
+        debugger.add_breakpoint(0x208);
+
+        assert_eq!(debugger.run_frame(&mut c8), Ok(false));
+        assert!(debugger.is_paused());
+        assert_eq!(c8.get_pc(), 0x208);
+        assert!(!debugger.is_at_frame_start());
+
+        // resuming leaves the breakpoint before it can trigger again
+        debugger.resume();
+        assert_eq!(debugger.run_frame(&mut c8), Ok(false));
+        assert_eq!(c8.get_pc(), 0x208);
+        assert_eq!(c8.get_registers()[1], 1);
+        assert_eq!(c8.get_registers()[0], 2);
+    }
+
+    #[test]
+    fn test_steps_complete_frames() {
+        let mut c8 = Chip8::from_rom_bytes(&ROM).unwrap();
+        c8.set_delay_timer(5);
+        let mut debugger = Debugger::new(2);
+
+        assert_eq!(debugger.step(&mut c8), Ok(false));
+        assert!(debugger.is_paused());
+        assert_eq!(c8.get_delay_timer(), 5);
+
+        assert_eq!(debugger.step(&mut c8), Ok(true));
+        assert_eq!(c8.get_delay_timer(), 4);
+        assert_eq!(c8.get_pc(), 0x208);
+
+        // paused until resumed
+        assert_eq!(debugger.run_frame(&mut c8), Ok(false));
+        assert_eq!(c8.get_pc(), 0x208);
+    }
+
+    #[test]
+    fn test_step_over_and_out() {
+        let mut c8 = Chip8::from_rom_bytes(&ROM).unwrap();
+        let mut debugger = Debugger::new(100);
+        debugger.step(&mut c8).unwrap();
+
+        // not inside a subroutine yet
+        assert!(!debugger.step_out(&c8));
+
+        assert_eq!(debugger.step_over(&mut c8), Ok(false));
+        assert!(debugger.is_paused());
+        assert_eq!(c8.get_pc(), 0x204);
+        assert_eq!(c8.get_registers()[1], 1);
+
+        // stepping over anything but a call is a single step
+        debugger.step_over(&mut c8).unwrap();
+        assert_eq!(c8.get_pc(), 0x206);
+
+        debugger.step(&mut c8).unwrap();
+        debugger.step(&mut c8).unwrap();
+        debugger.step(&mut c8).unwrap();
+        assert_eq!(c8.get_pc(), 0x208);
+        assert!(debugger.step_out(&c8));
+        assert_eq!(debugger.run_frame(&mut c8), Ok(false));
+        assert_eq!(c8.get_pc(), 0x204);
+        assert_eq!(c8.get_sp(), 0);
+    }
+
+    #[test]
+    fn test_toggle_breakpoint() {
+        let mut debugger = Debugger::new(10);
+
+        assert!(debugger.toggle_breakpoint(0x300));
+        assert!(debugger.add_breakpoint(0x200));
+        assert!(!debugger.add_breakpoint(0x200));
+        assert_eq!(debugger.get_breakpoints(), vec![0x200, 0x300]);
+        assert!(!debugger.toggle_breakpoint(0x300));
+        assert!(debugger.remove_breakpoint(0x200));
+        assert!(debugger.get_breakpoints().is_empty());
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("c"), Ok(DebugCommand::Continue));
+        assert_eq!(parse_command("  step 10 "), Ok(DebugCommand::Step(10)));
+        assert_eq!(parse_command("s"), Ok(DebugCommand::Step(1)));
+        assert_eq!(parse_command("b 0x2A0"), Ok(DebugCommand::Break(Some(0x2A0))));
+        assert_eq!(parse_command("break"), Ok(DebugCommand::Break(None)));
+        assert_eq!(parse_command("d 2a0"), Ok(DebugCommand::Delete(0x2A0)));
+        assert_eq!(parse_command("finish"), Ok(DebugCommand::StepOut));
+
+        assert!(parse_command("").is_err());
+        assert!(parse_command("step 0").is_err());
+        assert!(parse_command("delete").is_err());
+        assert!(parse_command("b xyz").is_err());
+        assert!(parse_command("next 2").is_err());
+        assert!(parse_command("jump").is_err());
+    }
+
+    #[test]
+    fn test_format_state() {
+        let mut c8 = Chip8::from_rom_bytes(&ROM).unwrap();
+        c8.get_registers_mut()[0xF] = 0xAB;
+
+        let state = format_state(&c8);
+
+        assert!(state.starts_with("PC=200 (7001: v0 += 0x01)  I=000  SP=0"));
+        assert!(state.contains("VF=AB"));
+        assert!(state.ends_with("stack: empty"));
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::BTreeMap;
+
+use super::constants::PROGRAM_START_ADDRESS;
+use super::instruction::Instruction;
+use super::platform::Platform;
+
+/// Assembly dialects the disassembler can write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Syntax {
+    /// Octo's structured syntax, e.g. `v0 := 0x05` and `if v1 == 0x02 then`
+    Octo,
+    /// Cowgod's mnemonics, e.g. `LD V0, 0x05` and `SNE V1, 0x02`
+    Classic,
+}
+
+impl Syntax {
+    /// Names accepted by `Syntax::from_name`
+    pub const NAMES: [&'static str; 2] = ["octo", "classic"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "octo" => Some(Syntax::Octo),
+            "classic" | "cowgod" => Some(Syntax::Classic),
+            _ => None,
+        }
+    }
+
+    fn comment(&self) -> &'static str {
+        match self {
+            Syntax::Octo => "#",
+            Syntax::Classic => ";",
+        }
+    }
+}
+
+/// One instruction of a disassembled ROM, or a data word that does not decode as one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    /// Name of the jump or call target at this address
+    pub label: Option<String>,
+    /// Mnemonic with its operands, or a data directive
+    pub text: String,
+}
+
+/// Disassembles a ROM image loaded at 0x200, in a single linear sweep.
+///
+/// Opcodes the platform does not support are written as data, as is a trailing odd byte.
+/// Jump and call targets that start an instruction get a label: `sub_XXX` for subroutines,
+/// `label_XXX` for anything else.
+pub fn disassemble(rom: &[u8], platform: Option<Platform>, syntax: Syntax) -> Vec<Line> {
+    let mut words = Vec::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        let length = instruction_length(&rom[offset..], platform);
+        words.push((offset, length));
+        offset += length;
+    }
+
+    // a target that does not start an instruction keeps its numeric address
+    let mut labels = BTreeMap::new();
+    for (offset, length) in words.iter() {
+        match decode(&rom[*offset..*offset + *length], platform) {
+            Some(Instruction::Jump { nnn }) => { labels.entry(nnn).or_insert_with(|| format!("label_{:03X}", nnn)); },
+            Some(Instruction::Call { nnn }) => { labels.insert(nnn, format!("sub_{:03X}", nnn)); },
+            _ => {},
+        }
+    }
+    labels.retain(|target, _| words.iter().any(|(offset, _)| usize::from(*target) == PROGRAM_START_ADDRESS + offset));
+
+    let name_target = |target: u16| match labels.get(&target) {
+        Some(label) => label.clone(),
+        None => format!("0x{:03X}", target),
+    };
+
+    words.iter()
+        .map(|(offset, length)| {
+            let address = (PROGRAM_START_ADDRESS + offset) as u16;
+            let bytes = rom[*offset..*offset + *length].to_vec();
+            let text = match decode(&bytes, platform) {
+                Some(instruction) => format_instruction(instruction, &bytes, syntax, &name_target),
+                None => format_data(&bytes, syntax),
+            };
+
+            Line { address, label: labels.get(&address).cloned(), bytes, text }
+        })
+        .collect()
+}
+
+/// Decodes the instruction at the start of `bytes`, writing addresses as numbers.
+/// Returns `None` if it is not an instruction of the platform.
+pub fn disassemble_instruction(bytes: &[u8], platform: Option<Platform>, syntax: Syntax) -> Option<String> {
+    let instruction = decode(bytes, platform)?;
+    Some(format_instruction(instruction, bytes, syntax, &|target| format!("0x{:03X}", target)))
+}
+
+/// Writes the disassembly as source text, with the address and raw bytes of every line in a
+/// comment so that the listing can be assembled again
+pub fn format_listing(lines: &[Line], syntax: Syntax) -> String {
+    let mut listing = String::new();
+
+    for line in lines.iter() {
+        if let Some(label) = &line.label {
+            listing.push_str(&match syntax {
+                Syntax::Octo => format!(": {}\n", label),
+                Syntax::Classic => format!("{}:\n", label),
+            });
+        }
+
+        let raw: Vec<String> = line.bytes.chunks(2)
+            .map(|word| word.iter().map(|byte| format!("{:02X}", byte)).collect())
+            .collect();
+        listing.push_str(&format!("    {:<28}{} {:03X}: {}\n", line.text, syntax.comment(), line.address, raw.join(" ")));
+    }
+
+    listing
+}
+
+/// XO-CHIP's `F000 nnnn` is 4 bytes long; every other instruction takes 2
+fn instruction_length(bytes: &[u8], platform: Option<Platform>) -> usize {
+    match decode(bytes, platform) {
+        Some(instruction) => instruction.size(),
+        None => bytes.len().min(2),
+    }
+}
+
+fn format_data(bytes: &[u8], syntax: Syntax) -> String {
+    let values: Vec<String> = bytes.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+    match syntax {
+        Syntax::Octo => values.join(" "),
+        Syntax::Classic => format!("DB {}", values.join(", ")),
+    }
+}
+
+/// Decodes the instruction at the start of `bytes`, if the platform supports it and all of
+/// its bytes are present
+fn decode(bytes: &[u8], platform: Option<Platform>) -> Option<Instruction> {
+    if bytes.len() < 2 {
+        return None;
+    }
+
+    let instruction = Instruction::decode(u16::from_be_bytes([bytes[0], bytes[1]]))?;
+    (instruction.is_supported_by(platform) && bytes.len() >= instruction.size()).then_some(instruction)
+}
+
+/// Writes an instruction decoded from `bytes`. `name_target` writes the target of a jump or call.
+fn format_instruction(instruction: Instruction, bytes: &[u8], syntax: Syntax, name_target: &dyn Fn(u16) -> String) -> String {
+    use Instruction::*;
+
+    let v = |register: u8| match syntax {
+        Syntax::Octo => format!("v{:x}", register),
+        Syntax::Classic => format!("V{:X}", register),
+    };
+
+    let (octo, classic) = match instruction {
+        ClearScreen => (String::from("clear"), String::from("CLS")),
+        Return => (String::from("return"), String::from("RET")),
+        ScrollDown { n } => (format!("scroll-down {}", n), format!("SCD {}", n)),
+        ScrollRight => (String::from("scroll-right"), String::from("SCR")),
+        ScrollLeft => (String::from("scroll-left"), String::from("SCL")),
+        Exit => (String::from("exit"), String::from("EXIT")),
+        LowRes => (String::from("lores"), String::from("LOW")),
+        HighRes => (String::from("hires"), String::from("HIGH")),
+        Jump { nnn } => (format!("jump {}", name_target(nnn)), format!("JP {}", name_target(nnn))),
+        Call { nnn } => (format!(":call {}", name_target(nnn)), format!("CALL {}", name_target(nnn))),
+        SkipEqImm { x, kk } => (format!("if {} != 0x{:02X} then", v(x), kk), format!("SE {}, 0x{:02X}", v(x), kk)),
+        SkipNeImm { x, kk } => (format!("if {} == 0x{:02X} then", v(x), kk), format!("SNE {}, 0x{:02X}", v(x), kk)),
+        SkipEqReg { x, y } => (format!("if {} != {} then", v(x), v(y)), format!("SE {}, {}", v(x), v(y))),
+        SaveRange { x, y } => (format!("save {} - {}", v(x), v(y)), format!("LD [I], {}-{}", v(x), v(y))),
+        LoadRange { x, y } => (format!("load {} - {}", v(x), v(y)), format!("LD {}-{}, [I]", v(x), v(y))),
+        LoadImm { x, kk } => (format!("{} := 0x{:02X}", v(x), kk), format!("LD {}, 0x{:02X}", v(x), kk)),
+        AddImm { x, kk } => (format!("{} += 0x{:02X}", v(x), kk), format!("ADD {}, 0x{:02X}", v(x), kk)),
+        Move { x, y } | Or { x, y } | And { x, y } | Xor { x, y } | AddReg { x, y }
+        | Sub { x, y } | ShiftRight { x, y } | SubN { x, y } | ShiftLeft { x, y } => {
+            let (operator, mnemonic) = match instruction {
+                Move { .. } => (":=", "LD"),
+                Or { .. } => ("|=", "OR"),
+                And { .. } => ("&=", "AND"),
+                Xor { .. } => ("^=", "XOR"),
+                AddReg { .. } => ("+=", "ADD"),
+                Sub { .. } => ("-=", "SUB"),
+                ShiftRight { .. } => (">>=", "SHR"),
+                SubN { .. } => ("=-", "SUBN"),
+                _ => ("<<=", "SHL"),
+            };
+            (format!("{} {} {}", v(x), operator, v(y)), format!("{} {}, {}", mnemonic, v(x), v(y)))
+        },
+        SkipNeReg { x, y } => (format!("if {} == {} then", v(x), v(y)), format!("SNE {}, {}", v(x), v(y))),
+        LoadI { nnn } => (format!("i := 0x{:03X}", nnn), format!("LD I, 0x{:03X}", nnn)),
+        JumpOffset { nnn } => (format!("jump0 {}", name_target(nnn)), format!("JP V0, {}", name_target(nnn))),
+        Random { x, kk } => (format!("{} := random 0x{:02X}", v(x), kk), format!("RND {}, 0x{:02X}", v(x), kk)),
+        Draw { x, y, n } => (format!("sprite {} {} {}", v(x), v(y), n), format!("DRW {}, {}, {}", v(x), v(y), n)),
+        SkipKey { x } => (format!("if {} -key then", v(x)), format!("SKP {}", v(x))),
+        SkipNotKey { x } => (format!("if {} key then", v(x)), format!("SKNP {}", v(x))),
+        LoadILong => {
+            let address = u16::from_be_bytes([bytes[2], bytes[3]]);
+            (format!("i := long 0x{:04X}", address), format!("LD I, LONG 0x{:04X}", address))
+        },
+        SelectPlanes { n } => (format!("plane {}", n), format!("PLANE {}", n)),
+        LoadAudio => (String::from("audio"), String::from("AUDIO")),
+        GetDelay { x } => (format!("{} := delay", v(x)), format!("LD {}, DT", v(x))),
+        WaitKey { x } => (format!("{} := key", v(x)), format!("LD {}, K", v(x))),
+        SetDelay { x } => (format!("delay := {}", v(x)), format!("LD DT, {}", v(x))),
+        SetSound { x } => (format!("buzzer := {}", v(x)), format!("LD ST, {}", v(x))),
+        AddI { x } => (format!("i += {}", v(x)), format!("ADD I, {}", v(x))),
+        LoadFont { x } => (format!("i := hex {}", v(x)), format!("LD F, {}", v(x))),
+        LoadBigFont { x } => (format!("i := bighex {}", v(x)), format!("LD HF, {}", v(x))),
+        StoreBcd { x } => (format!("bcd {}", v(x)), format!("LD B, {}", v(x))),
+        SetPitch { x } => (format!("pitch := {}", v(x)), format!("PITCH {}", v(x))),
+        SaveRegisters { x } => (format!("save {}", v(x)), format!("LD [I], {}", v(x))),
+        LoadRegisters { x } => (format!("load {}", v(x)), format!("LD {}, [I]", v(x))),
+        SaveFlags { x } => (format!("saveflags {}", v(x)), format!("LD R, {}", v(x))),
+        LoadFlags { x } => (format!("loadflags {}", v(x)), format!("LD {}, R", v(x))),
+    };
+
+    match syntax {
+        Syntax::Octo => octo,
+        Syntax::Classic => classic,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0x200: call 0x206; 0x202: V0 += 1; 0x204: jump to 0x202
+    // 0x206: draw 5 rows at (V0, V1); 0x208: return; 0x20A: data
+    const ROM: [u8; 13] = [0x22, 0x06, 0x70, 0x01, 0x12, 0x02, 0xD0, 0x15, 0x00, 0xEE, 0xFF, 0xFF, 0x42];
+
+    #[test]
+    fn test_disassemble_octo() {
+        let lines = disassemble(&ROM, None, Syntax::Octo);
+
+        let texts: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(texts, [":call sub_206", "v0 += 0x01", "jump label_202", "sprite v0 v1 5", "return", "0xFF 0xFF", "0x42"]);
+
+        let labels: Vec<(u16, &str)> = lines.iter()
+            .filter_map(|line| line.label.as_deref().map(|label| (line.address, label)))
+            .collect();
+        assert_eq!(labels, [(0x202, "label_202"), (0x206, "sub_206")]);
+
+        assert_eq!(lines[6].address, 0x20C);
+        assert_eq!(lines[6].bytes, [0x42]);
+    }
+
+    #[test]
+    fn test_disassemble_classic() {
+        let lines = disassemble(&ROM, None, Syntax::Classic);
+
+        let texts: Vec<&str> = lines.iter().map(|line| line.text.as_str()).collect();
+        assert_eq!(texts, ["CALL sub_206", "ADD V0, 0x01", "JP label_202", "DRW V0, V1, 5", "RET", "DB 0xFF, 0xFF", "DB 0x42"]);
+    }
+
+    #[test]
+    fn test_targets_outside_the_listing_stay_numeric() {
+        // 0x200: jump into the middle of the long instruction at 0x202
+        let rom = [0x12, 0x04, 0xF0, 0x00, 0x12, 0x34];
+
+        let lines = disassemble(&rom, Some(Platform::XoChip), Syntax::Octo);
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].text, "jump 0x204");
+        assert_eq!(lines[1].text, "i := long 0x1234");
+        assert_eq!(lines[1].bytes.len(), 4);
+        assert!(lines.iter().all(|line| line.label.is_none()));
+    }
+
+    #[test]
+    fn test_platform_specific_opcodes() {
+        assert_eq!(disassemble_instruction(&[0x00, 0xFF], None, Syntax::Octo), None);
+        assert_eq!(disassemble_instruction(&[0x00, 0xFF], Some(Platform::SuperChip), Syntax::Octo).as_deref(), Some("hires"));
+        assert_eq!(disassemble_instruction(&[0xF3, 0x75], Some(Platform::SuperChip), Syntax::Classic).as_deref(), Some("LD R, V3"));
+        assert_eq!(disassemble_instruction(&[0x51, 0x42], Some(Platform::SuperChip), Syntax::Octo), None);
+        assert_eq!(disassemble_instruction(&[0x51, 0x42], Some(Platform::XoChip), Syntax::Octo).as_deref(), Some("save v1 - v4"));
+        assert_eq!(disassemble_instruction(&[0xF2, 0x01], Some(Platform::XoChip), Syntax::Classic).as_deref(), Some("PLANE 2"));
+        assert_eq!(disassemble_instruction(&[0x8A, 0xB7], None, Syntax::Octo).as_deref(), Some("va =- vb"));
+        assert_eq!(disassemble_instruction(&[0xE5, 0x9E], None, Syntax::Octo).as_deref(), Some("if v5 -key then"));
+        assert_eq!(disassemble_instruction(&[0x12], None, Syntax::Octo), None);
+    }
+
+    #[test]
+    fn test_format_listing() {
+        let listing = format_listing(&disassemble(&ROM[..6], None, Syntax::Octo), Syntax::Octo);
+
+        let expected = "    :call 0x206                 # 200: 2206\n\
+                        : label_202\n\
+                        \x20   v0 += 0x01                  # 202: 7001\n\
+                        \x20   jump label_202              # 204: 1202\n";
+        assert_eq!(listing, expected);
+
+        let listing = format_listing(&disassemble(&ROM[10..], None, Syntax::Classic), Syntax::Classic);
+        assert_eq!(listing, "    DB 0xFF, 0xFF               ; 200: FFFF\n    DB 0x42                     ; 202: 42\n");
+    }
+
+    #[test]
+    fn test_syntax_from_name() {
+        assert_eq!(Syntax::from_name("Octo"), Some(Syntax::Octo));
+        assert_eq!(Syntax::from_name("classic"), Some(Syntax::Classic));
+        assert_eq!(Syntax::from_name("intel"), None);
+    }
+}
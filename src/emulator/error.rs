@@ -0,0 +1,75 @@
+use std::fmt;
+
+/// Errors raised while loading or executing a CHIP-8 program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Chip8Error {
+    /// The opcode fetched from `address` does not decode to any instruction
+    UnknownOpcode { opcode: u16, address: u16 },
+    /// A `call` at `address` was made with every stack slot in use
+    StackOverflow { address: u16 },
+    /// A `ret` at `address` was made with an empty stack
+    StackUnderflow { address: u16 },
+    /// An instruction tried to read or write memory past the end of the address space
+    MemoryOutOfBounds { address: usize },
+    /// The ROM does not fit in the memory available above `0x200`
+    RomTooLarge { size: usize, max_size: usize },
+    /// A word in a hex-text `.ch8` file could not be parsed
+    ParseError { word: String, reason: String },
+    /// A ROM or save state file could not be read or written
+    Io { path: String, reason: String },
+    /// The data passed to `Chip8::load_state` is not a valid save state
+    InvalidState { reason: String },
+    /// The save state was made while running a different ROM than the one loaded
+    StateRomMismatch { expected_hash: u64, found_hash: u64 },
+    /// A movie file is malformed or was recorded with a different ROM
+    InvalidMovie { reason: String },
+    /// Playing back a movie did not end in the state it was recorded with
+    MovieDesync { expected_hash: u64, found_hash: u64 },
+    /// Assembly source could not be assembled; `line` and `column` start at 1
+    Assembly { path: String, line: usize, column: usize, reason: String },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::UnknownOpcode { opcode, address } => {
+                write!(f, "no instruction for opcode 0x{:04X} at address 0x{:03X}", opcode, address)
+            },
+            Chip8Error::StackOverflow { address } => {
+                write!(f, "stack overflow on call at address 0x{:03X}", address)
+            },
+            Chip8Error::StackUnderflow { address } => {
+                write!(f, "stack underflow on return at address 0x{:03X}", address)
+            },
+            Chip8Error::MemoryOutOfBounds { address } => {
+                write!(f, "memory access out of bounds at address 0x{:X}", address)
+            },
+            Chip8Error::RomTooLarge { size, max_size } => {
+                write!(f, "ROM is {} bytes but at most {} bytes fit in memory", size, max_size)
+            },
+            Chip8Error::ParseError { word, reason } => {
+                write!(f, "error parsing instruction file. Tried converting {} and got {}", word, reason)
+            },
+            Chip8Error::Io { path, reason } => {
+                write!(f, "could not access {}: {}", path, reason)
+            },
+            Chip8Error::InvalidState { reason } => {
+                write!(f, "invalid save state: {}", reason)
+            },
+            Chip8Error::StateRomMismatch { expected_hash, found_hash } => {
+                write!(f, "save state belongs to ROM {:016X}, but ROM {:016X} is loaded", found_hash, expected_hash)
+            },
+            Chip8Error::InvalidMovie { reason } => {
+                write!(f, "invalid movie: {}", reason)
+            },
+            Chip8Error::MovieDesync { expected_hash, found_hash } => {
+                write!(f, "movie desynced: expected final state {:016X}, got {:016X}", expected_hash, found_hash)
+            },
+            Chip8Error::Assembly { path, line, column, reason } => {
+                write!(f, "{}:{}:{}: {}", path, line, column, reason)
+            },
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
@@ -0,0 +1,264 @@
+use super::chip8::Chip8;
+use super::error::Chip8Error;
+
+/// Largest scale factor the frontends accept for the window, screenshots and captures. Keeps a
+/// scaled 128x64 display at 8192x4096, well within the sizes image formats can store.
+pub const MAX_SCALE: u32 = 64;
+
+/// `0xRRGGBB` colors for each combination of the two XO-CHIP bitplanes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    /// Pixels that are off in both planes
+    pub background: u32,
+    /// Pixels that are on in plane 1 only; the only "on" color outside of XO-CHIP
+    pub foreground: u32,
+    /// Pixels that are on in plane 2 only
+    pub plane2: u32,
+    /// Pixels that are on in both planes
+    pub blend: u32,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            background: 0x000000,
+            foreground: 0xFFFFFF,
+            plane2: 0xFF6600,
+            blend: 0x662200,
+        }
+    }
+}
+
+impl Palette {
+    /// Returns the `0xRRGGBB` color of a color index from `Chip8::get_display_colors`
+    pub fn color(&self, color_index: u8) -> u32 {
+        match color_index & 0b11 {
+            0b00 => self.background,
+            0b01 => self.foreground,
+            0b10 => self.plane2,
+            _ => self.blend,
+        }
+    }
+}
+
+/// File formats the display can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// 24-bit RGB PNG in the colors of the palette
+    Png,
+    /// Binary (`P4`) portable bitmap; every pixel that is not background is black
+    Pbm,
+}
+
+impl ImageFormat {
+    /// Picks the format from the extension of `path`
+    pub fn from_path(path: &str) -> Option<Self> {
+        let extension = path.rsplit_once('.')?.1;
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(ImageFormat::Png),
+            "pbm" => Some(ImageFormat::Pbm),
+            _ => None,
+        }
+    }
+}
+
+/// Encodes the display, each pixel scaled up to a `scale`x`scale` square.
+///
+/// `display_colors` holds the color index of each pixel, see `Chip8::get_display_colors`.
+pub fn encode(format: ImageFormat, display_colors: &[Vec<u8>], scale: usize, palette: &Palette) -> Vec<u8> {
+    match format {
+        ImageFormat::Png => encode_png(display_colors, scale, palette),
+        ImageFormat::Pbm => encode_pbm(display_colors, scale),
+    }
+}
+
+/// Writes the current display of `c8` to `file_path`, in the format given by its extension
+pub fn save_screenshot(c8: &Chip8, file_path: &str, scale: usize, palette: &Palette) -> Result<(), Chip8Error> {
+    let format = match ImageFormat::from_path(file_path) {
+        Some(format) => format,
+        None => return Err(Chip8Error::Io {
+            path: file_path.to_string(),
+            reason: String::from("unsupported image format, expected .png or .pbm"),
+        }),
+    };
+
+    let image = encode(format, &c8.get_display_colors(), scale, palette);
+    std::fs::write(file_path, image).map_err(|e| Chip8Error::Io {
+        path: file_path.to_string(),
+        reason: e.to_string(),
+    })
+}
+
+/// Repeats every pixel and row `scale` times
+fn scale_rows(display_colors: &[Vec<u8>], scale: usize) -> Vec<Vec<u8>> {
+    let scale = scale.max(1);
+
+    display_colors.iter()
+        .flat_map(|row| {
+            let scaled_row: Vec<u8> = row.iter().flat_map(|pixel| std::iter::repeat_n(*pixel, scale)).collect();
+            std::iter::repeat_n(scaled_row, scale)
+        })
+        .collect()
+}
+
+pub fn encode_pbm(display_colors: &[Vec<u8>], scale: usize) -> Vec<u8> {
+    let rows = scale_rows(display_colors, scale);
+    let width = rows.first().map_or(0, |row| row.len());
+
+    let mut image = format!("P4\n{} {}\n", width, rows.len()).into_bytes();
+    for row in rows.iter() {
+        // rows are padded to whole bytes; 1 is black
+        for chunk in row.chunks(8) {
+            image.push(chunk.iter().enumerate().fold(0, |byte, (i, pixel)| byte | (u8::from(*pixel != 0) << (7 - i))));
+        }
+    }
+    image
+}
+
+pub fn encode_png(display_colors: &[Vec<u8>], scale: usize, palette: &Palette) -> Vec<u8> {
+    let rows = scale_rows(display_colors, scale);
+    let width = rows.first().map_or(0, |row| row.len());
+
+    // every scanline starts with filter type 0 (none)
+    let mut pixels = Vec::with_capacity(rows.len() * (1 + 3 * width));
+    for row in rows.iter() {
+        pixels.push(0);
+        for pixel in row.iter() {
+            pixels.extend_from_slice(&palette.color(*pixel).to_be_bytes()[1..]);
+        }
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(rows.len() as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);    // 8-bit RGB, deflate, no filtering, no interlacing
+
+    let mut image = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    write_png_chunk(&mut image, b"IHDR", &header);
+    write_png_chunk(&mut image, b"IDAT", &zlib_store(&pixels));
+    write_png_chunk(&mut image, b"IEND", &[]);
+    image
+}
+
+fn write_png_chunk(image: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    image.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    image.extend_from_slice(chunk_type);
+    image.extend_from_slice(data);
+
+    let crc = crc32(&[chunk_type.as_slice(), data].concat());
+    image.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// Wraps `data` in a zlib stream of uncompressed deflate blocks
+fn zlib_store(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01];
+
+    let mut blocks = data.chunks(0xFFFF).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[0x01, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let length = block.len() as u16;
+
+        stream.push(u8::from(is_final));
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), byte| {
+        let a = (a + u32::from(*byte)) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(0xFFFF_FFFF, |crc, byte| CRC_TABLE[((crc ^ u32::from(*byte)) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn test_zlib_store() {
+        assert_eq!(zlib_store(b"abc"), [0x78, 0x01, 0x01, 0x03, 0x00, 0xFC, 0xFF, b'a', b'b', b'c', 0x02, 0x4D, 0x01, 0x27]);
+
+        // data is split into blocks of at most 65535 bytes
+        let stream = zlib_store(&vec![0; 0x10000]);
+        assert_eq!(stream.len(), 2 + (5 + 0xFFFF) + (5 + 1) + 4);
+        assert_eq!(stream[2], 0x00);
+        assert_eq!(stream[2 + 5 + 0xFFFF], 0x01);
+    }
+
+    #[test]
+    fn test_encode_pbm() {
+        let display = [vec![1, 0, 0, 0, 0, 0, 0, 0, 2], vec![0, 0, 0, 0, 0, 0, 0, 0, 0]];
+
+        let mut expected = b"P4\n9 2\n".to_vec();
+        expected.extend_from_slice(&[0b1000_0000, 0b1000_0000, 0, 0]);
+        assert_eq!(encode_pbm(&display, 1), expected);
+
+        let mut expected = b"P4\n2 2\n".to_vec();
+        expected.extend_from_slice(&[0b1100_0000, 0b1100_0000]);
+        assert_eq!(encode_pbm(&[vec![1]], 2), expected);
+    }
+
+    #[test]
+    fn test_encode_png() {
+        let palette = Palette { background: 0x000000, foreground: 0x112233, plane2: 0, blend: 0 };
+
+        let image = encode_png(&[vec![0, 1]], 2, &palette);
+
+        assert_eq!(image[..8], [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]);
+        assert_eq!(image[12..16], *b"IHDR");
+        assert_eq!(image[16..20], 4u32.to_be_bytes());
+        assert_eq!(image[20..24], 2u32.to_be_bytes());
+        assert_eq!(image[24..29], [8, 2, 0, 0, 0]);
+
+        // the IDAT payload is stored uncompressed, so the scanlines can be read back directly
+        let scanline = [0, 0, 0, 0, 0, 0, 0, 0x11, 0x22, 0x33, 0x11, 0x22, 0x33];
+        let idat_data = &image[41..];
+        assert_eq!(idat_data[7..20], scanline);
+        assert_eq!(idat_data[20..33], scanline);
+        assert_eq!(image[image.len() - 12..], [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]);
+    }
+
+    #[test]
+    fn test_image_format_from_path() {
+        assert_eq!(ImageFormat::from_path("shot.PNG"), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_path("dir.v2/shot.pbm"), Some(ImageFormat::Pbm));
+        assert_eq!(ImageFormat::from_path("shot"), None);
+    }
+}
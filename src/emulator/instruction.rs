@@ -0,0 +1,318 @@
+use super::platform::Platform;
+
+/// A decoded CHIP-8 instruction, from the base set or the SUPER-CHIP and XO-CHIP extensions.
+///
+/// `x` and `y` are register indices, `n` a 4-bit operand, `kk` a byte and `nnn` a 12-bit address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// `00E0`
+    ClearScreen,
+    /// `00EE`
+    Return,
+    /// `00Cn`, SUPER-CHIP
+    ScrollDown { n: u8 },
+    /// `00FB`, SUPER-CHIP
+    ScrollRight,
+    /// `00FC`, SUPER-CHIP
+    ScrollLeft,
+    /// `00FD`, SUPER-CHIP
+    Exit,
+    /// `00FE`, SUPER-CHIP
+    LowRes,
+    /// `00FF`, SUPER-CHIP
+    HighRes,
+    /// `1nnn`
+    Jump { nnn: u16 },
+    /// `2nnn`
+    Call { nnn: u16 },
+    /// `3xkk`
+    SkipEqImm { x: u8, kk: u8 },
+    /// `4xkk`
+    SkipNeImm { x: u8, kk: u8 },
+    /// `5xy0`
+    SkipEqReg { x: u8, y: u8 },
+    /// `5xy2`, XO-CHIP
+    SaveRange { x: u8, y: u8 },
+    /// `5xy3`, XO-CHIP
+    LoadRange { x: u8, y: u8 },
+    /// `6xkk`
+    LoadImm { x: u8, kk: u8 },
+    /// `7xkk`
+    AddImm { x: u8, kk: u8 },
+    /// `8xy0`
+    Move { x: u8, y: u8 },
+    /// `8xy1`
+    Or { x: u8, y: u8 },
+    /// `8xy2`
+    And { x: u8, y: u8 },
+    /// `8xy3`
+    Xor { x: u8, y: u8 },
+    /// `8xy4`
+    AddReg { x: u8, y: u8 },
+    /// `8xy5`
+    Sub { x: u8, y: u8 },
+    /// `8xy6`
+    ShiftRight { x: u8, y: u8 },
+    /// `8xy7`
+    SubN { x: u8, y: u8 },
+    /// `8xyE`
+    ShiftLeft { x: u8, y: u8 },
+    /// `9xy0`
+    SkipNeReg { x: u8, y: u8 },
+    /// `Annn`
+    LoadI { nnn: u16 },
+    /// `Bnnn`, or `Bxnn` with the `jump_with_vx` quirk
+    JumpOffset { nnn: u16 },
+    /// `Cxkk`
+    Random { x: u8, kk: u8 },
+    /// `Dxyn`
+    Draw { x: u8, y: u8, n: u8 },
+    /// `Ex9E`
+    SkipKey { x: u8 },
+    /// `ExA1`
+    SkipNotKey { x: u8 },
+    /// `F000 nnnn`, XO-CHIP. The 16-bit address is the word following the opcode.
+    LoadILong,
+    /// `Fn01`, XO-CHIP
+    SelectPlanes { n: u8 },
+    /// `F002`, XO-CHIP
+    LoadAudio,
+    /// `Fx07`
+    GetDelay { x: u8 },
+    /// `Fx0A`
+    WaitKey { x: u8 },
+    /// `Fx15`
+    SetDelay { x: u8 },
+    /// `Fx18`
+    SetSound { x: u8 },
+    /// `Fx1E`
+    AddI { x: u8 },
+    /// `Fx29`
+    LoadFont { x: u8 },
+    /// `Fx30`, SUPER-CHIP
+    LoadBigFont { x: u8 },
+    /// `Fx33`
+    StoreBcd { x: u8 },
+    /// `Fx3A`, XO-CHIP
+    SetPitch { x: u8 },
+    /// `Fx55`
+    SaveRegisters { x: u8 },
+    /// `Fx65`
+    LoadRegisters { x: u8 },
+    /// `Fx75`, SUPER-CHIP
+    SaveFlags { x: u8 },
+    /// `Fx85`, SUPER-CHIP
+    LoadFlags { x: u8 },
+}
+
+impl Instruction {
+    /// Decodes an opcode of any platform. Returns `None` if it is not an instruction at all;
+    /// see `is_supported_by` for whether a platform can execute it.
+    pub fn decode(opcode: u16) -> Option<Self> {
+        use Instruction::*;
+
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let kk = (opcode & 0x00FF) as u8;
+        let nnn = opcode & 0x0FFF;
+
+        let instruction = match opcode >> 12 {
+            0x0 => match opcode {
+                0x00E0 => ClearScreen,
+                0x00EE => Return,
+                0x00C0..=0x00CF => ScrollDown { n },
+                0x00FB => ScrollRight,
+                0x00FC => ScrollLeft,
+                0x00FD => Exit,
+                0x00FE => LowRes,
+                0x00FF => HighRes,
+                _ => return None,
+            },
+            0x1 => Jump { nnn },
+            0x2 => Call { nnn },
+            0x3 => SkipEqImm { x, kk },
+            0x4 => SkipNeImm { x, kk },
+            0x5 => match n {
+                0x0 => SkipEqReg { x, y },
+                0x2 => SaveRange { x, y },
+                0x3 => LoadRange { x, y },
+                _ => return None,
+            },
+            0x6 => LoadImm { x, kk },
+            0x7 => AddImm { x, kk },
+            0x8 => match n {
+                0x0 => Move { x, y },
+                0x1 => Or { x, y },
+                0x2 => And { x, y },
+                0x3 => Xor { x, y },
+                0x4 => AddReg { x, y },
+                0x5 => Sub { x, y },
+                0x6 => ShiftRight { x, y },
+                0x7 => SubN { x, y },
+                0xE => ShiftLeft { x, y },
+                _ => return None,
+            },
+            0x9 if n == 0 => SkipNeReg { x, y },
+            0x9 => return None,
+            0xA => LoadI { nnn },
+            0xB => JumpOffset { nnn },
+            0xC => Random { x, kk },
+            0xD => Draw { x, y, n },
+            0xE => match kk {
+                0x9E => SkipKey { x },
+                0xA1 => SkipNotKey { x },
+                _ => return None,
+            },
+            _ => match kk {
+                0x00 if opcode == 0xF000 => LoadILong,
+                0x01 => SelectPlanes { n: x },
+                0x02 if opcode == 0xF002 => LoadAudio,
+                0x07 => GetDelay { x },
+                0x0A => WaitKey { x },
+                0x15 => SetDelay { x },
+                0x18 => SetSound { x },
+                0x1E => AddI { x },
+                0x29 => LoadFont { x },
+                0x30 => LoadBigFont { x },
+                0x33 => StoreBcd { x },
+                0x3A => SetPitch { x },
+                0x55 => SaveRegisters { x },
+                0x65 => LoadRegisters { x },
+                0x75 => SaveFlags { x },
+                0x85 => LoadFlags { x },
+                _ => return None,
+            },
+        };
+
+        Some(instruction)
+    }
+
+    /// The opcode of the instruction. Operands are truncated to the width of their field.
+    pub fn encode(&self) -> u16 {
+        use Instruction::*;
+
+        let xy = |x: u8, y: u8| (u16::from(x & 0xF) << 8) | (u16::from(y & 0xF) << 4);
+        let xkk = |x: u8, kk: u8| xy(x, 0) | u16::from(kk);
+
+        match *self {
+            ClearScreen => 0x00E0,
+            Return => 0x00EE,
+            ScrollDown { n } => 0x00C0 | u16::from(n & 0xF),
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            LowRes => 0x00FE,
+            HighRes => 0x00FF,
+            Jump { nnn } => 0x1000 | (nnn & 0x0FFF),
+            Call { nnn } => 0x2000 | (nnn & 0x0FFF),
+            SkipEqImm { x, kk } => 0x3000 | xkk(x, kk),
+            SkipNeImm { x, kk } => 0x4000 | xkk(x, kk),
+            SkipEqReg { x, y } => 0x5000 | xy(x, y),
+            SaveRange { x, y } => 0x5002 | xy(x, y),
+            LoadRange { x, y } => 0x5003 | xy(x, y),
+            LoadImm { x, kk } => 0x6000 | xkk(x, kk),
+            AddImm { x, kk } => 0x7000 | xkk(x, kk),
+            Move { x, y } => 0x8000 | xy(x, y),
+            Or { x, y } => 0x8001 | xy(x, y),
+            And { x, y } => 0x8002 | xy(x, y),
+            Xor { x, y } => 0x8003 | xy(x, y),
+            AddReg { x, y } => 0x8004 | xy(x, y),
+            Sub { x, y } => 0x8005 | xy(x, y),
+            ShiftRight { x, y } => 0x8006 | xy(x, y),
+            SubN { x, y } => 0x8007 | xy(x, y),
+            ShiftLeft { x, y } => 0x800E | xy(x, y),
+            SkipNeReg { x, y } => 0x9000 | xy(x, y),
+            LoadI { nnn } => 0xA000 | (nnn & 0x0FFF),
+            JumpOffset { nnn } => 0xB000 | (nnn & 0x0FFF),
+            Random { x, kk } => 0xC000 | xkk(x, kk),
+            Draw { x, y, n } => 0xD000 | xy(x, y) | u16::from(n & 0xF),
+            SkipKey { x } => 0xE09E | xy(x, 0),
+            SkipNotKey { x } => 0xE0A1 | xy(x, 0),
+            LoadILong => 0xF000,
+            SelectPlanes { n } => 0xF001 | xy(n, 0),
+            LoadAudio => 0xF002,
+            GetDelay { x } => 0xF007 | xy(x, 0),
+            WaitKey { x } => 0xF00A | xy(x, 0),
+            SetDelay { x } => 0xF015 | xy(x, 0),
+            SetSound { x } => 0xF018 | xy(x, 0),
+            AddI { x } => 0xF01E | xy(x, 0),
+            LoadFont { x } => 0xF029 | xy(x, 0),
+            LoadBigFont { x } => 0xF030 | xy(x, 0),
+            StoreBcd { x } => 0xF033 | xy(x, 0),
+            SetPitch { x } => 0xF03A | xy(x, 0),
+            SaveRegisters { x } => 0xF055 | xy(x, 0),
+            LoadRegisters { x } => 0xF065 | xy(x, 0),
+            SaveFlags { x } => 0xF075 | xy(x, 0),
+            LoadFlags { x } => 0xF085 | xy(x, 0),
+        }
+    }
+
+    /// Whether `platform` can execute the instruction. `None` is the original CHIP-8
+    /// instruction set without extensions.
+    pub fn is_supported_by(&self, platform: Option<Platform>) -> bool {
+        use Instruction::*;
+
+        match self {
+            ScrollDown { .. } | ScrollRight | ScrollLeft | Exit | LowRes | HighRes
+            | LoadBigFont { .. } | SaveFlags { .. } | LoadFlags { .. } => {
+                platform.is_some_and(|platform| platform.supports_schip())
+            },
+            SaveRange { .. } | LoadRange { .. } | LoadILong | SelectPlanes { .. } | LoadAudio | SetPitch { .. } => {
+                platform.is_some_and(|platform| platform.supports_xochip())
+            },
+            _ => true,
+        }
+    }
+
+    /// Bytes the instruction takes up in memory: 4 for `LoadILong`, which is followed by its
+    /// address, 2 for everything else
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::LoadILong => 4,
+            _ => 2,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_extracts_operands() {
+        assert_eq!(Instruction::decode(0xD12F), Some(Instruction::Draw { x: 1, y: 2, n: 0xF }));
+        assert_eq!(Instruction::decode(0x7A05), Some(Instruction::AddImm { x: 0xA, kk: 0x05 }));
+        assert_eq!(Instruction::decode(0x2ABC), Some(Instruction::Call { nnn: 0xABC }));
+        assert_eq!(Instruction::decode(0xF301), Some(Instruction::SelectPlanes { n: 3 }));
+
+        for opcode in [0x0000, 0x00E1, 0x5121, 0x8008, 0x9001, 0xE000, 0xF100, 0xF102, 0xF0FF] {
+            assert_eq!(Instruction::decode(opcode), None, "0x{:04X}", opcode);
+        }
+    }
+
+    #[test]
+    fn test_encode_round_trips_every_opcode() {
+        for opcode in 0..=0xFFFF {
+            if let Some(instruction) = Instruction::decode(opcode) {
+                assert_eq!(instruction.encode(), opcode, "{:?}", instruction);
+            }
+        }
+    }
+
+    #[test]
+    fn test_platform_support() {
+        let scroll = Instruction::ScrollDown { n: 1 };
+        let plane = Instruction::SelectPlanes { n: 1 };
+        let draw = Instruction::Draw { x: 0, y: 0, n: 0 };
+
+        assert!(draw.is_supported_by(None));
+        assert!(!scroll.is_supported_by(None));
+        assert!(!scroll.is_supported_by(Some(Platform::Chip48)));
+        assert!(scroll.is_supported_by(Some(Platform::SuperChip)));
+        assert!(!plane.is_supported_by(Some(Platform::SuperChip)));
+        assert!(plane.is_supported_by(Some(Platform::XoChip)));
+        assert_eq!(Instruction::LoadILong.size(), 4);
+        assert_eq!(draw.size(), 2);
+    }
+}
@@ -1,3 +1,18 @@
+pub mod assembler;
+pub mod audio;
 pub mod chip8;
-mod constants;
+pub mod constants;
+pub mod debugger;
+pub mod disassembler;
+pub mod error;
+pub mod image;
+pub mod instruction;
+pub mod movie;
+pub mod platform;
+pub mod quirks;
+pub mod recorder;
+pub mod rewind;
+pub mod rng;
+pub mod state;
+pub mod tracer;
 mod utils;
@@ -0,0 +1,330 @@
+use super::chip8::Chip8;
+use super::error::Chip8Error;
+use super::platform::Platform;
+use super::quirks::Quirks;
+use super::state::{StateReader, StateWriter};
+
+/// Bytes every movie file starts with
+pub const MOVIE_MAGIC: &[u8; 4] = b"C8MV";
+
+/// Version of the movie layout written by `Movie::to_bytes`
+pub const MOVIE_VERSION: u16 = 2;
+
+/// Everything besides the input that a run depends on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MovieHeader {
+    pub rom_hash: u64,
+    pub seed: u64,
+    pub platform: Option<Platform>,
+    pub quirks: Quirks,
+    pub instructions_per_frame: u32,
+}
+
+impl MovieHeader {
+    /// Describes a freshly loaded machine about to be run at `instructions_per_frame`
+    pub fn from_chip8(c8: &Chip8, instructions_per_frame: u32) -> Self {
+        Self {
+            rom_hash: c8.get_rom_hash(),
+            seed: c8.get_rng_seed(),
+            platform: c8.get_platform(),
+            quirks: c8.get_quirks(),
+            instructions_per_frame,
+        }
+    }
+}
+
+/// The keypad state of every frame of a run, starting from a freshly loaded ROM.
+///
+/// Playing the frames back on a machine set up by `Movie::create_machine` reproduces the run
+/// exactly, which `Movie::verify` checks against the hash of the final recorded state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Movie {
+    header: MovieHeader,
+    frames: Vec<u16>,              // bit n is set while key n is held
+    final_state_hash: Option<u64>,
+}
+
+impl Movie {
+    pub fn new(header: MovieHeader) -> Self {
+        Self {
+            header,
+            frames: Vec::new(),
+            final_state_hash: None,
+        }
+    }
+
+    pub fn get_header(&self) -> &MovieHeader {
+        &self.header
+    }
+
+    /// Number of recorded frames
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn get_final_state_hash(&self) -> Option<u64> {
+        self.final_state_hash
+    }
+
+    /// Appends the keypad state used for the next frame. Call before each `Chip8::run_frame`.
+    pub fn record_frame(&mut self, keypad: &[u8; 16]) {
+        let keys = keypad.iter().enumerate()
+            .filter(|(_, pressed)| **pressed != 0)
+            .fold(0u16, |keys, (key, _)| keys | (1 << key));
+
+        self.frames.push(keys);
+    }
+
+    /// Ends the recording, remembering the final state of the machine for `verify`
+    pub fn finish(&mut self, c8: &Chip8) {
+        self.final_state_hash = Some(c8.get_state_hash());
+    }
+
+    /// Returns the keypad state of `frame`, or `None` past the end of the movie
+    pub fn get_frame_keypad(&self, frame: usize) -> Option<[u8; 16]> {
+        let keys = self.frames.get(frame)?;
+
+        let mut keypad = [0; 16];
+        for (key, pressed) in keypad.iter_mut().enumerate() {
+            *pressed = u8::from(keys & (1 << key) != 0);
+        }
+        Some(keypad)
+    }
+
+    /// Creates a machine set up like the recorded one, with the ROM at `rom_path` loaded
+    pub fn create_machine(&self, rom_path: &str) -> Result<Chip8, Chip8Error> {
+        let mut c8 = self.empty_machine()?;
+        c8.load_rom_file(rom_path)?;
+        self.apply_header(&mut c8)?;
+
+        Ok(c8)
+    }
+
+    /// Creates a machine set up like the recorded one, with a binary ROM image loaded
+    pub fn create_machine_from_rom_bytes(&self, rom: &[u8]) -> Result<Chip8, Chip8Error> {
+        let mut c8 = self.empty_machine()?;
+        c8.load_rom_bytes(rom)?;
+        self.apply_header(&mut c8)?;
+
+        Ok(c8)
+    }
+
+    fn empty_machine(&self) -> Result<Chip8, Chip8Error> {
+        match self.header.platform {
+            Some(platform) => Ok(Chip8::with_platform(platform)),
+            None => Chip8::from_rom_bytes(&[]),
+        }
+    }
+
+    fn apply_header(&self, c8: &mut Chip8) -> Result<(), Chip8Error> {
+        if c8.get_rom_hash() != self.header.rom_hash {
+            return Err(Chip8Error::InvalidMovie {
+                reason: format!("recorded with ROM {:016X}, but ROM {:016X} is loaded", self.header.rom_hash, c8.get_rom_hash()),
+            });
+        }
+
+        c8.set_quirks(self.header.quirks);
+        c8.seed_rng(self.header.seed);
+
+        Ok(())
+    }
+
+    /// Runs every frame of the movie on a machine from `create_machine`
+    pub fn play(&self, c8: &mut Chip8) -> Result<(), Chip8Error> {
+        for frame in 0..self.frames.len() {
+            if let Some(keypad) = self.get_frame_keypad(frame) {
+                *c8.get_keypad() = keypad;
+            }
+            c8.run_frame(self.header.instructions_per_frame)?;
+        }
+
+        Ok(())
+    }
+
+    /// Plays the movie and checks that the machine ends up in the recorded final state
+    pub fn verify(&self, c8: &mut Chip8) -> Result<(), Chip8Error> {
+        let expected_hash = match self.final_state_hash {
+            Some(hash) => hash,
+            None => return Err(Chip8Error::InvalidMovie { reason: String::from("no final state was recorded") }),
+        };
+
+        self.play(c8)?;
+
+        let found_hash = c8.get_state_hash();
+        if found_hash != expected_hash {
+            return Err(Chip8Error::MovieDesync { expected_hash, found_hash });
+        }
+
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = StateWriter::default();
+        writer.write_bytes(MOVIE_MAGIC);
+        writer.write_u16(MOVIE_VERSION);
+        writer.write_u64(self.header.rom_hash);
+        writer.write_u64(self.header.seed);
+        writer.write_platform(self.header.platform);
+        writer.write_quirks(&self.header.quirks);
+        writer.write_u32(self.header.instructions_per_frame);
+
+        writer.write_u32(self.frames.len() as u32);
+        for keys in self.frames.iter() {
+            writer.write_u16(*keys);
+        }
+
+        writer.write_bool(self.final_state_hash.is_some());
+        writer.write_u64(self.final_state_hash.unwrap_or(0));
+
+        writer.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Chip8Error> {
+        Self::read(&mut StateReader::new(bytes)).map_err(|e| match e {
+            Chip8Error::InvalidState { reason } => Chip8Error::InvalidMovie { reason },
+            e => e,
+        })
+    }
+
+    fn read(reader: &mut StateReader) -> Result<Self, Chip8Error> {
+        if reader.read_bytes(MOVIE_MAGIC.len())? != MOVIE_MAGIC {
+            return Err(Chip8Error::InvalidMovie { reason: String::from("not a CHIP-8 movie") });
+        }
+        let version = reader.read_u16()?;
+        if version != MOVIE_VERSION {
+            return Err(Chip8Error::InvalidMovie {
+                reason: format!("unsupported version {}, expected {}", version, MOVIE_VERSION),
+            });
+        }
+
+        let header = MovieHeader {
+            rom_hash: reader.read_u64()?,
+            seed: reader.read_u64()?,
+            platform: reader.read_platform()?,
+            quirks: reader.read_quirks()?,
+            instructions_per_frame: reader.read_u32()?,
+        };
+
+        let frame_count = reader.read_u32()?;
+        let frames = (0..frame_count).map(|_| reader.read_u16()).collect::<Result<_, _>>()?;
+
+        let has_final_state = reader.read_bool()?;
+        let final_state_hash = reader.read_u64()?;
+
+        if !reader.is_empty() {
+            return Err(Chip8Error::InvalidMovie { reason: String::from("unexpected data after the last frame") });
+        }
+
+        Ok(Self {
+            header,
+            frames,
+            final_state_hash: if has_final_state { Some(final_state_hash) } else { None },
+        })
+    }
+
+    pub fn save_file(&self, file_path: &str) -> Result<(), Chip8Error> {
+        std::fs::write(file_path, self.to_bytes()).map_err(|e| Chip8Error::Io {
+            path: file_path.to_string(),
+            reason: e.to_string(),
+        })
+    }
+
+    pub fn load_file(file_path: &str) -> Result<Self, Chip8Error> {
+        let bytes = std::fs::read(file_path).map_err(|e| Chip8Error::Io {
+            path: file_path.to_string(),
+            reason: e.to_string(),
+        })?;
+
+        Self::from_bytes(&bytes)
+    }
+
+    /// Loads the movie at `movie_path` and verifies it against the ROM at `rom_path`
+    pub fn verify_file(movie_path: &str, rom_path: &str) -> Result<Self, Chip8Error> {
+        let movie = Self::load_file(movie_path)?;
+        let mut c8 = movie.create_machine(rom_path)?;
+        movie.verify(&mut c8)?;
+
+        Ok(movie)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0x200: V3 = 0x0F; 0x202: V0 = random byte; 0x204: skip if key V1 is not pressed;
+    // 0x206: V2 += 1; 0x208: V1 += 1; 0x20A: V1 &= V3; 0x20C: jump to 0x202
+    const ROM: [u8; 14] = [0x63, 0x0F, 0xC0, 0xFF, 0xE1, 0xA1, 0x72, 0x01, 0x71, 0x01, 0x81, 0x32, 0x12, 0x02];
+
+    fn record(seed: u64) -> (Movie, Chip8) {
+        let mut c8 = Chip8::with_platform(Platform::Chip48);
+        c8.load_rom_bytes(&ROM).unwrap();
+        c8.seed_rng(seed);
+
+        let mut movie = Movie::new(MovieHeader::from_chip8(&c8, 6));
+        for frame in 0..20 {
+            c8.get_keypad()[frame % 16] = 1;
+            c8.get_keypad()[(frame + 15) % 16] = 0;
+            movie.record_frame(&c8.get_keypad().clone());
+            c8.run_frame(6).unwrap();
+        }
+        movie.finish(&c8);
+
+        (movie, c8)
+    }
+
+    #[test]
+    fn test_keypad_round_trip() {
+        let mut movie = Movie::new(MovieHeader::from_chip8(&Chip8::from_rom_bytes(&[]).unwrap(), 10));
+        let mut keypad = [0; 16];
+        keypad[0x0] = 1;
+        keypad[0xA] = 1;
+        movie.record_frame(&keypad);
+
+        assert_eq!(movie.get_frame_keypad(0), Some(keypad));
+        assert_eq!(movie.get_frame_keypad(1), None);
+    }
+
+    #[test]
+    fn test_file_format_round_trip() {
+        let (movie, _) = record(7);
+
+        let bytes = movie.to_bytes();
+
+        assert_eq!(Movie::from_bytes(&bytes), Ok(movie));
+        assert!(matches!(Movie::from_bytes(&bytes[..bytes.len() - 1]), Err(Chip8Error::InvalidMovie { .. })));
+        assert!(matches!(Movie::from_bytes(b"C8ST"), Err(Chip8Error::InvalidMovie { .. })));
+    }
+
+    #[test]
+    fn test_playback_is_deterministic() {
+        let (movie, recorded) = record(7);
+        let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+
+        let mut c8 = movie.create_machine_from_rom_bytes(&ROM).unwrap();
+
+        assert_eq!(movie.verify(&mut c8), Ok(()));
+        assert_eq!(c8.get_registers(), recorded.get_registers());
+    }
+
+    #[test]
+    fn test_desync_is_detected() {
+        let (mut movie, _) = record(7);
+        movie.frames[10] = 0;
+
+        let mut c8 = movie.create_machine_from_rom_bytes(&ROM).unwrap();
+
+        assert!(matches!(movie.verify(&mut c8), Err(Chip8Error::MovieDesync { .. })));
+    }
+
+    #[test]
+    fn test_rom_mismatch() {
+        let (movie, _) = record(7);
+
+        assert!(matches!(movie.create_machine_from_rom_bytes(&[0x12, 0x00]), Err(Chip8Error::InvalidMovie { .. })));
+    }
+}
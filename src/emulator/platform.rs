@@ -0,0 +1,107 @@
+use super::constants::{
+    COSMAC_VIP_FONT_SET,
+    FONT_SET,
+    HIRES_HEIGHT,
+    HIRES_WIDTH,
+    LORES_HEIGHT,
+    LORES_WIDTH,
+};
+use super::quirks::Quirks;
+
+/// A CHIP-8 implementation whose behavior a ROM may have been written for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    /// The original interpreter on the RCA COSMAC VIP
+    CosmacVip,
+    /// CHIP-48 on the HP-48 calculators
+    Chip48,
+    /// SUPER-CHIP 1.1
+    SuperChip,
+    /// Octo's XO-CHIP extension of SUPER-CHIP
+    XoChip,
+}
+
+impl Platform {
+    /// Names accepted by `Platform::from_name`
+    pub const NAMES: [&'static str; 4] = ["vip", "chip48", "schip", "xochip"];
+
+    /// Looks up a platform by name, see `Platform::NAMES`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "vip" | "cosmac-vip" => Some(Platform::CosmacVip),
+            "chip48" | "chip-48" => Some(Platform::Chip48),
+            "schip" | "superchip" => Some(Platform::SuperChip),
+            "xochip" | "xo-chip" => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        match self {
+            Platform::CosmacVip => Quirks::cosmac_vip(),
+            Platform::Chip48 => Quirks::chip48(),
+            Platform::SuperChip => Quirks::schip(),
+            Platform::XoChip => Quirks {
+                shift_vx: false,
+                load_store_increment_i: true,
+                load_store_increment_by_x: false,
+                jump_with_vx: false,
+                vf_reset: false,
+                clip_sprites: false,
+                display_wait: false,
+            },
+        }
+    }
+
+    /// Size of the addressable memory in bytes
+    pub fn memory_size(&self) -> usize {
+        match self {
+            Platform::XoChip => 0x10000,
+            _ => 0x1000,
+        }
+    }
+
+    /// Highest display resolution as `(width, height)`. ROMs start in 64x32 and switch with `00FF`.
+    pub fn resolution(&self) -> (usize, usize) {
+        if self.supports_schip() {
+            (HIRES_WIDTH, HIRES_HEIGHT)
+        } else {
+            (LORES_WIDTH, LORES_HEIGHT)
+        }
+    }
+
+    /// Whether the SUPER-CHIP instructions (scrolling, hi-res mode, 16x16 sprites, ...) are available
+    pub fn supports_schip(&self) -> bool {
+        matches!(self, Platform::SuperChip | Platform::XoChip)
+    }
+
+    /// Whether the XO-CHIP instructions (bitplanes, audio patterns, long `I` loads, ...) are available
+    pub fn supports_xochip(&self) -> bool {
+        matches!(self, Platform::XoChip)
+    }
+
+    /// The 4x5 hex digit sprites loaded at `FONT_SET_START_ADDRESS`
+    pub fn font_set(&self) -> &'static [u8; 80] {
+        match self {
+            Platform::CosmacVip => &COSMAC_VIP_FONT_SET,
+            _ => &FONT_SET,
+        }
+    }
+
+    /// Number of nested subroutine calls the stack can hold
+    pub fn stack_depth(&self) -> usize {
+        match self {
+            Platform::CosmacVip => 12,
+            _ => 16,
+        }
+    }
+
+    /// Number of instructions executed per 60 Hz frame on the original hardware
+    pub fn instructions_per_frame(&self) -> u32 {
+        match self {
+            Platform::CosmacVip => 15,
+            Platform::Chip48 | Platform::SuperChip => 30,
+            Platform::XoChip => 1000,
+        }
+    }
+}
@@ -0,0 +1,90 @@
+/// Behaviors of ambiguous instructions that differ between CHIP-8 interpreters.
+///
+/// The `Default` quirks reproduce this emulator's original behavior.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8xy6`/`8xyE` shift `Vx` in place instead of storing the shifted `Vy` in `Vx`
+    pub shift_vx: bool,
+    /// `Fx55`/`Fx65` leave `index_register` pointing past the last register accessed
+    pub load_store_increment_i: bool,
+    /// With `load_store_increment_i`, `index_register` is advanced by `x` instead of `x + 1`, leaving it
+    /// on the last register accessed like CHIP-48 did
+    pub load_store_increment_by_x: bool,
+    /// `Bnnn` jumps to `nnn + Vx` (with `x` being the highest nibble of `nnn`) instead of `nnn + V0`
+    pub jump_with_vx: bool,
+    /// `8xy1`/`8xy2`/`8xy3` reset `VF` to 0
+    pub vf_reset: bool,
+    /// Sprites drawn past the edge of the screen are clipped instead of wrapping around
+    pub clip_sprites: bool,
+    /// `Dxyn` waits for the next vertical blank, so at most one sprite is drawn per frame
+    pub display_wait: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            shift_vx: false,
+            load_store_increment_i: true,
+            load_store_increment_by_x: false,
+            jump_with_vx: false,
+            vf_reset: false,
+            clip_sprites: false,
+            display_wait: false,
+        }
+    }
+}
+
+impl Quirks {
+    /// Names accepted by `Quirks::from_name`
+    pub const NAMES: [&'static str; 4] = ["default", "vip", "chip48", "schip"];
+
+    /// The original COSMAC VIP interpreter
+    pub fn cosmac_vip() -> Self {
+        Self {
+            shift_vx: false,
+            load_store_increment_i: true,
+            load_store_increment_by_x: false,
+            jump_with_vx: false,
+            vf_reset: true,
+            clip_sprites: true,
+            display_wait: true,
+        }
+    }
+
+    /// CHIP-48 on the HP-48 calculators
+    pub fn chip48() -> Self {
+        Self {
+            shift_vx: true,
+            load_store_increment_i: true,
+            load_store_increment_by_x: true,
+            jump_with_vx: true,
+            vf_reset: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1
+    pub fn schip() -> Self {
+        Self {
+            shift_vx: true,
+            load_store_increment_i: false,
+            load_store_increment_by_x: false,
+            jump_with_vx: true,
+            vf_reset: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+
+    /// Looks up a quirk profile by name, see `Quirks::NAMES`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "default" => Some(Self::default()),
+            "vip" | "cosmac-vip" => Some(Self::cosmac_vip()),
+            "chip48" | "chip-48" => Some(Self::chip48()),
+            "schip" | "superchip" => Some(Self::schip()),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,467 @@
+use std::collections::HashMap;
+
+use super::error::Chip8Error;
+use super::image::{encode_png, Palette};
+
+/// Collects the frames shown by the emulator into a video file
+pub trait FrameRecorder {
+    /// Adds one 60 Hz frame. `display_colors` holds the color index of each pixel,
+    /// see `Chip8::get_display_colors`.
+    fn add_frame(&mut self, display_colors: &[Vec<u8>]) -> Result<(), Chip8Error>;
+
+    /// Writes out any pending data. No frames may be added afterwards.
+    fn finish(&mut self) -> Result<(), Chip8Error>;
+
+    /// Number of frames added so far
+    fn frame_count(&self) -> u32;
+}
+
+/// Records an animated GIF that loops forever.
+///
+/// The image size is fixed by the first frame; later frames at another resolution are
+/// stretched to it. Runs of identical frames are merged into one longer GIF frame.
+#[derive(Debug)]
+pub struct GifRecorder {
+    file_path: String,
+    palette: Palette,
+    scale: usize,
+    size: Option<(u16, u16)>,       // scaled (width, height), set by the first frame
+    bytes: Vec<u8>,
+    pending: Option<Vec<u8>>,       // last frame as color indices, not yet written
+    pending_start: u32,             // 60 Hz tick the pending frame was first shown at
+    frame_count: u32,
+}
+
+impl GifRecorder {
+    pub fn new(file_path: &str, palette: Palette, scale: usize) -> Self {
+        Self {
+            file_path: file_path.to_string(),
+            palette,
+            scale: scale.max(1),
+            size: None,
+            bytes: Vec::new(),
+            pending: None,
+            pending_start: 0,
+            frame_count: 0,
+        }
+    }
+
+    /// Returns the GIF written so far, without the frame still waiting for its duration
+    pub fn get_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn write_header(&mut self, width: u16, height: u16) {
+        self.bytes.extend_from_slice(b"GIF89a");
+        self.bytes.extend_from_slice(&width.to_le_bytes());
+        self.bytes.extend_from_slice(&height.to_le_bytes());
+        // global color table of 4 entries, one for each color index
+        self.bytes.extend_from_slice(&[0x91, 0, 0]);
+        for color_index in 0..4 {
+            self.bytes.extend_from_slice(&self.palette.color(color_index).to_be_bytes()[1..]);
+        }
+
+        // NETSCAPE2.0 extension: loop forever
+        self.bytes.extend_from_slice(&[0x21, 0xFF, 0x0B]);
+        self.bytes.extend_from_slice(b"NETSCAPE2.0");
+        self.bytes.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+    }
+
+    /// Writes the pending frame, shown from its start tick until `end_tick`. Frames shown longer
+    /// than the largest GIF delay are repeated.
+    fn flush_pending(&mut self, end_tick: u32) {
+        let pixels = match self.pending.take() {
+            Some(pixels) => pixels,
+            None => return,
+        };
+        let (width, height) = self.size.unwrap_or((0, 0));
+        let data = lzw_encode(&pixels, GIF_MIN_CODE_SIZE);
+
+        // GIF delays are in hundredths of a second; rounding the start and end times rather
+        // than the duration keeps the total in step with 60 Hz
+        let to_centiseconds = |tick: u32| (u64::from(tick) * 100 + 30) / 60;
+        let mut remaining = to_centiseconds(end_tick) - to_centiseconds(self.pending_start);
+
+        loop {
+            let delay = remaining.min(u64::from(u16::MAX)) as u16;
+            remaining -= u64::from(delay);
+
+            // graphic control extension
+            self.bytes.extend_from_slice(&[0x21, 0xF9, 0x04, 0x00]);
+            self.bytes.extend_from_slice(&delay.to_le_bytes());
+            self.bytes.extend_from_slice(&[0x00, 0x00]);
+
+            // image descriptor covering the whole screen, without a local color table
+            self.bytes.push(0x2C);
+            self.bytes.extend_from_slice(&[0, 0, 0, 0]);
+            self.bytes.extend_from_slice(&width.to_le_bytes());
+            self.bytes.extend_from_slice(&height.to_le_bytes());
+            self.bytes.push(0x00);
+
+            self.bytes.push(GIF_MIN_CODE_SIZE);
+            for block in data.chunks(255) {
+                self.bytes.push(block.len() as u8);
+                self.bytes.extend_from_slice(block);
+            }
+            self.bytes.push(0x00);
+
+            if remaining == 0 {
+                break;
+            }
+        }
+    }
+}
+
+impl FrameRecorder for GifRecorder {
+    fn add_frame(&mut self, display_colors: &[Vec<u8>]) -> Result<(), Chip8Error> {
+        let (width, height) = match self.size {
+            Some(size) => size,
+            None => {
+                let (width, height) = (display_colors[0].len() * self.scale, display_colors.len() * self.scale);
+                let size = match (u16::try_from(width), u16::try_from(height)) {
+                    (Ok(width), Ok(height)) => (width, height),
+                    _ => return Err(Chip8Error::Io {
+                        path: self.file_path.clone(),
+                        reason: format!("{}x{} is larger than a GIF can be", width, height),
+                    }),
+                };
+                self.write_header(size.0, size.1);
+                self.size = Some(size);
+                size
+            },
+        };
+
+        let pixels = stretch(display_colors, usize::from(width), usize::from(height));
+        if self.pending.as_ref() != Some(&pixels) {
+            self.flush_pending(self.frame_count);
+            self.pending = Some(pixels);
+            self.pending_start = self.frame_count;
+        }
+        self.frame_count += 1;
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Chip8Error> {
+        self.flush_pending(self.frame_count);
+        self.bytes.push(0x3B);
+
+        std::fs::write(&self.file_path, &self.bytes).map_err(|e| Chip8Error::Io {
+            path: self.file_path.clone(),
+            reason: e.to_string(),
+        })
+    }
+
+    fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+}
+
+/// Writes every frame as a numbered PNG file, `frame00000.png` onwards, into a directory.
+/// Frame `n` is shown at `n / 60` seconds.
+#[derive(Debug)]
+pub struct PngSequenceRecorder {
+    directory: String,
+    palette: Palette,
+    scale: usize,
+    frame_count: u32,
+}
+
+impl PngSequenceRecorder {
+    /// Creates the recorder, and `directory` if it does not exist yet
+    pub fn new(directory: &str, palette: Palette, scale: usize) -> Result<Self, Chip8Error> {
+        std::fs::create_dir_all(directory).map_err(|e| Chip8Error::Io {
+            path: directory.to_string(),
+            reason: e.to_string(),
+        })?;
+
+        Ok(Self {
+            directory: directory.to_string(),
+            palette,
+            scale,
+            frame_count: 0,
+        })
+    }
+}
+
+impl FrameRecorder for PngSequenceRecorder {
+    fn add_frame(&mut self, display_colors: &[Vec<u8>]) -> Result<(), Chip8Error> {
+        let path = format!("{}/frame{:05}.png", self.directory.trim_end_matches('/'), self.frame_count);
+        std::fs::write(&path, encode_png(display_colors, self.scale, &self.palette)).map_err(|e| Chip8Error::Io {
+            path,
+            reason: e.to_string(),
+        })?;
+        self.frame_count += 1;
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Chip8Error> {
+        Ok(())
+    }
+
+    fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+}
+
+/// Smallest LZW code size allowed by GIF, enough for the 4 color indices
+const GIF_MIN_CODE_SIZE: u8 = 2;
+
+/// Resizes the display to `width`x`height` color indices, row by row, by nearest neighbour
+fn stretch(display_colors: &[Vec<u8>], width: usize, height: usize) -> Vec<u8> {
+    let (source_width, source_height) = (display_colors[0].len(), display_colors.len());
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let row = &display_colors[y * source_height / height];
+        for x in 0..width {
+            pixels.push(row[x * source_width / width] & 0b11);
+        }
+    }
+    pixels
+}
+
+/// Compresses color indices with the variable-length LZW variant used by GIF
+fn lzw_encode(pixels: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear_code = 1u16 << min_code_size;
+    let end_code = clear_code + 1;
+
+    let mut writer = BitWriter::default();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = min_code_size + 1;
+    let mut next_code = end_code + 1;
+    let mut codes_since_clear = 0;
+
+    writer.write(clear_code, code_size);
+
+    let mut pixels = pixels.iter();
+    let mut prefix = match pixels.next() {
+        Some(pixel) => u16::from(*pixel),
+        None => {
+            writer.write(end_code, code_size);
+            return writer.into_bytes();
+        },
+    };
+
+    for pixel in pixels {
+        if let Some(code) = table.get(&(prefix, *pixel)) {
+            prefix = *code;
+            continue;
+        }
+
+        writer.write(prefix, code_size);
+        codes_since_clear += 1;
+
+        if next_code < 4096 {
+            table.insert((prefix, *pixel), next_code);
+            next_code += 1;
+            // the decoder adds each entry one code later, but grows its codes at the same point
+            if next_code > (1 << code_size) && code_size < 12 {
+                code_size += 1;
+            }
+        } else {
+            writer.write(clear_code, code_size);
+            table.clear();
+            code_size = min_code_size + 1;
+            next_code = end_code + 1;
+            codes_since_clear = 0;
+        }
+
+        prefix = u16::from(*pixel);
+    }
+
+    writer.write(prefix, code_size);
+    // the decoder adds one more entry after reading the last code, which may widen the end code
+    if codes_since_clear > 0 && next_code < 4096 && next_code + 1 > (1 << code_size) && code_size < 12 {
+        code_size += 1;
+    }
+    writer.write(end_code, code_size);
+
+    writer.into_bytes()
+}
+
+/// Packs codes least significant bit first, as GIF expects
+#[derive(Debug, Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bit_count: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= u32::from(code) << self.bit_count;
+        self.bit_count += size;
+
+        while self.bit_count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bit_count -= 8;
+        }
+    }
+
+    fn into_bytes(mut self) -> Vec<u8> {
+        if self.bit_count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::rng::Rng;
+
+    /// Reference GIF LZW decoder
+    fn lzw_decode(data: &[u8], min_code_size: u8) -> Vec<u8> {
+        let clear_code = 1usize << min_code_size;
+        let end_code = clear_code + 1;
+
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut code_size = min_code_size + 1;
+        let mut previous: Option<usize> = None;
+        let mut output = Vec::new();
+
+        let mut bit_position = 0;
+        let mut read = |size: u8| -> usize {
+            let mut code = 0;
+            for i in 0..usize::from(size) {
+                let bit = (data[(bit_position + i) / 8] >> ((bit_position + i) % 8)) & 1;
+                code |= usize::from(bit) << i;
+            }
+            bit_position += usize::from(size);
+            code
+        };
+
+        loop {
+            let code = read(code_size);
+            if code == clear_code {
+                table = (0..clear_code).map(|i| vec![i as u8]).collect();
+                table.push(Vec::new());
+                table.push(Vec::new());
+                code_size = min_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == end_code {
+                return output;
+            }
+
+            let entry = match previous {
+                None => table[code].clone(),
+                Some(previous) => {
+                    let entry = if code < table.len() {
+                        table[code].clone()
+                    } else {
+                        let mut entry = table[previous].clone();
+                        entry.push(table[previous][0]);
+                        entry
+                    };
+                    if table.len() < 4096 {
+                        let mut new_entry = table[previous].clone();
+                        new_entry.push(entry[0]);
+                        table.push(new_entry);
+                        if table.len() == (1 << code_size) && code_size < 12 {
+                            code_size += 1;
+                        }
+                    }
+                    entry
+                },
+            };
+            output.extend_from_slice(&entry);
+            previous = Some(code);
+        }
+    }
+
+    #[test]
+    fn test_lzw_round_trip() {
+        let mut inputs: Vec<Vec<u8>> = vec![
+            vec![],
+            vec![1],
+            vec![0, 1, 2, 3],
+            vec![0; 10000],
+            (0..20000u32).map(|i| ((i * 7 + i / 13) % 4) as u8).collect(),
+        ];
+        // noise fills the code table several times over, exercising the clear codes
+        let mut rng = Rng::new(1);
+        inputs.push((0..50000).map(|_| rng.next_u8() % 4).collect());
+
+
+        for pixels in inputs.iter() {
+            assert_eq!(&lzw_decode(&lzw_encode(pixels, 2), 2), pixels);
+        }
+    }
+
+    #[test]
+    fn test_stretch() {
+        let display = [vec![0, 1], vec![2, 3]];
+
+        assert_eq!(stretch(&display, 4, 2), [0, 0, 1, 1, 2, 2, 3, 3]);
+        assert_eq!(stretch(&display, 1, 1), [0]);
+    }
+
+    #[test]
+    fn test_gif_timing() {
+        let mut recorder = GifRecorder::new("unused.gif", Palette::default(), 1);
+        let blank = vec![vec![0; 4]; 2];
+        let mut lit = blank.clone();
+        lit[0][0] = 1;
+
+        // 3 frames of blank, 2 of lit, 1 of blank
+        for frame in [&blank, &blank, &blank, &lit, &lit, &blank] {
+            recorder.add_frame(frame).unwrap();
+        }
+        recorder.flush_pending(recorder.frame_count);
+
+        // graphic control extensions carry the delays: 3, 2 and 1 frames at 60 Hz
+        let delays: Vec<u16> = recorder.get_bytes().windows(4)
+            .enumerate()
+            .filter(|(_, window)| *window == [0x21, 0xF9, 0x04, 0x00])
+            .map(|(i, _)| u16::from_le_bytes([recorder.get_bytes()[i + 4], recorder.get_bytes()[i + 5]]))
+            .collect();
+        assert_eq!(delays, [5, 3, 2]);
+        assert_eq!(delays.iter().sum::<u16>(), 10);
+        assert_eq!(recorder.frame_count(), 6);
+    }
+
+    #[test]
+    fn test_gif_long_frame_is_split() {
+        let mut recorder = GifRecorder::new("unused.gif", Palette::default(), 1);
+        let blank = vec![vec![0; 1]; 1];
+
+        // 700 seconds, more than the 655.35 a single GIF frame can be shown for
+        for _ in 0..700 * 60 {
+            recorder.add_frame(&blank).unwrap();
+        }
+        recorder.flush_pending(recorder.frame_count);
+
+        let delays: Vec<u16> = recorder.get_bytes().windows(4)
+            .enumerate()
+            .filter(|(_, window)| *window == [0x21, 0xF9, 0x04, 0x00])
+            .map(|(i, _)| u16::from_le_bytes([recorder.get_bytes()[i + 4], recorder.get_bytes()[i + 5]]))
+            .collect();
+        assert_eq!(delays, [u16::MAX, 4465]);
+        assert_eq!(delays.iter().map(|&delay| u32::from(delay)).sum::<u32>(), 70_000);
+    }
+
+    #[test]
+    fn test_gif_too_large() {
+        let mut recorder = GifRecorder::new("unused.gif", Palette::default(), 512);
+
+        assert!(matches!(recorder.add_frame(&vec![vec![0; 128]; 64]), Err(Chip8Error::Io { .. })));
+    }
+
+    #[test]
+    fn test_gif_header() {
+        let mut recorder = GifRecorder::new("unused.gif", Palette::default(), 2);
+        recorder.add_frame(&vec![vec![0; 64]; 32]).unwrap();
+
+        let bytes = recorder.get_bytes();
+        assert_eq!(bytes[..6], *b"GIF89a");
+        assert_eq!(bytes[6..10], [128, 0, 64, 0]);
+        assert_eq!(bytes[13..16], [0x00, 0x00, 0x00]);
+        assert_eq!(bytes[16..19], [0xFF, 0xFF, 0xFF]);
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::VecDeque;
+
+use super::chip8::Chip8;
+
+/// Ring buffer of save states taken while emulating, used to step back in time.
+///
+/// The oldest snapshots are dropped once either `depth` snapshots are stored or they take
+/// up more than `memory_budget` bytes.
+#[derive(Debug, Clone)]
+pub struct RewindBuffer {
+    snapshots: VecDeque<Vec<u8>>,
+    depth: usize,
+    memory_budget: usize,
+    memory_used: usize,
+    interval: u32,             // frames between two snapshots
+    frames_since_snapshot: u32,
+    latest_is_current: bool,   // the newest snapshot was taken of the machine as it is now
+}
+
+impl RewindBuffer {
+    /// Creates a buffer holding up to `depth` snapshots and `memory_budget` bytes,
+    /// taking one snapshot every `interval` frames
+    pub fn new(depth: usize, memory_budget: usize, interval: u32) -> Self {
+        Self {
+            snapshots: VecDeque::new(),
+            depth,
+            memory_budget,
+            memory_used: 0,
+            interval: interval.max(1),
+            frames_since_snapshot: 0,
+            latest_is_current: false,
+        }
+    }
+
+    /// Call after every emulated frame; snapshots the machine every `interval` frames
+    pub fn record(&mut self, c8: &Chip8) {
+        self.frames_since_snapshot += 1;
+        self.latest_is_current = false;
+        if self.frames_since_snapshot < self.interval {
+            return;
+        }
+        self.frames_since_snapshot = 0;
+
+        let snapshot = c8.save_state();
+        self.memory_used += snapshot.len();
+        self.snapshots.push_back(snapshot);
+        self.latest_is_current = true;
+
+        while self.snapshots.len() > self.depth || self.memory_used > self.memory_budget {
+            match self.snapshots.pop_front() {
+                Some(oldest) => self.memory_used -= oldest.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Restores the most recent snapshot that differs from the current frame and removes it from the
+    /// buffer. The keypad keeps its current state. Returns false, leaving `c8` untouched, when there is
+    /// nothing left to rewind to.
+    pub fn rewind(&mut self, c8: &mut Chip8) -> bool {
+        // restoring the frame that was just saved would not go back at all
+        if self.latest_is_current && self.snapshots.len() > 1 {
+            if let Some(current) = self.snapshots.pop_back() {
+                self.memory_used -= current.len();
+            }
+        }
+        self.latest_is_current = false;
+
+        let snapshot = match self.snapshots.pop_back() {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+        self.memory_used -= snapshot.len();
+        self.frames_since_snapshot = 0;
+
+        // the keys are still held by the player, not part of the past being restored
+        let keypad = *c8.get_keypad();
+        // snapshots always come from the running ROM, so they cannot be rejected
+        let restored = c8.load_state(&snapshot).is_ok();
+        *c8.get_keypad() = keypad;
+
+        restored
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.memory_used = 0;
+        self.frames_since_snapshot = 0;
+        self.latest_is_current = false;
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Total size in bytes of the stored snapshots
+    pub fn get_memory_used(&self) -> usize {
+        self.memory_used
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0x200: V0 += 1; 0x202: jump to 0x200
+    const COUNTER_ROM: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+    #[test]
+    fn test_rewind_frame_by_frame() {
+        let mut c8 = Chip8::from_rom_bytes(&COUNTER_ROM).unwrap();
+        let mut rewind = RewindBuffer::new(100, usize::MAX, 1);
+
+        for _ in 0..5 {
+            c8.run_frame(2).unwrap();
+            rewind.record(&c8);
+        }
+        assert_eq!(c8.get_registers()[0], 5);
+
+        // the first step skips the frame that is already on screen
+        assert!(rewind.rewind(&mut c8));
+        assert_eq!(c8.get_registers()[0], 4);
+        assert!(rewind.rewind(&mut c8));
+        assert_eq!(c8.get_registers()[0], 3);
+        assert_eq!(rewind.len(), 2);
+    }
+
+    #[test]
+    fn test_rewind_keeps_keypad() {
+        let mut c8 = Chip8::from_rom_bytes(&COUNTER_ROM).unwrap();
+        let mut rewind = RewindBuffer::new(100, usize::MAX, 1);
+
+        for _ in 0..3 {
+            c8.run_frame(2).unwrap();
+            rewind.record(&c8);
+        }
+        c8.get_keypad()[0x5] = 1;
+
+        assert!(rewind.rewind(&mut c8));
+        assert_eq!(c8.get_registers()[0], 2);
+        assert_eq!(c8.get_keypad()[0x5], 1);
+    }
+
+    #[test]
+    fn test_empty_buffer() {
+        let mut c8 = Chip8::from_rom_bytes(&COUNTER_ROM).unwrap();
+        let mut rewind = RewindBuffer::new(100, usize::MAX, 1);
+        c8.run_frame(2).unwrap();
+
+        assert!(!rewind.rewind(&mut c8));
+        assert_eq!(c8.get_registers()[0], 1);
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let mut c8 = Chip8::from_rom_bytes(&COUNTER_ROM).unwrap();
+        let mut rewind = RewindBuffer::new(3, usize::MAX, 1);
+
+        for _ in 0..10 {
+            c8.run_frame(2).unwrap();
+            rewind.record(&c8);
+        }
+        while rewind.rewind(&mut c8) {}
+
+        // only the 3 most recent frames are kept
+        assert_eq!(c8.get_registers()[0], 8);
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let mut c8 = Chip8::from_rom_bytes(&COUNTER_ROM).unwrap();
+        let state_size = c8.save_state().len();
+        let mut rewind = RewindBuffer::new(100, state_size * 2, 1);
+
+        for _ in 0..10 {
+            c8.run_frame(2).unwrap();
+            rewind.record(&c8);
+        }
+
+        assert_eq!(rewind.len(), 2);
+        assert_eq!(rewind.get_memory_used(), state_size * 2);
+    }
+
+    #[test]
+    fn test_interval() {
+        let mut c8 = Chip8::from_rom_bytes(&COUNTER_ROM).unwrap();
+        let mut rewind = RewindBuffer::new(100, usize::MAX, 4);
+
+        for _ in 0..10 {
+            c8.run_frame(2).unwrap();
+            rewind.record(&c8);
+        }
+        assert_eq!(rewind.len(), 2);
+
+        assert!(rewind.rewind(&mut c8));
+        assert_eq!(c8.get_registers()[0], 8);
+    }
+}
@@ -0,0 +1,85 @@
+/// Pseudo-random generator behind `Cxkk`.
+///
+/// A SplitMix64 generator: its whole state is a single `u64`, so it can be stored in
+/// save states and restored to continue the exact same sequence. Two generators created
+/// with the same seed produce the same bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+    seed: u64,
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, state: seed }
+    }
+
+    /// Creates a generator with a seed taken from the operating system's entropy
+    pub fn from_entropy() -> Self {
+        Self::new(rand::random::<u64>())
+    }
+
+    /// Recreates a generator from the values returned by `Rng::get_seed` and `Rng::get_state`
+    pub fn from_state(seed: u64, state: u64) -> Self {
+        Self { seed, state }
+    }
+
+    /// Returns the seed the generator was created with, which reproduces its whole sequence
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a random byte valued in the range `[0, 255]`
+    pub fn next_u8(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_state_same_sequence() {
+        let mut rng = Rng::from_entropy();
+        rng.next_u64();
+        let mut copy = Rng::from_state(rng.get_seed(), rng.get_state());
+
+        for _ in 0..16 {
+            assert_eq!(rng.next_u8(), copy.next_u8());
+        }
+    }
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut first = Rng::new(1234);
+        let mut second = Rng::new(1234);
+
+        for _ in 0..16 {
+            assert_eq!(first.next_u8(), second.next_u8());
+        }
+        assert_eq!(first.get_seed(), 1234);
+    }
+
+    #[test]
+    fn test_known_sequence() {
+        // reference values of SplitMix64 seeded with 0
+        let mut rng = Rng::new(0);
+
+        assert_eq!(rng.next_u64(), 0xE220_A839_7B1D_CDAF);
+        assert_eq!(rng.next_u64(), 0x6E78_9E6A_A1B9_65F4);
+    }
+}
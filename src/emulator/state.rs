@@ -0,0 +1,205 @@
+use super::error::Chip8Error;
+use super::platform::Platform;
+use super::quirks::Quirks;
+
+/// Bytes every save state starts with
+pub const STATE_MAGIC: &[u8; 4] = b"C8ST";
+
+/// Version of the save state layout written by `Chip8::save_state`. Bump it whenever
+/// the layout changes; older versions are rejected on load.
+pub const STATE_VERSION: u16 = 3;
+
+/// 64-bit FNV-1a hash, used to tie save states and movies to the ROM they were made with
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+/// Appends big-endian values to a save state
+#[derive(Debug, Default)]
+pub(super) struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u8(u8::from(value));
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+    }
+
+    /// Writes a fixed number of bytes; the reader must know the length
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Writes a length-prefixed block of bytes
+    pub fn write_block(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.write_bytes(bytes);
+    }
+
+    pub fn write_platform(&mut self, platform: Option<Platform>) {
+        self.write_u8(match platform {
+            None => 0,
+            Some(Platform::CosmacVip) => 1,
+            Some(Platform::Chip48) => 2,
+            Some(Platform::SuperChip) => 3,
+            Some(Platform::XoChip) => 4,
+        });
+    }
+
+    pub fn write_quirks(&mut self, quirks: &Quirks) {
+        self.write_bool(quirks.shift_vx);
+        self.write_bool(quirks.load_store_increment_i);
+        self.write_bool(quirks.load_store_increment_by_x);
+        self.write_bool(quirks.jump_with_vx);
+        self.write_bool(quirks.vf_reset);
+        self.write_bool(quirks.clip_sprites);
+        self.write_bool(quirks.display_wait);
+    }
+}
+
+/// Reads the values appended by a `StateWriter`, in the same order
+#[derive(Debug)]
+pub(super) struct StateReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == self.bytes.len()
+    }
+
+    pub fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], Chip8Error> {
+        if self.bytes.len() - self.position < length {
+            return Err(invalid_state("unexpected end of data"));
+        }
+
+        let bytes = &self.bytes[self.position..self.position + length];
+        self.position += length;
+
+        Ok(bytes)
+    }
+
+    pub fn read_array<const N: usize>(&mut self) -> Result<[u8; N], Chip8Error> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+
+        Ok(array)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, Chip8Error> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, Chip8Error> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            value => Err(invalid_state(&format!("{} is not a boolean", value))),
+        }
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, Chip8Error> {
+        Ok(u16::from_be_bytes(self.read_array()?))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, Chip8Error> {
+        Ok(u32::from_be_bytes(self.read_array()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, Chip8Error> {
+        Ok(u64::from_be_bytes(self.read_array()?))
+    }
+
+    pub fn read_block(&mut self) -> Result<&'a [u8], Chip8Error> {
+        let length = self.read_u32()? as usize;
+        self.read_bytes(length)
+    }
+
+    pub fn read_platform(&mut self) -> Result<Option<Platform>, Chip8Error> {
+        match self.read_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(Platform::CosmacVip)),
+            2 => Ok(Some(Platform::Chip48)),
+            3 => Ok(Some(Platform::SuperChip)),
+            4 => Ok(Some(Platform::XoChip)),
+            value => Err(invalid_state(&format!("unknown platform {}", value))),
+        }
+    }
+
+    pub fn read_quirks(&mut self) -> Result<Quirks, Chip8Error> {
+        Ok(Quirks {
+            shift_vx: self.read_bool()?,
+            load_store_increment_i: self.read_bool()?,
+            load_store_increment_by_x: self.read_bool()?,
+            jump_with_vx: self.read_bool()?,
+            vf_reset: self.read_bool()?,
+            clip_sprites: self.read_bool()?,
+            display_wait: self.read_bool()?,
+        })
+    }
+}
+
+pub(super) fn invalid_state(reason: &str) -> Chip8Error {
+    Chip8Error::InvalidState { reason: reason.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut writer = StateWriter::default();
+        writer.write_u8(0xAB);
+        writer.write_bool(true);
+        writer.write_u16(0x1234);
+        writer.write_u32(0xDEADBEEF);
+        writer.write_u64(u64::MAX);
+        writer.write_block(&[1, 2, 3]);
+        let bytes = writer.into_bytes();
+
+        let mut reader = StateReader::new(&bytes);
+        assert_eq!(reader.read_u8(), Ok(0xAB));
+        assert_eq!(reader.read_bool(), Ok(true));
+        assert_eq!(reader.read_u16(), Ok(0x1234));
+        assert_eq!(reader.read_u32(), Ok(0xDEADBEEF));
+        assert_eq!(reader.read_u64(), Ok(u64::MAX));
+        assert_eq!(reader.read_block(), Ok(&[1u8, 2, 3][..]));
+        assert!(reader.is_empty());
+        assert!(reader.read_u8().is_err());
+    }
+
+    #[test]
+    fn test_rom_hash() {
+        assert_eq!(rom_hash(&[]), 0xCBF2_9CE4_8422_2325);
+        assert_eq!(rom_hash(b"a"), 0xAF63_DC4C_8601_EC8C);
+        assert_ne!(rom_hash(&[0x00, 0xE0]), rom_hash(&[0xE0, 0x00]));
+    }
+}
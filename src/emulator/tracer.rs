@@ -0,0 +1,346 @@
+//! Execution traces: every instruction the interpreter runs, with the state it changed.
+//!
+//! While tracing is enabled with `Chip8::set_tracing`, executed instructions are collected as
+//! `TraceEntry`s; a `Tracer` takes them with `Chip8::take_trace`, filters them and writes them
+//! out, e.g. once per frame.
+//!
+//! The text format has one line per instruction: the address, the raw bytes, the disassembly in
+//! the classic syntax and the changes, e.g.
+//!
+//! ```text
+//! 0204  7A05      ADD VA, 0x05                VA:01->06
+//! ```
+//!
+//! The binary format starts with `C8TR` and a big-endian u16 version, then holds one record per
+//! instruction: the u16 address, a u8 byte count and the raw bytes, a u8 change count, then one
+//! u8 tag and the new value per change. Tags 0x0 to 0xF are the registers `V0` to `VF` (u8),
+//! 0x10 is `I` (u16), 0x11 the delay timer and 0x12 the sound timer (u8).
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use super::debugger::parse_address;
+use super::disassembler::{disassemble_instruction, Syntax};
+use super::error::Chip8Error;
+use super::instruction::Instruction;
+use super::platform::Platform;
+
+/// Bytes every binary trace starts with
+pub const TRACE_MAGIC: &[u8; 4] = b"C8TR";
+
+/// Version of the binary trace layout
+pub const TRACE_VERSION: u16 = 1;
+
+/// A piece of machine state changed by an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Register { x: u8, old: u8, new: u8 },
+    IndexRegister { old: u16, new: u16 },
+    DelayTimer { old: u8, new: u8 },
+    SoundTimer { old: u8, new: u8 },
+}
+
+/// One executed instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub address: u16,
+    /// The opcode, followed by the address word of an XO-CHIP `F000 nnnn`
+    pub bytes: Vec<u8>,
+    pub instruction: Instruction,
+    pub changes: Vec<Change>,
+}
+
+/// The state an instruction is checked for changes in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct TraceSnapshot {
+    pub registers: [u8; 16],
+    pub index_register: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+}
+
+impl TraceSnapshot {
+    /// Differences from `self` to the state after the instruction
+    pub fn changes_to(&self, after: &TraceSnapshot) -> Vec<Change> {
+        let mut changes: Vec<Change> = (0..16)
+            .filter(|x| self.registers[*x] != after.registers[*x])
+            .map(|x| Change::Register { x: x as u8, old: self.registers[x], new: after.registers[x] })
+            .collect();
+
+        if self.index_register != after.index_register {
+            changes.push(Change::IndexRegister { old: self.index_register, new: after.index_register });
+        }
+        if self.delay_timer != after.delay_timer {
+            changes.push(Change::DelayTimer { old: self.delay_timer, new: after.delay_timer });
+        }
+        if self.sound_timer != after.sound_timer {
+            changes.push(Change::SoundTimer { old: self.sound_timer, new: after.sound_timer });
+        }
+
+        changes
+    }
+}
+
+/// Output formats of a `Tracer`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    Text,
+    Binary,
+}
+
+impl TraceFormat {
+    /// Names accepted by `TraceFormat::from_name`
+    pub const NAMES: [&'static str; 2] = ["text", "binary"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "text" => Some(TraceFormat::Text),
+            "binary" => Some(TraceFormat::Binary),
+            _ => None,
+        }
+    }
+}
+
+/// Selects the instructions a `Tracer` writes. Empty lists match everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceFilter {
+    /// Inclusive `(start, end)` address ranges
+    pub address_ranges: Vec<(u16, u16)>,
+    /// First hex digits of the opcodes to trace, e.g. 0xD for draws
+    pub opcode_classes: Vec<u8>,
+    /// Stop after this many instructions have been written
+    pub limit: Option<u64>,
+}
+
+impl TraceFilter {
+    pub fn matches(&self, entry: &TraceEntry) -> bool {
+        let in_range = self.address_ranges.is_empty()
+            || self.address_ranges.iter().any(|(start, end)| (*start..=*end).contains(&entry.address));
+        let in_class = self.opcode_classes.is_empty()
+            || self.opcode_classes.contains(&(entry.bytes[0] >> 4));
+
+        in_range && in_class
+    }
+
+    /// Parses a hex address range like `200-2FF`. A single address is a range of one.
+    pub fn parse_range(value: &str) -> Option<(u16, u16)> {
+        let (start, end) = value.split_once('-').unwrap_or((value, value));
+        let (start, end) = (parse_address(start.trim())?, parse_address(end.trim())?);
+        (start <= end).then_some((start, end))
+    }
+
+    /// Parses comma-separated opcode classes, each the first hex digit of an opcode, like `8,D,F`
+    pub fn parse_classes(value: &str) -> Option<Vec<u8>> {
+        value.split(',')
+            .map(|class| match class.trim() {
+                digit if digit.len() == 1 => u8::from_str_radix(digit, 16).ok(),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Writes the instructions collected by `Chip8` to a file or any other writer
+pub struct Tracer<W: Write> {
+    writer: W,
+    path: String,   // reported in write errors
+    format: TraceFormat,
+    filter: TraceFilter,
+    platform: Option<Platform>,
+    written: u64,
+}
+
+impl Tracer<BufWriter<File>> {
+    /// Creates the trace file at `path`. `platform` selects the instruction set of the disassembly.
+    pub fn create(path: &str, format: TraceFormat, filter: TraceFilter, platform: Option<Platform>) -> Result<Self, Chip8Error> {
+        let file = File::create(path).map_err(|e| Chip8Error::Io {
+            path: path.to_string(),
+            reason: e.to_string(),
+        })?;
+
+        Tracer::new(BufWriter::new(file), path, format, filter, platform)
+    }
+}
+
+impl<W: Write> Tracer<W> {
+    /// `path` names the writer in errors
+    pub fn new(writer: W, path: &str, format: TraceFormat, filter: TraceFilter, platform: Option<Platform>) -> Result<Self, Chip8Error> {
+        let mut tracer = Self { writer, path: path.to_string(), format, filter, platform, written: 0 };
+        if format == TraceFormat::Binary {
+            tracer.write(&[&TRACE_MAGIC[..], &TRACE_VERSION.to_be_bytes()].concat())?;
+        }
+
+        Ok(tracer)
+    }
+
+    pub fn get_writer(&self) -> &W {
+        &self.writer
+    }
+
+    /// Number of instructions written so far
+    pub fn get_written(&self) -> u64 {
+        self.written
+    }
+
+    /// Whether the filter's limit has been reached; nothing more will be written
+    pub fn is_done(&self) -> bool {
+        self.filter.limit.is_some_and(|limit| self.written >= limit)
+    }
+
+    /// Writes the entries that pass the filter, up to its limit
+    pub fn record(&mut self, entries: &[TraceEntry]) -> Result<(), Chip8Error> {
+        for entry in entries.iter() {
+            if self.is_done() {
+                break;
+            }
+            if !self.filter.matches(entry) {
+                continue;
+            }
+
+            let bytes = match self.format {
+                TraceFormat::Text => self.format_text(entry).into_bytes(),
+                TraceFormat::Binary => format_binary(entry),
+            };
+            self.write(&bytes)?;
+            self.written += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Flushes everything written so far
+    pub fn finish(&mut self) -> Result<(), Chip8Error> {
+        self.writer.flush().map_err(|e| self.io_error(e))
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Chip8Error> {
+        self.writer.write_all(bytes).map_err(|e| self.io_error(e))
+    }
+
+    fn io_error(&self, e: std::io::Error) -> Chip8Error {
+        Chip8Error::Io { path: self.path.clone(), reason: e.to_string() }
+    }
+
+    fn format_text(&self, entry: &TraceEntry) -> String {
+        let raw: String = entry.bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let text = disassemble_instruction(&entry.bytes, self.platform, Syntax::Classic)
+            .unwrap_or_else(|| format!("{:?}", entry.instruction));
+        let changes: Vec<String> = entry.changes.iter()
+            .map(|change| match change {
+                Change::Register { x, old, new } => format!("V{:X}:{:02X}->{:02X}", x, old, new),
+                Change::IndexRegister { old, new } => format!("I:{:03X}->{:03X}", old, new),
+                Change::DelayTimer { old, new } => format!("DT:{:02X}->{:02X}", old, new),
+                Change::SoundTimer { old, new } => format!("ST:{:02X}->{:02X}", old, new),
+            })
+            .collect();
+
+        let line = format!("{:04X}  {:<8}  {:<28}{}", entry.address, raw, text, changes.join(" "));
+        format!("{}\n", line.trim_end())
+    }
+}
+
+fn format_binary(entry: &TraceEntry) -> Vec<u8> {
+    let mut bytes = entry.address.to_be_bytes().to_vec();
+    bytes.push(entry.bytes.len() as u8);
+    bytes.extend_from_slice(&entry.bytes);
+
+    bytes.push(entry.changes.len() as u8);
+    for change in entry.changes.iter() {
+        match change {
+            Change::Register { x, new, .. } => bytes.extend_from_slice(&[*x, *new]),
+            Change::IndexRegister { new, .. } => {
+                bytes.push(0x10);
+                bytes.extend_from_slice(&new.to_be_bytes());
+            },
+            Change::DelayTimer { new, .. } => bytes.extend_from_slice(&[0x11, *new]),
+            Change::SoundTimer { new, .. } => bytes.extend_from_slice(&[0x12, *new]),
+        }
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::chip8::Chip8;
+
+    fn traced_run(rom: &[u8], cycles: usize) -> Vec<TraceEntry> {
+        let mut c8 = Chip8::from_rom_bytes(rom).unwrap();
+        c8.set_tracing(true);
+        for _ in 0..cycles {
+            c8.cycle().unwrap();
+        }
+        c8.take_trace()
+    }
+
+    // 0x200: VA := 0x05; 0x202: I := 0x300; 0x204: VA += 0x01; 0x206: delay := VA; 0x208: jump 0x204
+    const ROM: [u8; 10] = [0x6A, 0x05, 0xA3, 0x00, 0x7A, 0x01, 0xFA, 0x15, 0x12, 0x04];
+
+    #[test]
+    fn test_chip8_collects_changes() {
+        let trace = traced_run(&ROM, 4);
+
+        assert_eq!(trace.len(), 4);
+        assert_eq!(trace[0], TraceEntry {
+            address: 0x200,
+            bytes: vec![0x6A, 0x05],
+            instruction: Instruction::LoadImm { x: 0xA, kk: 0x05 },
+            changes: vec![Change::Register { x: 0xA, old: 0, new: 5 }],
+        });
+        assert_eq!(trace[1].changes, [Change::IndexRegister { old: 0, new: 0x300 }]);
+        assert_eq!(trace[3].changes, [Change::DelayTimer { old: 0, new: 6 }]);
+
+        let mut c8 = Chip8::from_rom_bytes(&ROM).unwrap();
+        c8.cycle().unwrap();
+        assert!(c8.take_trace().is_empty());
+    }
+
+    #[test]
+    fn test_text_trace() {
+        let mut tracer = Tracer::new(Vec::new(), "trace.txt", TraceFormat::Text, TraceFilter::default(), None).unwrap();
+        tracer.record(&traced_run(&ROM, 2)).unwrap();
+
+        assert_eq!(String::from_utf8(tracer.get_writer().clone()).unwrap(), "\
+0200  6A05      LD VA, 0x05                 VA:00->05
+0202  A300      LD I, 0x300                 I:000->300
+");
+    }
+
+    #[test]
+    fn test_binary_trace() {
+        let mut tracer = Tracer::new(Vec::new(), "trace.bin", TraceFormat::Binary, TraceFilter::default(), None).unwrap();
+        tracer.record(&traced_run(&ROM, 2)).unwrap();
+
+        assert_eq!(tracer.get_writer().as_slice(), [
+            b'C', b'8', b'T', b'R', 0x00, 0x01,
+            0x02, 0x00, 2, 0x6A, 0x05, 1, 0x0A, 0x05,
+            0x02, 0x02, 2, 0xA3, 0x00, 1, 0x10, 0x03, 0x00,
+        ]);
+    }
+
+    #[test]
+    fn test_filters() {
+        let trace = traced_run(&ROM, 12);
+        let written = |filter: TraceFilter| {
+            let mut tracer = Tracer::new(Vec::new(), "trace.txt", TraceFormat::Text, filter, None).unwrap();
+            tracer.record(&trace).unwrap();
+            tracer.get_written()
+        };
+
+        assert_eq!(written(TraceFilter::default()), 12);
+        assert_eq!(written(TraceFilter { address_ranges: vec![(0x204, 0x205)], ..Default::default() }), 4);
+        assert_eq!(written(TraceFilter { opcode_classes: vec![0x1, 0x6], ..Default::default() }), 4);
+        assert_eq!(written(TraceFilter { limit: Some(5), ..Default::default() }), 5);
+    }
+
+    #[test]
+    fn test_parse_filters() {
+        assert_eq!(TraceFilter::parse_range("200-2ff"), Some((0x200, 0x2FF)));
+        assert_eq!(TraceFilter::parse_range("0x300"), Some((0x300, 0x300)));
+        assert_eq!(TraceFilter::parse_range("300-200"), None);
+        assert_eq!(TraceFilter::parse_classes("8, d,F"), Some(vec![0x8, 0xD, 0xF]));
+        assert_eq!(TraceFilter::parse_classes("8,10"), None);
+        assert_eq!(TraceFormat::from_name("Binary"), Some(TraceFormat::Binary));
+    }
+}
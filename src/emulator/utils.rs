@@ -1,12 +1,30 @@
 pub fn get_bits_of_byte(byte: u8) -> [u8; 8] {
     let mut bits: [u8; 8] = [0; 8];
-    for i in 0..8 {
-        bits[i] = (byte >> (7 - i)) & 1;
+    for (i, bit) in bits.iter_mut().enumerate() {
+        *bit = (byte >> (7 - i)) & 1;
     }
 
     bits
 }
 
+/// Returns true if `contents` looks like a hex-text dump of 16-bit words
+/// (e.g. `6000 6100 a222`) rather than a binary ROM image
+pub fn is_hex_text(contents: &[u8]) -> bool {
+    let mut words = 0;
+
+    for word in contents.split(|byte| byte.is_ascii_whitespace()) {
+        if word.is_empty() {
+            continue;
+        }
+        if word.len() > 4 || !word.iter().all(|byte| byte.is_ascii_hexdigit()) {
+            return false;
+        }
+        words += 1;
+    }
+
+    words > 0
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -28,4 +46,14 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_is_hex_text() {
+        assert!(is_hex_text(b"6000 6100 a222\n"));
+        assert!(is_hex_text(b"124e eaac\r\naaea"));
+
+        assert!(!is_hex_text(b""));
+        assert!(!is_hex_text(&[0x60, 0x00, 0x61, 0x00]));
+        assert!(!is_hex_text(b"6000 zz00"));
+    }
 }
@@ -0,0 +1,14 @@
+pub mod emulator;
+
+pub use emulator::audio::{AudioSink, Buzzer, BuzzerSettings, NullSink, Waveform};
+pub use emulator::chip8::Chip8;
+pub use emulator::debugger::{DebugCommand, Debugger};
+pub use emulator::error::Chip8Error;
+pub use emulator::image::{ImageFormat, Palette};
+pub use emulator::instruction::Instruction;
+pub use emulator::movie::{Movie, MovieHeader};
+pub use emulator::platform::Platform;
+pub use emulator::quirks::Quirks;
+pub use emulator::recorder::{FrameRecorder, GifRecorder, PngSequenceRecorder};
+pub use emulator::rewind::RewindBuffer;
+pub use emulator::rng::Rng;
@@ -1,27 +1,156 @@
 extern crate sdl2;
-mod emulator;
+mod audio;
+mod cli;
+mod pacer;
 mod screen;
 
-use emulator::chip8::Chip8;
+use chip8_rust::emulator::assembler::assemble_file;
+use chip8_rust::emulator::chip8::read_rom_file;
+use chip8_rust::emulator::constants::{LORES_HEIGHT, LORES_WIDTH};
+use chip8_rust::emulator::debugger::format_state;
+use chip8_rust::emulator::disassembler::{disassemble, format_listing};
+use chip8_rust::emulator::image::save_screenshot;
+use chip8_rust::emulator::tracer::Tracer;
+use chip8_rust::{
+    AudioSink,
+    Chip8,
+    Debugger,
+    FrameRecorder,
+    GifRecorder,
+    Movie,
+    MovieHeader,
+    NullSink,
+    Palette,
+    PngSequenceRecorder,
+    RewindBuffer,
+};
+use cli::{AsmConfig, Command, DisasmConfig};
 
-use sdl2::keyboard::Keycode;
 use sdl2::render::{
-    TextureCreator, 
+    TextureCreator,
     TextureAccess,
 };
 use sdl2::video::WindowContext;
 use sdl2::pixels::PixelFormatEnum;
-use std::collections::HashMap;
 
 
 fn main() {
+    let config = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(Command::Run(config)) => config,
+        Ok(Command::Disassemble(config)) => std::process::exit(disassemble_rom(&config)),
+        Ok(Command::Assemble(config)) => std::process::exit(assemble_source(&config)),
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        },
+        Ok(Command::DisasmHelp) => {
+            println!("{}", cli::DISASM_USAGE);
+            return;
+        },
+        Ok(Command::AsmHelp) => {
+            println!("{}", cli::ASM_USAGE);
+            return;
+        },
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        },
+    };
+
+    if let Some(path) = &config.verify_path {
+        std::process::exit(verify_movie(path, &config.rom_path));
+    }
+
+    let key_mapping = match &config.keymap_path {
+        Some(path) => {
+            let contents = match std::fs::read_to_string(path) {
+                Ok(contents) => contents,
+                Err(e) => {
+                    eprintln!("error: could not read key map {}: {}", path, e);
+                    std::process::exit(1);
+                },
+            };
+            match screen::parse_key_mapping(&contents) {
+                Ok(key_mapping) => key_mapping,
+                Err(e) => {
+                    eprintln!("error: invalid key map {}: {}", path, e);
+                    std::process::exit(1);
+                },
+            }
+        },
+        None => screen::default_key_mapping(),
+    };
+
+    let playback = match &config.play_path {
+        Some(path) => match Movie::load_file(path) {
+            Ok(movie) => Some(movie),
+            Err(e) => {
+                eprintln!("Failed to load movie {}: {}", path, e);
+                std::process::exit(1);
+            },
+        },
+        None => None,
+    };
+
+    // a movie brings its own platform, quirks and seed
+    let loaded = match (&playback, config.platform) {
+        (Some(movie), _) => movie.create_machine(&config.rom_path),
+        (None, Some(platform)) => {
+            let mut c8 = Chip8::with_platform(platform);
+            c8.load_rom_file(&config.rom_path).map(|_| c8)
+        },
+        (None, None) => Chip8::new(&config.rom_path),
+    };
+
+    let mut c8 = match loaded {
+        Ok(c8) => c8,
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", config.rom_path, e);
+            std::process::exit(1);
+        },
+    };
+    if playback.is_none() {
+        c8.set_quirks(config.quirks);
+        if let Some(seed) = config.seed {
+            c8.seed_rng(seed);
+        }
+    }
+
+    let instructions_per_frame = match &playback {
+        Some(movie) => movie.get_header().instructions_per_frame,
+        None => config.instructions_per_frame,
+    };
+
+    let mut recording = config.record_path.as_ref()
+        .map(|_| Movie::new(MovieHeader::from_chip8(&c8, instructions_per_frame)));
+
+    let palette = Palette {
+        background: config.background,
+        foreground: config.foreground,
+        plane2: config.plane2_color,
+        blend: config.blend_color,
+    };
+
     // setup sdl2 resources
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem  = sdl_context.video().unwrap();
 
-    let screen_width = 64;
-    let screen_height = 32;
-    let video_scale = 12_u32;
+    // fall back to silence rather than refusing to run without an audio device
+    let mut audio_sink: Box<dyn AudioSink> = match sdl_context.audio()
+        .and_then(|audio_subsystem| audio::SdlAudioSink::open(&audio_subsystem, config.buzzer)) {
+        Ok(sink) => Box::new(sink),
+        Err(e) => {
+            eprintln!("Audio disabled: {}", e);
+            Box::new(NullSink)
+        },
+    };
+    audio_sink.set_muted(config.mute);
+
+    // sized for the platform's highest resolution; lo-res frames are stretched to fill it
+    let (screen_width, screen_height) = c8.get_platform()
+        .map_or((LORES_WIDTH, LORES_HEIGHT), |platform| platform.resolution());
+    let (screen_width, screen_height) = (screen_width as u32, screen_height as u32);
+    let video_scale = config.scale;
 
     let window = video_subsystem.window("Chip8", screen_width * video_scale, screen_height * video_scale)
         .position_centered()
@@ -35,8 +164,10 @@ fn main() {
 
     let texture_creator: TextureCreator<WindowContext> = canvas.texture_creator();
 
+    // the texture matches the emulated resolution and is recreated when the ROM switches modes
+    let mut texture_size = c8.get_display_size();
     let mut texture = texture_creator.create_texture(
-        PixelFormatEnum::RGBA8888, TextureAccess::Streaming, screen_width, screen_height
+        PixelFormatEnum::RGBA8888, TextureAccess::Streaming, texture_size.0 as u32, texture_size.1 as u32
     ).unwrap();
 
     let mut event_pump = match sdl_context.event_pump() {
@@ -44,54 +175,381 @@ fn main() {
         Err(e) => panic!("{}", e),
     };
 
-    let key_mapping = HashMap::from([
-        (Keycode::NUM_1, 1),
-        (Keycode::NUM_2, 2),
-        (Keycode::NUM_3, 3),
-        (Keycode::NUM_4, 0xC),
-    
-        (Keycode::Q, 4),
-        (Keycode::W, 5),
-        (Keycode::E, 6),
-        (Keycode::R, 0xD),
-    
-        (Keycode::A, 7),
-        (Keycode::S, 8),
-        (Keycode::D, 9),
-        (Keycode::F, 0xE),
-    
-        (Keycode::Z, 0xA),
-        (Keycode::X, 0),
-        (Keycode::C, 0xB),
-        (Keycode::V, 0xF),
-    ]);
-
-    let mut c8 = Chip8::new("./examples/test_opcode.ch8");
-
-    let cycle_delay = 4;
-    let video_pitch = (std::mem::size_of_val(&c8.get_display_memory()[0][0]) * 64) as u32;
-    
-    let mut last_cycle_time = std::time::SystemTime::now();
+    let mut pacer = pacer::FramePacer::new();
+    let mut rewind_buffer = RewindBuffer::new(config.rewind_depth as usize, config.rewind_memory as usize * 1024 * 1024, 1);
+    let mut rewinding = false;
+    let mut fast_forward = false;
+    let mut slow_motion = false;
+
+    let mut capture: Option<Box<dyn FrameRecorder>> = match &config.capture_path {
+        Some(path) => match open_capture(path, palette, config.scale as usize) {
+            Ok(recorder) => Some(recorder),
+            Err(e) => {
+                eprintln!("Failed to start capture: {}", e);
+                std::process::exit(1);
+            },
+        },
+        None => None,
+    };
+
+    let mut tracer = match &config.trace_path {
+        Some(path) => match Tracer::create(path, config.trace_format, config.trace_filter.clone(), c8.get_platform()) {
+            Ok(tracer) => Some(tracer),
+            Err(e) => {
+                eprintln!("Failed to start trace: {}", e);
+                std::process::exit(1);
+            },
+        },
+        None => None,
+    };
+    c8.set_tracing(tracer.is_some());
+
+    let mut debugger = Debugger::new(instructions_per_frame);
+    for address in config.breakpoints.iter() {
+        debugger.add_breakpoint(*address);
+    }
+    if config.debug {
+        debugger.pause();
+        println!("{}", format_state(&c8));
+    }
+    let mut title_paused = false;
 
     let mut quit = false;
+    let mut halted = false;
+    let mut frame = 0;
 
     while !quit {
-        quit = screen::process_input(&mut event_pump, c8.get_keypad(), &key_mapping);
+        // jumping around in time would make the recorded or played back input meaningless
+        let input_locked = recording.is_some() || playback.as_ref().is_some_and(|movie| frame < movie.len());
 
-        let current_time = std::time::SystemTime::now();
-        let dt = match current_time.duration_since(last_cycle_time) {
-            Ok(v) => v.as_millis(),
-            Err(e) => panic!("{}", e),
-        };
+        for action in screen::process_input(&mut event_pump, c8.get_keypad(), &key_mapping) {
+            match action {
+                screen::Action::Quit => quit = true,
+                screen::Action::ToggleMute => audio_sink.set_muted(!audio_sink.is_muted()),
+                screen::Action::ToggleFastForward => {
+                    fast_forward = !fast_forward;
+                    slow_motion = false;
+                },
+                screen::Action::ToggleSlowMotion => {
+                    slow_motion = !slow_motion;
+                    fast_forward = false;
+                },
+                screen::Action::SaveState(slot) => {
+                    let path = state_slot_path(&config.rom_path, slot);
+                    match c8.save_state_file(&path) {
+                        Ok(()) => println!("Saved state to {}", path),
+                        Err(e) => eprintln!("Failed to save state: {}", e),
+                    }
+                },
+                screen::Action::LoadState(_) | screen::Action::StartRewind if input_locked => {
+                    eprintln!("Loading states and rewinding are disabled while a movie is recorded or played");
+                },
+                screen::Action::TogglePause
+                | screen::Action::Step
+                | screen::Action::StepOver
+                | screen::Action::StepOut
+                | screen::Action::ToggleBreakpoint if input_locked => {
+                    eprintln!("The debugger is disabled while a movie is recorded or played");
+                },
+                screen::Action::LoadState(slot) => {
+                    let path = state_slot_path(&config.rom_path, slot);
+                    match c8.load_state_file(&path) {
+                        Ok(()) => {
+                            println!("Loaded state from {}", path);
+                            halted = false;
+                            title_paused = false;
+                            let _ = canvas.window_mut().set_title("Chip8");
+                        },
+                        Err(e) => eprintln!("Failed to load state: {}", e),
+                    }
+                },
+                screen::Action::Screenshot => {
+                    let path = screenshot_path(&config.rom_path);
+                    match save_screenshot(&c8, &path, config.scale as usize, &palette) {
+                        Ok(()) => println!("Saved screenshot to {}", path),
+                        Err(e) => eprintln!("Failed to save screenshot: {}", e),
+                    }
+                },
+                screen::Action::ToggleCapture => match capture.take() {
+                    Some(recorder) => finish_capture(recorder),
+                    None => {
+                        let path = capture_path(&config.rom_path);
+                        match open_capture(&path, palette, config.scale as usize) {
+                            Ok(recorder) => {
+                                println!("Capturing to {}", path);
+                                capture = Some(recorder);
+                            },
+                            Err(e) => eprintln!("Failed to start capture: {}", e),
+                        }
+                    },
+                },
+                screen::Action::TogglePause if debugger.is_paused() => debugger.resume(),
+                screen::Action::TogglePause => {
+                    debugger.pause();
+                    println!("{}", format_state(&c8));
+                },
+                screen::Action::Step | screen::Action::StepOver if debugger.is_paused() && !halted => {
+                    let result = match action {
+                        screen::Action::Step => debugger.step(&mut c8),
+                        _ => debugger.step_over(&mut c8),
+                    };
+                    match result {
+                        Ok(completed) => {
+                            if completed {
+                                frame += 1;
+                                rewind_buffer.record(&c8);
+                            }
+                            if debugger.is_paused() {
+                                println!("{}", format_state(&c8));
+                            }
+                        },
+                        Err(e) => {
+                            eprintln!("Emulation halted: {}", e);
+                            let _ = canvas.window_mut().set_title(&format!("Chip8 - halted: {}", e));
+                            halted = true;
+                        },
+                    }
+                },
+                screen::Action::StepOut if debugger.is_paused() && !halted => {
+                    if !debugger.step_out(&c8) {
+                        println!("Not inside a subroutine");
+                    }
+                },
+                // stepping only makes sense while paused
+                screen::Action::Step | screen::Action::StepOver | screen::Action::StepOut => {},
+                screen::Action::ToggleBreakpoint => {
+                    let pc = c8.get_pc();
+                    if debugger.toggle_breakpoint(pc) {
+                        println!("Breakpoint set at {:03X}", pc);
+                    } else {
+                        println!("Breakpoint removed at {:03X}", pc);
+                    }
+                },
+                screen::Action::StartRewind => rewinding = true,
+                screen::Action::StopRewind => rewinding = false,
+            }
+        }
 
-        if dt > cycle_delay {
-            last_cycle_time = current_time;
+        if rewinding {
+            // play back one recorded frame per frame until the buffer runs out
+            if rewind_buffer.rewind(&mut c8) && halted {
+                halted = false;
+                title_paused = false;
+                let _ = canvas.window_mut().set_title("Chip8");
+            }
+        } else if !halted && !debugger.is_paused() {
+            // the debugger can pause in the middle of a frame; its input was set when it started
+            if debugger.is_at_frame_start() {
+                if let Some(movie) = &playback {
+                    match movie.get_frame_keypad(frame) {
+                        Some(keypad) => *c8.get_keypad() = keypad,
+                        None if frame == movie.len() => {
+                            println!("Movie finished after {} frames", frame);
+                            *c8.get_keypad() = [0; 16];
+                        },
+                        None => {},
+                    }
+                }
+                if let Some(movie) = recording.as_mut() {
+                    movie.record_frame(&c8.get_keypad().clone());
+                }
+                frame += 1;
+            }
 
-            c8.cycle();
+            // stop executing but keep the window open so the last frame stays visible
+            match debugger.run_frame(&mut c8) {
+                Ok(_) if debugger.is_paused() => println!("Paused at {:03X}\n{}", c8.get_pc(), format_state(&c8)),
+                Ok(_) => {},
+                Err(e) => {
+                    eprintln!("Emulation halted: {}", e);
+                    let _ = canvas.window_mut().set_title(&format!("Chip8 - halted: {}", e));
+                    halted = true;
+                },
+            }
+            if c8.has_exited() && !halted {
+                let _ = canvas.window_mut().set_title("Chip8 - exited");
+                halted = true;
+            }
+            if debugger.is_at_frame_start() {
+                rewind_buffer.record(&c8);
+            }
+        }
+
+        // covers both the frame and any instructions stepped in the debugger
+        if let Some(trace) = tracer.as_mut() {
+            match trace.record(&c8.take_trace()) {
+                Ok(()) if trace.is_done() => c8.set_tracing(false),
+                Ok(()) => {},
+                Err(e) => {
+                    eprintln!("Trace stopped: {}", e);
+                    c8.set_tracing(false);
+                    tracer = None;
+                },
+            }
+        }
 
-            screen::update(&mut canvas, &mut texture, &c8.get_display_memory(), video_pitch as u32);
+        // keep the title in sync with the debugger, unless it shows why emulation halted
+        if !halted && debugger.is_paused() != title_paused {
+            title_paused = debugger.is_paused();
+            let _ = canvas.window_mut().set_title(if title_paused { "Chip8 - paused" } else { "Chip8" });
+        }
+
+        if c8.get_display_size() != texture_size {
+            texture_size = c8.get_display_size();
+            texture = texture_creator.create_texture(
+                PixelFormatEnum::RGBA8888, TextureAccess::Streaming, texture_size.0 as u32, texture_size.1 as u32
+            ).unwrap();
+        }
+
+        let display_colors = c8.get_display_colors();
+        screen::update(&mut canvas, &mut texture, &display_colors, &palette);
+
+        if let Some(recorder) = capture.as_mut() {
+            if let Err(e) = recorder.add_frame(&display_colors) {
+                eprintln!("Capture stopped: {}", e);
+                capture = None;
+            }
+        }
+
+        audio_sink.update(&c8);
+
+        let mut speed = config.speed;
+        if fast_forward {
+            speed *= pacer::FAST_FORWARD_FACTOR;
+        } else if slow_motion {
+            speed *= pacer::SLOW_MOTION_FACTOR;
+        }
+        pacer.wait(speed);
+    }
+
+    if let Some(recorder) = capture.take() {
+        finish_capture(recorder);
+    }
+
+    if let (Some(trace), Some(path)) = (tracer.as_mut(), &config.trace_path) {
+        match trace.finish() {
+            Ok(()) => println!("Traced {} instructions to {}", trace.get_written(), path),
+            Err(e) => eprintln!("Failed to save trace: {}", e),
+        }
+    }
+
+    if let (Some(movie), Some(path)) = (recording.as_mut(), &config.record_path) {
+        movie.finish(&c8);
+        match movie.save_file(path) {
+            Ok(()) => println!("Recorded {} frames to {}", movie.len(), path),
+            Err(e) => eprintln!("Failed to save movie: {}", e),
         }
     }
 
     // println!("{:?}", c8.get_display_memory())
 }
+
+
+/// First unused `game.ch8.screenshotN.png` next to the ROM
+fn screenshot_path(rom_path: &str) -> String {
+    (1..)
+        .map(|n| format!("{}.screenshot{}.png", rom_path, n))
+        .find(|path| !std::path::Path::new(path).exists())
+        .unwrap()
+}
+
+
+/// First unused `game.ch8.captureN.gif` next to the ROM
+fn capture_path(rom_path: &str) -> String {
+    (1..)
+        .map(|n| format!("{}.capture{}.gif", rom_path, n))
+        .find(|path| !std::path::Path::new(path).exists())
+        .unwrap()
+}
+
+
+/// Records to a GIF if `path` ends in `.gif`, otherwise to a directory of PNG frames
+fn open_capture(path: &str, palette: Palette, scale: usize) -> Result<Box<dyn FrameRecorder>, chip8_rust::Chip8Error> {
+    if path.to_ascii_lowercase().ends_with(".gif") {
+        Ok(Box::new(GifRecorder::new(path, palette, scale)))
+    } else {
+        Ok(Box::new(PngSequenceRecorder::new(path, palette, scale)?))
+    }
+}
+
+
+fn finish_capture(mut recorder: Box<dyn FrameRecorder>) {
+    match recorder.finish() {
+        Ok(()) => println!("Captured {} frames", recorder.frame_count()),
+        Err(e) => eprintln!("Failed to save capture: {}", e),
+    }
+}
+
+
+/// Runs the `disasm` subcommand. Returns the exit code.
+fn disassemble_rom(config: &DisasmConfig) -> i32 {
+    let rom = match read_rom_file(&config.rom_path) {
+        Ok(rom) => rom,
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", config.rom_path, e);
+            return 1;
+        },
+    };
+
+    let listing = format_listing(&disassemble(&rom, config.platform, config.syntax), config.syntax);
+    match &config.output_path {
+        Some(path) => match std::fs::write(path, listing) {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("Failed to write {}: {}", path, e);
+                1
+            },
+        },
+        None => {
+            print!("{}", listing);
+            0
+        },
+    }
+}
+
+
+/// Runs the `asm` subcommand. Returns the exit code.
+fn assemble_source(config: &AsmConfig) -> i32 {
+    let assembly = match assemble_file(&config.source_path) {
+        Ok(assembly) => assembly,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return 1;
+        },
+    };
+
+    if let Err(e) = std::fs::write(&config.output_path, &assembly.rom) {
+        eprintln!("Failed to write {}: {}", config.output_path, e);
+        return 1;
+    }
+    if let Some(path) = &config.listing_path {
+        if let Err(e) = std::fs::write(path, assembly.format_listing()) {
+            eprintln!("Failed to write {}: {}", path, e);
+            return 1;
+        }
+    }
+
+    println!("Assembled {} bytes into {}", assembly.rom.len(), config.output_path);
+    0
+}
+
+
+/// Plays a movie back as fast as possible and checks its final state. Returns the exit code.
+fn verify_movie(movie_path: &str, rom_path: &str) -> i32 {
+    match Movie::verify_file(movie_path, rom_path) {
+        Ok(movie) => {
+            println!("{}: {} frames, final state matches", movie_path, movie.len());
+            0
+        },
+        Err(e) => {
+            eprintln!("{}: {}", movie_path, e);
+            1
+        },
+    }
+}
+
+
+/// Save states live next to the ROM, e.g. `game.ch8.state1`
+fn state_slot_path(rom_path: &str, slot: u8) -> String {
+    format!("{}.state{}", rom_path, slot)
+}
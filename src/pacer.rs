@@ -0,0 +1,73 @@
+use std::time::{Duration, Instant};
+
+/// Wall-clock duration of one emulated frame at normal speed
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// How far the loop may fall behind before the pacer gives up catching up
+const MAX_LAG: Duration = Duration::from_millis(100);
+
+/// Speed multiplier applied while fast-forward is on
+pub const FAST_FORWARD_FACTOR: f64 = 4.0;
+
+/// Speed multiplier applied while slow motion is on
+pub const SLOW_MOTION_FACTOR: f64 = 0.25;
+
+/// Sleeps between frames so they are emulated at 60 Hz times a speed multiplier
+pub struct FramePacer {
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    pub fn new() -> Self {
+        Self {
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// Blocks until the next frame is due at the given speed multiplier.
+    ///
+    /// If the host could not keep up, the schedule is reset instead of running a burst
+    /// of frames to catch up.
+    pub fn wait(&mut self, speed: f64) {
+        self.next_frame += frame_duration(speed);
+
+        let now = Instant::now();
+        if self.next_frame > now {
+            std::thread::sleep(self.next_frame - now);
+        } else if now - self.next_frame > MAX_LAG {
+            self.next_frame = now;
+        }
+    }
+}
+
+/// Wall-clock duration of one frame at the given speed multiplier
+pub fn frame_duration(speed: f64) -> Duration {
+    FRAME_DURATION.div_f64(speed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_duration() {
+        let close_to = |duration: Duration, expected: Duration| duration.abs_diff(expected) < Duration::from_micros(1);
+
+        assert!(close_to(frame_duration(1.0), FRAME_DURATION));
+        assert!(close_to(frame_duration(FAST_FORWARD_FACTOR), FRAME_DURATION / 4));
+        assert!(close_to(frame_duration(SLOW_MOTION_FACTOR), FRAME_DURATION * 4));
+    }
+
+    #[test]
+    fn test_wait_resets_after_lag() {
+        let mut pacer = FramePacer::new();
+        pacer.next_frame -= Duration::from_secs(1);
+
+        let start = Instant::now();
+        pacer.wait(1.0);
+
+        // no sleep while behind, and the schedule restarts from now
+        assert!(start.elapsed() < FRAME_DURATION);
+        assert!(pacer.next_frame >= start);
+    }
+}
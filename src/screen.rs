@@ -1,7 +1,7 @@
 extern crate sdl2;
 
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Mod};
 use sdl2::render::{
     Canvas,
     Texture,
@@ -10,22 +10,28 @@ use sdl2::EventPump;
 use sdl2::video::Window;
 use std::collections::HashMap;
 
+use chip8_rust::Palette;
 
-pub fn update(canvas: &mut Canvas<Window>, texture: &mut Texture, display_memory: &[[u32; 64]; 32], pitch: u32) {
-    let mut buffer : [u8; 64 * 32 * 4] = [0; 64 * 32 * 4];
+
+/// Draws the display onto the canvas, stretched to fill the window.
+///
+/// `display_colors` holds the color index of each pixel, see `Chip8::get_display_colors`.
+/// `texture` must have the same dimensions as `display_colors`.
+pub fn update(canvas: &mut Canvas<Window>, texture: &mut Texture, display_colors: &[Vec<u8>], palette: &Palette) {
+    let width = display_colors[0].len();
+    let pitch = width * 4;
+    let mut buffer = vec![0u8; pitch * display_colors.len()];
 
     // flatten the display input
-    // TODO: Might cause a slowdown if we do this with every cpu cycle; try to flatten the display memory at the emulator level
-    for i in 0..display_memory.len() {
-        let row = display_memory[i];
+    for (i, row) in display_colors.iter().enumerate() {
         let row_offset = i * row.len();
-        for j in 0..row.len() {
+        for (j, pixel) in row.iter().enumerate() {
             let start_index = j + row_offset;
-            buffer[4*start_index..][..4].copy_from_slice(&row[j].to_le_bytes());
+            buffer[4*start_index..][..4].copy_from_slice(&((palette.color(*pixel) << 8) | 0xFF).to_le_bytes());
         }
     }
 
-    match texture.update(Option::None, &buffer, pitch.try_into().unwrap()) {
+    match texture.update(Option::None, &buffer, pitch) {
         Ok(_) => (),
         Err(e) => panic!("{}", e),
     };
@@ -41,55 +47,152 @@ pub fn update(canvas: &mut Canvas<Window>, texture: &mut Texture, display_memory
 }
 
 
-pub fn process_input(event_pump: &mut EventPump, keys: &mut [u8; 16], key_mapping: &HashMap<Keycode, u8>) -> bool {
-    let mut quit = false;
+/// Emulator commands bound to keys outside of the CHIP-8 keypad
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    ToggleMute,
+    ToggleFastForward,
+    ToggleSlowMotion,
+    /// Save the machine state to the numbered slot, 1 to 10
+    SaveState(u8),
+    LoadState(u8),
+    /// Rewind key pressed; emulation runs backwards until `StopRewind`
+    StartRewind,
+    StopRewind,
+    /// Save the display to an image file
+    Screenshot,
+    /// Start or stop capturing the display to an animated GIF
+    ToggleCapture,
+    TogglePause,
+    /// Execute a single instruction while paused
+    Step,
+    StepOver,
+    StepOut,
+    /// Toggle a breakpoint at the current `pc`
+    ToggleBreakpoint,
+}
+
+/// Updates the keypad from the pending events and returns the actions triggered by hotkeys.
+/// Keys in `key_mapping` always go to the keypad, even if they are also a hotkey.
+pub fn process_input(event_pump: &mut EventPump, keys: &mut [u8; 16], key_mapping: &HashMap<Keycode, u8>) -> Vec<Action> {
+    let mut actions = Vec::new();
 
-    'event_poll: for event in event_pump.poll_iter() {
+    for event in event_pump.poll_iter() {
         match event {
             Event::Quit{..} => {
-                quit = true;
-                break 'event_poll;
+                actions.push(Action::Quit);
             },
-            Event::KeyDown { keycode, ..} => {
-                match keycode {
-                    Some(Keycode::ESCAPE) => {
-                        quit = true;
-                        break 'event_poll;
-                    },
-                    Some(Keycode::NUM_1) => keys[1] = 1,
-                    Some(Keycode::NUM_2) => keys[2] = 1,
-                    Some(Keycode::NUM_3) => keys[3] = 1,
-                    Some(Keycode::NUM_4) => keys[0xC] = 1,
-                    Some(Keycode::Q) => keys[4] = 1,
-                    Some(Keycode::W) => keys[5] = 1,
-                    Some(Keycode::E) => keys[6] = 1,
-                    Some(Keycode::R) => keys[0xD] = 1,
-
-                    Some(Keycode::A) => keys[7] = 1,
-                    Some(Keycode::S) => keys[8] = 1,
-                    Some(Keycode::D) => keys[9] = 1,
-                    Some(Keycode::F) => keys[0xE] = 1,
-
-                    Some(Keycode::Z) => keys[0xA] = 1,
-                    Some(Keycode::X) => keys[0] = 1,
-                    Some(Keycode::C) => keys[0xB] = 1,
-                    Some(Keycode::V) => keys[0xF] = 1,
-                    Some(_) | None => {},
+            Event::KeyDown { keycode: Some(keycode), keymod, repeat, ..} => {
+                if let Some(key_value) = key_mapping.get(&keycode) {
+                    keys[usize::from(*key_value)] = 1;
+                } else if !repeat {
+                    match keycode {
+                        Keycode::ESCAPE => actions.push(Action::Quit),
+                        Keycode::M => actions.push(Action::ToggleMute),
+                        Keycode::TAB => actions.push(Action::ToggleFastForward),
+                        Keycode::BACKQUOTE => actions.push(Action::ToggleSlowMotion),
+                        Keycode::BACKSPACE => actions.push(Action::StartRewind),
+                        Keycode::F11 => actions.push(Action::ToggleCapture),
+                        Keycode::F12 => actions.push(Action::Screenshot),
+                        Keycode::P => actions.push(Action::TogglePause),
+                        Keycode::N => actions.push(Action::Step),
+                        Keycode::O => actions.push(Action::StepOver),
+                        Keycode::U => actions.push(Action::StepOut),
+                        Keycode::B => actions.push(Action::ToggleBreakpoint),
+                        _ => match state_slot(keycode) {
+                            Some(slot) if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) => actions.push(Action::SaveState(slot)),
+                            Some(slot) => actions.push(Action::LoadState(slot)),
+                            None => {},
+                        },
+                    }
                 }
             }
-            Event::KeyUp { keycode, .. } => {
-                match keycode {
-                    Some(keycode) => {
-                        if key_mapping.contains_key(&keycode) {
-                            let key_value = *key_mapping.get(&keycode).unwrap();
-                            keys[usize::from(key_value)] = 0;
-                        }
-                    },
-                    None => {},
+            Event::KeyUp { keycode: Some(keycode), .. } => {
+                if let Some(key_value) = key_mapping.get(&keycode) {
+                    keys[usize::from(*key_value)] = 0;
+                } else if keycode == Keycode::BACKSPACE {
+                    actions.push(Action::StopRewind);
                 }
             }
             _ => {},
         }
     }
-    quit
+    actions
+}
+
+
+/// Save state slot selected by the function keys F1 to F10
+fn state_slot(keycode: Keycode) -> Option<u8> {
+    let slot = match keycode {
+        Keycode::F1 => 1,
+        Keycode::F2 => 2,
+        Keycode::F3 => 3,
+        Keycode::F4 => 4,
+        Keycode::F5 => 5,
+        Keycode::F6 => 6,
+        Keycode::F7 => 7,
+        Keycode::F8 => 8,
+        Keycode::F9 => 9,
+        Keycode::F10 => 10,
+        _ => return None,
+    };
+    Some(slot)
+}
+
+
+/// The conventional mapping of the left side of a QWERTY keyboard onto the CHIP-8 hex keypad
+pub fn default_key_mapping() -> HashMap<Keycode, u8> {
+    HashMap::from([
+        (Keycode::NUM_1, 1),
+        (Keycode::NUM_2, 2),
+        (Keycode::NUM_3, 3),
+        (Keycode::NUM_4, 0xC),
+
+        (Keycode::Q, 4),
+        (Keycode::W, 5),
+        (Keycode::E, 6),
+        (Keycode::R, 0xD),
+
+        (Keycode::A, 7),
+        (Keycode::S, 8),
+        (Keycode::D, 9),
+        (Keycode::F, 0xE),
+
+        (Keycode::Z, 0xA),
+        (Keycode::X, 0),
+        (Keycode::C, 0xB),
+        (Keycode::V, 0xF),
+    ])
+}
+
+
+/// Parses a key map file. Each non-empty line holds an SDL key name and the hex CHIP-8 key
+/// it maps to, e.g. `Up 5`. Lines starting with `#` are ignored.
+pub fn parse_key_mapping(contents: &str) -> Result<HashMap<Keycode, u8>, String> {
+    let mut key_mapping = HashMap::new();
+
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key_name, key_value) = match line.rsplit_once(char::is_whitespace) {
+            Some((key_name, key_value)) => (key_name.trim(), key_value),
+            None => return Err(format!("line {}: expected `<key name> <CHIP-8 key>`", line_number + 1)),
+        };
+
+        let keycode = match Keycode::from_name(key_name) {
+            Some(keycode) => keycode,
+            None => return Err(format!("line {}: unknown key name '{}'", line_number + 1, key_name)),
+        };
+
+        match u8::from_str_radix(key_value, 16) {
+            Ok(value) if value <= 0xF => key_mapping.insert(keycode, value),
+            _ => return Err(format!("line {}: '{}' is not a CHIP-8 key (0-F)", line_number + 1, key_value)),
+        };
+    }
+
+    Ok(key_mapping)
 }